@@ -0,0 +1,36 @@
+const passwordResetForm = document.getElementById("password-reset-form");
+const passwordResetButton = document.getElementById("password-reset-form-submit");
+const passwordResetErrAlert = document.getElementById("password-reset-err-alert");
+
+const token = new URLSearchParams(window.location.search).get("token");
+
+passwordResetButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const newPassword = passwordResetForm.new_password.value;
+
+    fetch('/password-reset/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token, newPassword }),
+    }).then(response => {
+        if (response.ok) {
+            passwordResetForm.new_password.value = "";
+            passwordResetErrAlert.style.display = "none";
+            alert("Your password has been reset. You can now log in.");
+            window.location.replace("/");
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    passwordResetErrAlert.textContent = `Error: ${error_msg}`;
+                    passwordResetErrAlert.style.display = "block";
+                } else {
+                    passwordResetErrAlert.style.display = "none";
+                }
+            });
+        }
+    });
+});
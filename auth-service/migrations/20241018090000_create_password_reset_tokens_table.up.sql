@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    email TEXT NOT NULL REFERENCES users (email) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL
+);
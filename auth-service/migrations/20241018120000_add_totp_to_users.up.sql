@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'none'
+    CHECK (two_fa_method IN ('none', 'email', 'totp'));
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+ALTER TABLE users DROP COLUMN IF EXISTS requires_2fa;
+-- Encrypted with TOTP_ENCRYPTION_KEY, never stored in plain text.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret BYTEA;
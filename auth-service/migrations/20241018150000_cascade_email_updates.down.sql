@@ -0,0 +1,20 @@
+-- Add down migration script here
+ALTER TABLE password_reset_tokens
+    DROP CONSTRAINT IF EXISTS password_reset_tokens_email_fkey,
+    ADD CONSTRAINT password_reset_tokens_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE;
+
+ALTER TABLE refresh_token_families
+    DROP CONSTRAINT IF EXISTS refresh_token_families_email_fkey,
+    ADD CONSTRAINT refresh_token_families_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE;
+
+ALTER TABLE recovery_codes
+    DROP CONSTRAINT IF EXISTS recovery_codes_email_fkey,
+    ADD CONSTRAINT recovery_codes_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE;
+
+ALTER TABLE sessions
+    DROP CONSTRAINT IF EXISTS sessions_email_fkey,
+    ADD CONSTRAINT sessions_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE;
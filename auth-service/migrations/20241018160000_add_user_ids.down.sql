@@ -0,0 +1,44 @@
+-- Add down migration script here
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS email TEXT;
+ALTER TABLE refresh_token_families ADD COLUMN IF NOT EXISTS email TEXT;
+ALTER TABLE recovery_codes ADD COLUMN IF NOT EXISTS email TEXT;
+
+UPDATE sessions SET email = users.email FROM users WHERE users.id = sessions.user_id;
+UPDATE refresh_token_families SET email = users.email
+    FROM users WHERE users.id = refresh_token_families.user_id;
+UPDATE recovery_codes SET email = users.email FROM users WHERE users.id = recovery_codes.user_id;
+
+ALTER TABLE password_reset_tokens DROP CONSTRAINT IF EXISTS password_reset_tokens_email_fkey;
+ALTER TABLE sessions DROP COLUMN user_id;
+ALTER TABLE refresh_token_families DROP COLUMN user_id;
+ALTER TABLE recovery_codes DROP COLUMN user_id;
+
+ALTER TABLE users
+    DROP CONSTRAINT users_pkey,
+    DROP CONSTRAINT users_email_key,
+    ADD PRIMARY KEY (email),
+    DROP COLUMN id,
+    DROP COLUMN created_at,
+    DROP COLUMN updated_at;
+
+ALTER TABLE password_reset_tokens
+    ADD CONSTRAINT password_reset_tokens_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE sessions
+    ALTER COLUMN email SET NOT NULL,
+    ADD CONSTRAINT sessions_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE refresh_token_families
+    ALTER COLUMN email SET NOT NULL,
+    ADD CONSTRAINT refresh_token_families_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE recovery_codes
+    ALTER COLUMN email SET NOT NULL,
+    ADD CONSTRAINT recovery_codes_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions (email);
+CREATE INDEX IF NOT EXISTS recovery_codes_email_idx ON recovery_codes (email);
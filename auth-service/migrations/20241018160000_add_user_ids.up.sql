@@ -0,0 +1,60 @@
+-- Add up migration script here
+-- Identify users by a stable id so that rows owned by a user no longer
+-- depend on their email address.
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS id UUID,
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+UPDATE users SET id = gen_random_uuid() WHERE id IS NULL;
+
+ALTER TABLE users
+    ALTER COLUMN id SET NOT NULL,
+    ALTER COLUMN id SET DEFAULT gen_random_uuid();
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS user_id UUID;
+ALTER TABLE refresh_token_families ADD COLUMN IF NOT EXISTS user_id UUID;
+ALTER TABLE recovery_codes ADD COLUMN IF NOT EXISTS user_id UUID;
+
+UPDATE sessions SET user_id = users.id FROM users WHERE users.email = sessions.email;
+UPDATE refresh_token_families SET user_id = users.id
+    FROM users WHERE users.email = refresh_token_families.email;
+UPDATE recovery_codes SET user_id = users.id FROM users WHERE users.email = recovery_codes.email;
+
+-- The primary key moves to the id, so drop everything that hangs off it first
+ALTER TABLE password_reset_tokens DROP CONSTRAINT IF EXISTS password_reset_tokens_email_fkey;
+ALTER TABLE refresh_token_families DROP CONSTRAINT IF EXISTS refresh_token_families_email_fkey;
+ALTER TABLE recovery_codes DROP CONSTRAINT IF EXISTS recovery_codes_email_fkey;
+ALTER TABLE sessions DROP CONSTRAINT IF EXISTS sessions_email_fkey;
+
+ALTER TABLE users
+    DROP CONSTRAINT users_pkey,
+    ADD PRIMARY KEY (id),
+    ADD CONSTRAINT users_email_key UNIQUE (email);
+
+ALTER TABLE sessions
+    ALTER COLUMN user_id SET NOT NULL,
+    ADD CONSTRAINT sessions_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    DROP COLUMN email;
+
+ALTER TABLE refresh_token_families
+    ALTER COLUMN user_id SET NOT NULL,
+    ADD CONSTRAINT refresh_token_families_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    DROP COLUMN email;
+
+ALTER TABLE recovery_codes
+    ALTER COLUMN user_id SET NOT NULL,
+    ADD CONSTRAINT recovery_codes_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    DROP COLUMN email;
+
+CREATE INDEX IF NOT EXISTS sessions_user_id_idx ON sessions (user_id);
+CREATE INDEX IF NOT EXISTS refresh_token_families_user_id_idx ON refresh_token_families (user_id);
+CREATE INDEX IF NOT EXISTS recovery_codes_user_id_idx ON recovery_codes (user_id);
+
+-- Reset tokens are requested by email and still follow it
+ALTER TABLE password_reset_tokens
+    ADD CONSTRAINT password_reset_tokens_email_fkey
+        FOREIGN KEY (email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
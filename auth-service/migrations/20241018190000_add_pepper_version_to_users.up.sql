@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Version of PASSWORD_PEPPER the password was hashed with, NULL if it wasn't peppered.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS pepper_version INTEGER;
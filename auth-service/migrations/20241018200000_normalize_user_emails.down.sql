@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- Lowercased domains stay lowercased
+DROP INDEX IF EXISTS users_email_lower_key;
@@ -0,0 +1,23 @@
+-- Add up migration script here
+-- Email addresses are compared case-insensitively. Lowercase the domain of
+-- every address that doesn't collide with another account; the local part is
+-- kept as given. Collisions have to be resolved by hand first, and domains
+-- that aren't plain ASCII need converting to punycode, which
+-- check_email_duplicates lists and can do.
+UPDATE users
+    SET email = substring(email FROM '^(.*)@') || '@' || lower(substring(email FROM '@([^@]*)$'))
+    WHERE substring(email FROM '@([^@]*)$') <> lower(substring(email FROM '@([^@]*)$'))
+    AND substring(email FROM '@([^@]*)$') !~ '[^\x01-\x7f]'
+    AND NOT EXISTS (
+        SELECT 1 FROM users other
+        WHERE other.id <> users.id AND lower(other.email) = lower(users.email)
+    );
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users GROUP BY lower(email) HAVING count(*) > 1) THEN
+        RAISE EXCEPTION 'accounts share an email address up to case, run check_email_duplicates';
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_key ON users (lower(email));
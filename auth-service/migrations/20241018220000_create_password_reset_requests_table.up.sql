@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- When a password reset was last requested, keyed by address and by IP
+-- address, to throttle how often reset emails go out.
+CREATE TABLE IF NOT EXISTS password_reset_requests (
+    key TEXT NOT NULL PRIMARY KEY,
+    requested_at TIMESTAMPTZ NOT NULL
+);
@@ -1,32 +1,79 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::domain::{
+    BannedTokenStore, EmailChangeTokenStore, EmailClient, EmailVerificationTokenStore,
+    FailedLoginStore, MagicLinkTokenStore, PasskeyChallengeStore, PasskeyStore,
+    PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TwoFACodeStore,
+    UserStore,
+};
 
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore + Send + Sync>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<dyn PasswordResetTokenStore + Send + Sync>>;
+pub type EmailVerificationTokenStoreType =
+    Arc<RwLock<dyn EmailVerificationTokenStore + Send + Sync>>;
+pub type EmailChangeTokenStoreType = Arc<RwLock<dyn EmailChangeTokenStore + Send + Sync>>;
+pub type MagicLinkTokenStoreType = Arc<RwLock<dyn MagicLinkTokenStore + Send + Sync>>;
+pub type PasskeyStoreType = Arc<RwLock<dyn PasskeyStore + Send + Sync>>;
+pub type PasskeyChallengeStoreType = Arc<RwLock<dyn PasskeyChallengeStore + Send + Sync>>;
+pub type FailedLoginStoreType = Arc<RwLock<dyn FailedLoginStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub email_verification_token_store: EmailVerificationTokenStoreType,
+    pub email_change_token_store: EmailChangeTokenStoreType,
+    pub magic_link_token_store: MagicLinkTokenStoreType,
+    pub passkey_store: PasskeyStoreType,
+    pub passkey_challenge_store: PasskeyChallengeStoreType,
+    pub failed_login_store: FailedLoginStoreType,
     pub email_client: EmailClientType,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
 	user_store: UserStoreType,
 	banned_token_store: BannedTokenStoreType,
+	refresh_token_store: RefreshTokenStoreType,
+	session_store: SessionStoreType,
 	two_fa_code_store: TwoFACodeStoreType,
+	recovery_code_store: RecoveryCodeStoreType,
+	password_reset_token_store: PasswordResetTokenStoreType,
+	email_verification_token_store: EmailVerificationTokenStoreType,
+	email_change_token_store: EmailChangeTokenStoreType,
+	magic_link_token_store: MagicLinkTokenStoreType,
+	passkey_store: PasskeyStoreType,
+	passkey_challenge_store: PasskeyChallengeStoreType,
+	failed_login_store: FailedLoginStoreType,
 	email_client: EmailClientType,
     ) -> Self {
 	Self {
 	    user_store,
 	    banned_token_store,
+	    refresh_token_store,
+	    session_store,
 	    two_fa_code_store,
+	    recovery_code_store,
+	    password_reset_token_store,
+	    email_verification_token_store,
+	    email_change_token_store,
+	    magic_link_token_store,
+	    passkey_store,
+	    passkey_challenge_store,
+	    failed_login_store,
 	    email_client,
 	}
     }
@@ -0,0 +1,110 @@
+//! Lists accounts that stop being distinct once email addresses are compared
+//! case-insensitively, such as `Alice@Example.com` and `alice@example.com`,
+//! plus addresses whose domain isn't stored in canonical form. The migration
+//! making addresses case-insensitive refuses to run while such duplicates
+//! exist, and lowercases ASCII domains only, so Unicode domains have to be
+//! converted to punycode here.
+//!
+//! Nothing is changed unless run with `--fix`, which converts the domains of
+//! addresses that don't collide with another account. Exits with 1 while
+//! duplicates or addresses not in canonical form remain.
+
+use std::{collections::BTreeMap, env, process};
+
+use auth_service::{domain::Email, get_postgres_pool, utils::constants::DATABASE_URL};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+
+    let fix = env::args().any(|arg| arg == "--fix");
+
+    let pool = get_postgres_pool(&DATABASE_URL).await?;
+
+    let rows = sqlx::query!(
+        r#"SELECT id, email, deleted_at IS NOT NULL AS "deleted!"
+	       FROM users
+	       ORDER BY created_at"#
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut accounts = BTreeMap::<String, Vec<String>>::new();
+    let mut not_canonical = Vec::<(Uuid, String, String)>::new();
+
+    for row in rows {
+        let account = match row.deleted {
+            true => format!("{} {} (deleted)", row.id, row.email),
+            false => format!("{} {}", row.id, row.email),
+        };
+
+        let email = match Email::parse(Secret::new(row.email.clone())) {
+            Ok(email) => email,
+            Err(_) => {
+                println!("Invalid address: {}", account);
+                continue;
+            }
+        };
+
+        let canonical = email.as_ref().expose_secret().to_owned();
+
+        if canonical != row.email {
+            not_canonical.push((row.id, account.clone(), canonical));
+        }
+
+        accounts
+            .entry(email.lowercase().expose_secret().to_owned())
+            .or_default()
+            .push(account);
+    }
+
+    let duplicates: Vec<_> = accounts
+        .iter()
+        .filter(|(_, accounts)| accounts.len() > 1)
+        .collect();
+
+    let mut fixed = 0;
+
+    for (id, account, canonical) in &not_canonical {
+        let duplicated = duplicates
+            .iter()
+            .any(|(_, accounts)| accounts.contains(account));
+
+        if fix && !duplicated {
+            sqlx::query!(
+                r#"UPDATE users SET email = $2, updated_at = NOW() WHERE id = $1"#,
+                id,
+                canonical,
+            )
+            .execute(&pool)
+            .await?;
+
+            println!("Fixed: {} -> {}", account, canonical);
+            fixed += 1;
+        } else {
+            println!("Not in canonical form: {} -> {}", account, canonical);
+        }
+    }
+
+    for (lowercase, accounts) in &duplicates {
+        println!("Duplicates of {}:", lowercase);
+        for account in accounts.iter() {
+            println!("    {}", account);
+        }
+    }
+
+    println!(
+        "{} addresses not in canonical form ({} fixed), {} duplicated addresses",
+        not_canonical.len(),
+        fixed,
+        duplicates.len()
+    );
+
+    if !duplicates.is_empty() || fixed < not_canonical.len() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
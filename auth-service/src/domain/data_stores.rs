@@ -1,18 +1,82 @@
-use super::{Email, LoginAttemptId, Password, TwoFACode, User};
+use std::time::Duration;
 
+use super::{
+    CredentialId, Email, EmailChangeToken, EmailVerificationToken, FailedLoginKey, LoginAttemptId,
+    MagicLinkToken, Passkey, PasskeyCeremony, PasskeyChallenge, Password, PasswordResetToken,
+    RecoveryCode, RefreshToken, Session, SessionId, TotpSecret, TwoFACode, TwoFAMethod, User,
+    UserId,
+};
+
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::Report;
-use secrecy::Secret;
 use thiserror::Error;
 
 #[async_trait::async_trait]
 pub trait UserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, username: &Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
     async fn validate_user(
         &self,
         username: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError>;
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    /// Re-hashes the password if the stored hash was made with an older
+    /// algorithm or weaker parameters. Only call it with a password that was
+    /// just validated. Takes `&self` so logins only need a shared lock on the
+    /// store while the new hash is computed.
+    async fn upgrade_password_hash(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError>;
+    /// Moves the account to `new_email`, which counts as verified since the
+    /// change is confirmed from that address.
+    async fn update_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError>;
+    async fn set_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
+    /// Replaces the user's TOTP secret; `None` clears it.
+    async fn set_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: Option<TotpSecret>,
+    ) -> Result<(), UserStoreError>;
+    async fn get_totp_secret(&self, email: &Email) -> Result<Option<TotpSecret>, UserStoreError>;
+    /// Records `step` as the time step of the last TOTP code accepted for the
+    /// user. Returns `false` without recording it when it isn't later than
+    /// the one already recorded, which means the code was used before.
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError>;
+    /// Removes the user for good, whether or not they were marked as deleted.
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    /// Hides the user until they are restored or purged. Their email address
+    /// stays taken in the meantime.
+    async fn mark_user_deleted(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    /// Brings back a user hidden by [`UserStore::mark_user_deleted`] once
+    /// their password checks out.
+    async fn restore_user(
+        &mut self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError>;
+    /// Deletes users marked as deleted before `deleted_before` and returns
+    /// how many were removed.
+    async fn purge_deleted_users(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<usize, UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -39,13 +103,11 @@ impl PartialEq for UserStoreError {
     }
 }
 
+/// Auth tokens revoked before they expire, identified by their `jti` claim.
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add_banned_token(
-        &mut self,
-        token: Secret<String>,
-    ) -> Result<(), BannedTokenStoreError>;
-    async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError>;
+    async fn add_banned_token(&mut self, jti: &str) -> Result<(), BannedTokenStoreError>;
+    async fn is_banned_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -56,25 +118,132 @@ pub enum BannedTokenStoreError {
     UnexpectedError(#[source] Report),
 }
 
+/// Refresh tokens are grouped in families: every login starts a new family,
+/// identified by the id of the session it belongs to, and each rotation adds
+/// the next token to it. Presenting a token that was already rotated revokes
+/// the whole family.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        session_id: SessionId,
+        user_id: UserId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn rotate_token(
+        &mut self,
+        current: &RefreshToken,
+        next: RefreshToken,
+    ) -> Result<(UserId, SessionId), RefreshTokenStoreError>;
+    async fn revoke_family(&mut self, session_id: &SessionId)
+        -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    /// The token was already rotated, so its family was revoked. Carries the
+    /// owner, whose sessions are no longer trusted.
+    #[error("Refresh token reused")]
+    TokenReused(UserId),
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::TokenReused(_), Self::TokenReused(_))
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Sessions expire once unused for as long as a refresh token lives.
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError>;
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn remove_session(
+        &mut self,
+        user_id: &UserId,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError>;
+    /// Removes every session of the user and returns their ids.
+    async fn remove_sessions(
+        &mut self,
+        user_id: &UserId,
+    ) -> Result<Vec<SessionId>, SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Pending 2FA challenges, one per login attempt, so a user can sign in on
+/// several devices at once without one attempt replacing another's code.
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
+    /// Stores a challenge for the user. Once the user has too many
+    /// outstanding, their oldest ones are dropped.
     async fn add_code(
         &mut self,
-        email: Email,
+        user_id: UserId,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(UserId, TwoFACode), TwoFACodeStoreError>;
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+    /// Swaps the code of a pending challenge for one that is about to be
+    /// resent, failing with `ResendThrottled` if the last one went out too
+    /// recently or the challenge was resent too often. The challenge keeps
+    /// its expiry and its count of wrong codes.
+    async fn replace_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError>;
+    /// Drops every challenge pending for the user.
+    async fn remove_codes(&mut self, user_id: &UserId) -> Result<(), TwoFACodeStoreError>;
+    /// Counts a wrong code entered against the challenge and returns how many
+    /// there have been since it was issued.
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError>;
 }
 
 #[derive(Debug, Error)]
 pub enum TwoFACodeStoreError {
     #[error("Login attempt ID not found")]
     LoginAttemptIdNotFound,
+    #[error("2FA code was resent too recently or too often")]
+    ResendThrottled,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -84,7 +253,317 @@ impl PartialEq for TwoFACodeStoreError {
         matches!(
             (self, other),
             (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
+                | (Self::ResendThrottled, Self::ResendThrottled)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
 }
+
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    /// Looks up the account a token was issued for without invalidating it.
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+    /// Looks up the account a token was issued for and invalidates the token,
+    /// so every token can be redeemed at most once.
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+    /// Invalidates every outstanding token issued for `email`.
+    async fn remove_tokens(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError>;
+    /// Records that a reset was requested for `email` from `ip_address`,
+    /// failing with `ResendThrottled` if either requested one too recently.
+    async fn record_reset_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetTokenStoreError {
+    #[error("Password reset token not found")]
+    TokenNotFound,
+    #[error("Password reset was requested too recently")]
+    ResendThrottled,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasswordResetTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::ResendThrottled, Self::ResendThrottled)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait EmailVerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError>;
+    async fn consume_token(
+        &mut self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError>;
+    /// Records that a verification email is about to be sent to `email`,
+    /// failing with `ResendThrottled` if the previous one was sent too recently.
+    async fn record_email_sent(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), EmailVerificationTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum EmailVerificationTokenStoreError {
+    #[error("Email verification token not found")]
+    TokenNotFound,
+    #[error("Verification email was sent too recently")]
+    ResendThrottled,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for EmailVerificationTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::ResendThrottled, Self::ResendThrottled)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait EmailChangeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        new_email: Email,
+        token: EmailChangeToken,
+    ) -> Result<(), EmailChangeTokenStoreError>;
+    /// Returns the current and the requested address of the account and
+    /// invalidates the token.
+    async fn consume_token(
+        &mut self,
+        token: &EmailChangeToken,
+    ) -> Result<(Email, Email), EmailChangeTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum EmailChangeTokenStoreError {
+    #[error("Email change token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for EmailChangeTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait MagicLinkTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: MagicLinkToken,
+    ) -> Result<(), MagicLinkTokenStoreError>;
+    /// Returns the address the link was sent to and invalidates the token.
+    async fn consume_token(
+        &mut self,
+        token: &MagicLinkToken,
+    ) -> Result<Email, MagicLinkTokenStoreError>;
+    /// Records that a link was requested for `email` from `ip_address`,
+    /// failing with `ResendThrottled` if either requested one too recently.
+    async fn record_link_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), MagicLinkTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum MagicLinkTokenStoreError {
+    #[error("Magic link token not found")]
+    TokenNotFound,
+    #[error("Magic link was requested too recently")]
+    ResendThrottled,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for MagicLinkTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::ResendThrottled, Self::ResendThrottled)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyStore {
+    async fn add_passkey(&mut self, passkey: Passkey) -> Result<(), PasskeyStoreError>;
+    async fn get_passkey(&self, credential_id: &CredentialId)
+        -> Result<Passkey, PasskeyStoreError>;
+    async fn get_passkeys(&self, user_id: &UserId) -> Result<Vec<Passkey>, PasskeyStoreError>;
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), PasskeyStoreError>;
+    async fn remove_passkey(
+        &mut self,
+        user_id: &UserId,
+        credential_id: &CredentialId,
+    ) -> Result<(), PasskeyStoreError>;
+    async fn remove_passkeys(&mut self, user_id: &UserId) -> Result<(), PasskeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyStoreError {
+    #[error("Passkey already exists")]
+    PasskeyAlreadyExists,
+    #[error("Passkey not found")]
+    PasskeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::PasskeyAlreadyExists, Self::PasskeyAlreadyExists)
+                | (Self::PasskeyNotFound, Self::PasskeyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: PasskeyChallenge,
+        ceremony: PasskeyCeremony,
+    ) -> Result<(), PasskeyChallengeStoreError>;
+    /// Returns what the challenge was handed out for and invalidates it.
+    async fn consume_challenge(
+        &mut self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PasskeyCeremony, PasskeyChallengeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyChallengeStoreError {
+    #[error("Passkey challenge not found")]
+    ChallengeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyChallengeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ChallengeNotFound, Self::ChallengeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    /// Replaces every recovery code of the user with `codes`; an empty set
+    /// removes them all.
+    async fn replace_codes(
+        &mut self,
+        user_id: &UserId,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    /// Checks `code` against the user's remaining codes and burns it if it
+    /// matches.
+    async fn consume_code(
+        &mut self,
+        user_id: &UserId,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    async fn count_codes(&self, user_id: &UserId) -> Result<usize, RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryCodeStoreError {
+    #[error("Recovery code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RecoveryCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait FailedLoginStore {
+    /// Counts a failed login and returns how many there have been since the
+    /// last successful one. Counts are forgotten after a while without
+    /// failures.
+    async fn record_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError>;
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError>;
+    async fn lock(
+        &mut self,
+        key: &FailedLoginKey,
+        duration: Duration,
+    ) -> Result<(), FailedLoginStoreError>;
+    /// Returns how much longer logins stay blocked, if they are.
+    async fn get_lockout(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<Duration>, FailedLoginStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum FailedLoginStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for FailedLoginStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
@@ -8,9 +8,11 @@ use validator::ValidateEmail;
 #[derive(Debug, Clone)]
 pub struct Email(Secret<String>);
 
+// Mail providers don't treat the local part as case-sensitive in practice, so
+// spellings that differ only in case refer to the same account.
 impl Hash for Email {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.expose_secret().hash(state);
+        self.0.expose_secret().to_lowercase().hash(state);
     }
 }
 
@@ -18,7 +20,7 @@ impl Eq for Email {}
 
 impl PartialEq for Email {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        self.0.expose_secret().to_lowercase() == other.0.expose_secret().to_lowercase()
     }
 }
 
@@ -29,13 +31,32 @@ impl AsRef<Secret<String>> for Email {
 }
 
 impl Email {
+    /// Validates the address and brings its domain into canonical form,
+    /// lowercased and converted to punycode. The local part is kept as given,
+    /// since that is what mail has to be sent to; accounts are compared by
+    /// the lowercased address instead, see [`Email::lowercase`].
     pub fn parse(email: Secret<String>) -> Result<Self> {
         if ValidateEmail::validate_email(&email.expose_secret()) {
-            Ok(Self(email))
+            Ok(Self(Secret::new(normalize(email.expose_secret()))))
         } else {
             Err(eyre!("Invalid email {}", email.expose_secret()))
         }
     }
+
+    /// The whole address lowercased, for keying anything that belongs to the
+    /// account rather than to this particular spelling of it.
+    pub fn lowercase(&self) -> Secret<String> {
+        Secret::new(self.0.expose_secret().to_lowercase())
+    }
+}
+
+fn normalize(email: &str) -> String {
+    // Validation guarantees an `@`; quoted local parts may contain more of them
+    let (local_part, domain) = email.rsplit_once('@').unwrap_or(("", email));
+    // IP literals like `[127.0.0.1]` aren't domain names
+    let domain = idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_lowercase());
+
+    format!("{}@{}", local_part, domain)
 }
 
 #[cfg(test)]
@@ -43,8 +64,10 @@ extern crate quickcheck;
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use quickcheck::Gen;
-    use secrecy::Secret;
+    use secrecy::{ExposeSecret, Secret};
 
     use super::Email;
 
@@ -74,6 +97,30 @@ mod tests {
         assert!(Email::parse(email).is_err());
     }
 
+    #[test]
+    fn lowercases_domain_only() {
+        let email = Email::parse(Secret::new("Alice@Example.COM".to_owned())).unwrap();
+
+        assert_eq!(email.as_ref().expose_secret(), "Alice@example.com");
+        assert_eq!(email.lowercase().expose_secret(), "alice@example.com");
+    }
+
+    #[test]
+    fn compares_case_insensitively() {
+        let email = Email::parse(Secret::new("Alice@Example.COM".to_owned())).unwrap();
+        let other = Email::parse(Secret::new("alice@example.com".to_owned())).unwrap();
+
+        assert_eq!(email, other);
+        assert!(HashSet::from([email]).contains(&other));
+    }
+
+    #[test]
+    fn converts_domain_to_punycode() {
+        let email = Email::parse(Secret::new("user@Bücher.example".to_owned())).unwrap();
+
+        assert_eq!(email.as_ref().expose_secret(), "user@xn--bcher-kva.example");
+    }
+
     #[derive(Debug, Clone)]
     struct ValidEmail(pub String);
 
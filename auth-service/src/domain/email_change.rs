@@ -0,0 +1,59 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use super::random_token::{generate_random_token, hash_random_token, is_valid_random_token};
+
+#[derive(Debug, Clone)]
+pub struct EmailChangeToken(Secret<String>);
+
+impl PartialEq for EmailChangeToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl EmailChangeToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid email change token"))
+        }
+    }
+
+    /// Digest used as the storage key in place of the token itself.
+    pub fn hash(&self) -> String {
+        hash_random_token(&self.0)
+    }
+}
+
+impl Default for EmailChangeToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl AsRef<Secret<String>> for EmailChangeToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::EmailChangeToken;
+
+    #[test]
+    fn empty_string() {
+        let token = Secret::new("".to_owned());
+        assert!(EmailChangeToken::parse(token).is_err());
+    }
+
+    #[test]
+    fn default_token_is_valid() {
+        let token = EmailChangeToken::default();
+        assert!(EmailChangeToken::parse(token.as_ref().clone()).is_ok());
+    }
+}
@@ -0,0 +1,71 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use super::random_token::{generate_random_token, hash_random_token, is_valid_random_token};
+
+#[derive(Debug, Clone)]
+pub struct EmailVerificationToken(Secret<String>);
+
+impl PartialEq for EmailVerificationToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl EmailVerificationToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid email verification token"))
+        }
+    }
+
+    /// Digest used as the storage key in place of the token itself.
+    pub fn hash(&self) -> String {
+        hash_random_token(&self.0)
+    }
+}
+
+impl Default for EmailVerificationToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl AsRef<Secret<String>> for EmailVerificationToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::EmailVerificationToken;
+
+    #[test]
+    fn empty_string() {
+        let token = Secret::new("".to_owned());
+        assert!(EmailVerificationToken::parse(token).is_err());
+    }
+
+    #[test]
+    fn not_alphanumeric() {
+        let token = Secret::new(format!("{}/", "a".repeat(63)));
+        assert!(EmailVerificationToken::parse(token).is_err());
+    }
+
+    #[test]
+    fn default_token_is_valid() {
+        let token = EmailVerificationToken::default();
+        assert!(EmailVerificationToken::parse(token.as_ref().clone()).is_ok());
+    }
+
+    #[test]
+    fn hash_hides_token() {
+        let token = EmailVerificationToken::default();
+        assert_ne!(&token.hash(), token.as_ref().expose_secret());
+    }
+}
@@ -1,18 +1,64 @@
+use std::time::Duration;
+
 use color_eyre::eyre::Report;
 use thiserror::Error;
 
+use super::PasswordViolation;
+
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
     #[error("User already exists")]
     UserAlreadyExists,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    /// Lists every rule the new password breaks.
+    #[error("Password policy violated")]
+    PasswordPolicyViolation(Vec<PasswordViolation>),
     #[error("Incorrect credentials")]
     IncorrectCredentials,
     #[error("Missing token")]
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Revoked token")]
+    RevokedToken,
+    #[error("Missing refresh token")]
+    MissingRefreshToken,
+    #[error("Invalid refresh token")]
+    InvalidRefreshToken,
+    #[error("Invalid password reset token")]
+    InvalidPasswordResetToken,
+    #[error("Email not verified")]
+    EmailNotVerified,
+    #[error("Invalid email verification token")]
+    InvalidEmailVerificationToken,
+    #[error("Invalid email change token")]
+    InvalidEmailChangeToken,
+    #[error("Invalid magic link token")]
+    InvalidMagicLinkToken,
+    #[error("Invalid passkey")]
+    InvalidPasskey,
+    #[error("Passkey already registered")]
+    PasskeyAlreadyRegistered,
+    #[error("No passkey registered")]
+    NoPasskeyRegistered,
+    #[error("Passkey not found")]
+    PasskeyNotFound,
+    #[error("Too many requests")]
+    TooManyRequests,
+    /// Carries how long the client has to wait before trying again.
+    #[error("Too many login attempts")]
+    TooManyLoginAttempts(Duration),
+    #[error("TOTP already enabled")]
+    TotpAlreadyEnabled,
+    #[error("TOTP not enrolled")]
+    TotpNotEnrolled,
+    #[error("TOTP not enabled")]
+    TotpNotEnabled,
+    #[error("2FA not enabled")]
+    TwoFANotEnabled,
+    #[error("Session not found")]
+    SessionNotFound,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
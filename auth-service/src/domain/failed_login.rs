@@ -0,0 +1,87 @@
+use std::{fmt, time::Duration};
+
+use secrecy::ExposeSecret;
+
+use super::Email;
+
+/// What failed logins are counted against. Counting per account stops guessing
+/// at one password from many addresses; counting per IP address stops one
+/// client from spraying guesses across many accounts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FailedLoginKey {
+    Account(Email),
+    IpAddress(String),
+}
+
+impl fmt::Display for FailedLoginKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Account(email) => write!(f, "account:{}", email.lowercase().expose_secret()),
+            Self::IpAddress(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+/// How long logins stay blocked after repeated failures. Nothing happens below
+/// the threshold; from there on every further failure doubles the lockout, up
+/// to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub threshold: u32,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn lockout_for(&self, failures: u32) -> Option<Duration> {
+        if self.threshold == 0 || failures < self.threshold {
+            return None;
+        }
+
+        let doublings = (failures - self.threshold).min(31);
+
+        Some(self.base.saturating_mul(1 << doublings).min(self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::LockoutPolicy;
+
+    const POLICY: LockoutPolicy = LockoutPolicy {
+        threshold: 3,
+        base: Duration::from_secs(60),
+        max: Duration::from_secs(600),
+    };
+
+    #[test]
+    fn no_lockout_below_threshold() {
+        assert_eq!(POLICY.lockout_for(0), None);
+        assert_eq!(POLICY.lockout_for(2), None);
+    }
+
+    #[test]
+    fn lockout_doubles_with_each_failure() {
+        assert_eq!(POLICY.lockout_for(3), Some(Duration::from_secs(60)));
+        assert_eq!(POLICY.lockout_for(4), Some(Duration::from_secs(120)));
+        assert_eq!(POLICY.lockout_for(5), Some(Duration::from_secs(240)));
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_eq!(POLICY.lockout_for(7), Some(Duration::from_secs(600)));
+        assert_eq!(POLICY.lockout_for(u32::MAX), Some(Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn zero_threshold_disables_lockout() {
+        let policy = LockoutPolicy {
+            threshold: 0,
+            ..POLICY
+        };
+
+        assert_eq!(policy.lockout_for(100), None);
+    }
+}
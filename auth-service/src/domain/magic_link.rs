@@ -0,0 +1,59 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use super::random_token::{generate_random_token, hash_random_token, is_valid_random_token};
+
+#[derive(Debug, Clone)]
+pub struct MagicLinkToken(Secret<String>);
+
+impl PartialEq for MagicLinkToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl MagicLinkToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid magic link token"))
+        }
+    }
+
+    /// Digest used as the storage key in place of the token itself.
+    pub fn hash(&self) -> String {
+        hash_random_token(&self.0)
+    }
+}
+
+impl Default for MagicLinkToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl AsRef<Secret<String>> for MagicLinkToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::MagicLinkToken;
+
+    #[test]
+    fn empty_string() {
+        let token = Secret::new("".to_owned());
+        assert!(MagicLinkToken::parse(token).is_err());
+    }
+
+    #[test]
+    fn default_token_is_valid() {
+        let token = MagicLinkToken::default();
+        assert!(MagicLinkToken::parse(token.as_ref().clone()).is_ok());
+    }
+}
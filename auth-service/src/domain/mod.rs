@@ -1,15 +1,38 @@
 mod data_stores;
 mod email;
+mod email_change;
 mod email_client;
+mod email_verification;
 mod error;
+mod failed_login;
+mod magic_link;
+mod passkey;
 mod password;
+mod password_policy;
+mod password_reset;
+mod random_token;
+mod recovery_code;
+mod refresh_token;
+mod session;
+mod totp;
 mod two_factor;
 mod user;
 
 pub use data_stores::*;
 pub use email::*;
+pub use email_change::*;
 pub use email_client::*;
+pub use email_verification::*;
 pub use error::*;
+pub use failed_login::*;
+pub use magic_link::*;
+pub use passkey::*;
 pub use password::*;
+pub use password_policy::*;
+pub use password_reset::*;
+pub use recovery_code::*;
+pub use refresh_token::*;
+pub use session::*;
+pub use totp::*;
 pub use two_factor::*;
 pub use user::*;
@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use data_encoding::BASE64URL_NOPAD;
+use rand::RngCore;
+
+use super::{LoginAttemptId, UserId};
+
+const PASSKEY_CHALLENGE_LENGTH: usize = 32;
+// Authenticators may use up to 1023 bytes for a credential ID
+const MAX_CREDENTIAL_ID_LENGTH: usize = 1023;
+
+/// ID the authenticator assigned to a passkey. Travels base64url encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CredentialId(Vec<u8>);
+
+impl CredentialId {
+    pub fn parse(id: &str) -> Result<Self> {
+        let id = BASE64URL_NOPAD
+            .decode(id.as_bytes())
+            .wrap_err("Invalid credential id")?;
+        Self::from_bytes(id)
+    }
+
+    pub fn from_bytes(id: Vec<u8>) -> Result<Self> {
+        if id.is_empty() || id.len() > MAX_CREDENTIAL_ID_LENGTH {
+            return Err(eyre!("Invalid credential id"));
+        }
+        Ok(Self(id))
+    }
+
+    pub fn encoded(&self) -> String {
+        BASE64URL_NOPAD.encode(&self.0)
+    }
+}
+
+impl AsRef<[u8]> for CredentialId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A registered WebAuthn credential. Only ES256 keys are accepted, kept as
+/// an uncompressed SEC1 point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passkey {
+    pub credential_id: CredentialId,
+    pub user_id: UserId,
+    pub public_key: Vec<u8>,
+    /// Signature counter reported by the authenticator on its latest use.
+    /// Authenticators that don't keep one always report 0.
+    pub sign_count: u32,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Random value an authenticator has to sign to prove it holds a passkey.
+/// Travels base64url encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PasskeyChallenge(Vec<u8>);
+
+impl PasskeyChallenge {
+    pub fn parse(challenge: &str) -> Result<Self> {
+        let challenge = BASE64URL_NOPAD
+            .decode(challenge.as_bytes())
+            .wrap_err("Invalid passkey challenge")?;
+
+        if challenge.len() == PASSKEY_CHALLENGE_LENGTH {
+            Ok(Self(challenge))
+        } else {
+            Err(eyre!("Invalid passkey challenge"))
+        }
+    }
+
+    pub fn encoded(&self) -> String {
+        BASE64URL_NOPAD.encode(&self.0)
+    }
+}
+
+impl Default for PasskeyChallenge {
+    fn default() -> Self {
+        let mut challenge = vec![0; PASSKEY_CHALLENGE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut challenge);
+        Self(challenge)
+    }
+}
+
+/// What a challenge was handed out for, so a response to it can only finish
+/// the ceremony it was started for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasskeyCeremony {
+    /// Adding a passkey to the account of a logged in user.
+    Registration { user_id: UserId },
+    /// Logging in with a passkey in place of the password.
+    Login,
+    /// Using a passkey as the second factor of a pending login attempt.
+    SecondFactor {
+        user_id: UserId,
+        login_attempt_id: LoginAttemptId,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_id_round_trip() {
+        let id = CredentialId::from_bytes(vec![1, 2, 3, 255]).unwrap();
+        assert_eq!(CredentialId::parse(&id.encoded()).unwrap(), id);
+    }
+
+    #[test]
+    fn invalid_credential_id() {
+        assert!(CredentialId::parse("").is_err());
+        assert!(CredentialId::parse("not base64!").is_err());
+        assert!(CredentialId::from_bytes(vec![0; MAX_CREDENTIAL_ID_LENGTH + 1]).is_err());
+    }
+
+    #[test]
+    fn default_challenge_is_valid() {
+        let challenge = PasskeyChallenge::default();
+        assert_eq!(
+            PasskeyChallenge::parse(&challenge.encoded()).unwrap(),
+            challenge
+        );
+        assert_ne!(challenge, PasskeyChallenge::default());
+    }
+
+    #[test]
+    fn wrong_challenge_length() {
+        let challenge = BASE64URL_NOPAD.encode(&[0; PASSKEY_CHALLENGE_LENGTH - 1]);
+        assert!(PasskeyChallenge::parse(&challenge).is_err());
+    }
+}
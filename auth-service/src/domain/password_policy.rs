@@ -0,0 +1,391 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use data_encoding::HEXUPPER_PERMISSIVE;
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+
+use super::Email;
+
+/// A rule a new password breaks. Every violation is reported at once so
+/// clients can show the user everything that needs fixing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordViolation {
+    TooShort(usize),
+    TooLong(usize),
+    MissingLowercase,
+    MissingUppercase,
+    MissingDigit,
+    MissingSymbol,
+    TooWeak,
+    ContainsEmail,
+    Breached,
+}
+
+impl PasswordViolation {
+    /// Stable identifier clients can match on instead of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TooShort(_) => "too_short",
+            Self::TooLong(_) => "too_long",
+            Self::MissingLowercase => "missing_lowercase",
+            Self::MissingUppercase => "missing_uppercase",
+            Self::MissingDigit => "missing_digit",
+            Self::MissingSymbol => "missing_symbol",
+            Self::TooWeak => "too_weak",
+            Self::ContainsEmail => "contains_email",
+            Self::Breached => "breached",
+        }
+    }
+}
+
+impl fmt::Display for PasswordViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort(min) => write!(f, "Password must be at least {} characters long", min),
+            Self::TooLong(max) => write!(f, "Password must be at most {} characters long", max),
+            Self::MissingLowercase => write!(f, "Password must contain a lowercase letter"),
+            Self::MissingUppercase => write!(f, "Password must contain an uppercase letter"),
+            Self::MissingDigit => write!(f, "Password must contain a digit"),
+            Self::MissingSymbol => write!(f, "Password must contain a symbol"),
+            Self::TooWeak => write!(f, "Password is too easy to guess"),
+            Self::ContainsEmail => write!(f, "Password must not contain the email address"),
+            Self::Breached => write!(f, "Password has appeared in a data breach"),
+        }
+    }
+}
+
+/// Rules a password has to follow when it is set. Passwords already stored
+/// keep working when the rules get stricter.
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Lowest accepted result of `estimate_strength`, from 0 to 4.
+    pub min_strength: u8,
+    /// Rejects passwords containing the local part of the account's email.
+    pub reject_email: bool,
+    pub breached_passwords: Option<BreachedPasswords>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            min_strength: 0,
+            reject_email: false,
+            breached_passwords: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn check(
+        &self,
+        password: &Secret<String>,
+        email: &Email,
+    ) -> Result<(), Vec<PasswordViolation>> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+        let mut violations = Vec::new();
+
+        if length < self.min_length {
+            violations.push(PasswordViolation::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            violations.push(PasswordViolation::TooLong(self.max_length));
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordViolation::MissingLowercase);
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordViolation::MissingUppercase);
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PasswordViolation::MissingDigit);
+        }
+        if self.require_symbol && !password.chars().any(is_symbol) {
+            violations.push(PasswordViolation::MissingSymbol);
+        }
+        if estimate_strength(password) < self.min_strength {
+            violations.push(PasswordViolation::TooWeak);
+        }
+        if self.reject_email && contains_email(password, email) {
+            violations.push(PasswordViolation::ContainsEmail);
+        }
+        if self
+            .breached_passwords
+            .as_ref()
+            .is_some_and(|breached| breached.contains(password))
+        {
+            violations.push(PasswordViolation::Breached);
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+}
+
+/// SHA-1 digests of passwords known from breaches, as published by Have I
+/// Been Pwned: one hex digest per line, optionally followed by `:count`.
+/// Kept sorted so lookups are a binary search.
+#[derive(Default)]
+pub struct BreachedPasswords(Vec<[u8; 20]>);
+
+impl BreachedPasswords {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .wrap_err_with(|| format!("failed to open breached password list {:?}", path))?;
+
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let mut digests = Vec::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.wrap_err("failed to read breached password list")?;
+            let hex = line.split(':').next().unwrap_or_default().trim();
+
+            if hex.is_empty() {
+                continue;
+            }
+
+            let digest = HEXUPPER_PERMISSIVE
+                .decode(hex.as_bytes())
+                .ok()
+                .and_then(|digest| <[u8; 20]>::try_from(digest).ok())
+                .ok_or_else(|| eyre!("invalid SHA-1 digest on line {}", number + 1))?;
+
+            digests.push(digest);
+        }
+
+        digests.sort_unstable();
+        digests.dedup();
+
+        Ok(Self(digests))
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        let digest: [u8; 20] = Sha1::digest(password.as_bytes()).into();
+
+        self.0.binary_search(&digest).is_ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BreachedPasswords({} digests)", self.0.len())
+    }
+}
+
+/// Rough guess at how hard a password is to brute force, from 0 (trivial) to
+/// 4 (strong). Counts the bits an attacker has to search given the character
+/// classes used, ignoring characters that repeat or continue a sequence from
+/// the one before, such as the `bc` in `abc` or the second `1` in `11`.
+pub fn estimate_strength(password: &str) -> u8 {
+    let mut pool = 0;
+
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii() && is_symbol(c)) {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+
+    let mut previous: Option<char> = None;
+    let mut effective_length = 0;
+
+    for c in password.chars() {
+        let predictable = previous
+            .is_some_and(|previous| (c as i64 - previous as i64).abs() <= 1 && c.is_alphanumeric());
+
+        if !predictable {
+            effective_length += 1;
+        }
+        previous = Some(c);
+    }
+
+    let bits = effective_length as f64 * f64::from(pool.max(1)).log2();
+
+    match bits {
+        bits if bits < 28.0 => 0,
+        bits if bits < 36.0 => 1,
+        bits if bits < 60.0 => 2,
+        bits if bits < 80.0 => 3,
+        _ => 4,
+    }
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric()
+}
+
+fn contains_email(password: &str, email: &Email) -> bool {
+    let email = email.as_ref().expose_secret().to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+
+    // Very short local parts would reject too many unrelated passwords
+    local_part.chars().count() >= 3 && password.to_lowercase().contains(local_part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap()
+    }
+
+    fn password(password: &str) -> Secret<String> {
+        Secret::new(password.to_owned())
+    }
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 64,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_password_meeting_policy() {
+        assert_eq!(policy().check(&password("password123"), &email()), Ok(()));
+    }
+
+    #[test]
+    fn checks_length() {
+        assert_eq!(
+            policy().check(&password("short"), &email()),
+            Err(vec![PasswordViolation::TooShort(8)])
+        );
+        assert_eq!(
+            policy().check(&password(&"a".repeat(65)), &email()),
+            Err(vec![PasswordViolation::TooLong(64)])
+        );
+        // Length counts characters, not bytes
+        assert_eq!(policy().check(&password("pässwörd"), &email()), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_missing_character_class() {
+        let policy = PasswordPolicy {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..policy()
+        };
+
+        assert_eq!(
+            policy.check(&password("abcdefghij"), &email()),
+            Err(vec![
+                PasswordViolation::MissingUppercase,
+                PasswordViolation::MissingDigit,
+                PasswordViolation::MissingSymbol,
+            ])
+        );
+        assert_eq!(policy.check(&password("Abcdefgh1!"), &email()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_weak_password() {
+        let policy = PasswordPolicy {
+            min_strength: 2,
+            ..policy()
+        };
+
+        assert_eq!(
+            policy.check(&password("aaaaaaaaaaaa"), &email()),
+            Err(vec![PasswordViolation::TooWeak])
+        );
+        assert_eq!(
+            policy.check(&password("abcdefgh1234"), &email()),
+            Err(vec![PasswordViolation::TooWeak])
+        );
+        assert_eq!(policy.check(&password("password123"), &email()), Ok(()));
+    }
+
+    #[test]
+    fn strength_grows_with_length_and_variety() {
+        assert_eq!(estimate_strength("12345678"), 0);
+        assert!(estimate_strength("password123") < estimate_strength("Tr0ub4dor&3"));
+        assert_eq!(estimate_strength("correct horse battery staple"), 4);
+    }
+
+    #[test]
+    fn rejects_password_containing_email() {
+        let policy = PasswordPolicy {
+            reject_email: true,
+            ..policy()
+        };
+
+        assert_eq!(
+            policy.check(&password("JohnDoe2024"), &email()),
+            Err(vec![PasswordViolation::ContainsEmail])
+        );
+        assert_eq!(policy.check(&password("password123"), &email()), Ok(()));
+
+        let short_email = Email::parse(Secret::new("jd@example.com".to_owned())).unwrap();
+
+        assert_eq!(policy.check(&password("jdpassword"), &short_email), Ok(()));
+    }
+
+    #[test]
+    fn rejects_breached_password() {
+        // SHA-1 of "password123" and "letmein", in HIBP format
+        let list = "CBFDAC6008F9CAB4083784CBD1874F76618D2A97:2400000\n\
+                    b7a875fc1ea228b9061041b7cec4bd3c52ab3ce3:70000\n";
+        let breached = BreachedPasswords::from_reader(list.as_bytes()).unwrap();
+        let policy = PasswordPolicy {
+            breached_passwords: Some(breached),
+            ..policy()
+        };
+
+        assert_eq!(
+            policy.check(&password("password123"), &email()),
+            Err(vec![PasswordViolation::Breached])
+        );
+        assert_eq!(policy.check(&password("password124"), &email()), Ok(()));
+    }
+
+    #[test]
+    fn rejects_malformed_breached_password_list() {
+        assert!(BreachedPasswords::from_reader("not-a-digest:1\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use rand::{distributions::Alphanumeric, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+/// Length of the opaque tokens handed out in emails and cookies.
+pub(crate) const RANDOM_TOKEN_LENGTH: usize = 64;
+
+pub(crate) fn generate_random_token() -> Secret<String> {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(RANDOM_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    Secret::new(token)
+}
+
+pub(crate) fn is_valid_random_token(token: &Secret<String>) -> bool {
+    let value = token.expose_secret();
+    value.len() == RANDOM_TOKEN_LENGTH && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// SHA-256 digest of a token. Stores only ever see this value, so a leaked
+/// store can't be replayed.
+pub(crate) fn hash_random_token(token: &Secret<String>) -> String {
+    format!("{:x}", Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::*;
+
+    #[test]
+    fn generated_token_is_valid() {
+        assert!(is_valid_random_token(&generate_random_token()));
+    }
+
+    #[test]
+    fn rejects_wrong_length_or_charset() {
+        assert!(!is_valid_random_token(&Secret::new("".to_owned())));
+        assert!(!is_valid_random_token(&Secret::new("a".repeat(63))));
+        assert!(!is_valid_random_token(&Secret::new(format!(
+            "{}-",
+            "a".repeat(63)
+        ))));
+    }
+
+    #[test]
+    fn hash_is_deterministic_and_hides_token() {
+        let token = generate_random_token();
+        assert_eq!(hash_random_token(&token), hash_random_token(&token));
+        assert_ne!(&hash_random_token(&token), token.expose_secret());
+        assert_ne!(
+            hash_random_token(&token),
+            hash_random_token(&generate_random_token())
+        );
+    }
+}
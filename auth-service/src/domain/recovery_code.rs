@@ -0,0 +1,120 @@
+use color_eyre::eyre::{eyre, Result};
+use rand::{distributions::Slice, Rng};
+use secrecy::{ExposeSecret, Secret};
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const RECOVERY_CODE_LENGTH: usize = 10;
+// Lowercase letters and digits without the easily confused 0/o and 1/l
+const RECOVERY_CODE_ALPHABET: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'm', 'n', 'p', 'q', 'r', 's', 't', 'u',
+    'v', 'w', 'x', 'y', 'z', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// One-time code that stands in for a 2FA code when the usual second factor
+/// is unavailable. Held without the hyphen that is shown to users.
+#[derive(Debug, Clone)]
+pub struct RecoveryCode(Secret<String>);
+
+impl PartialEq for RecoveryCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RecoveryCode {
+    /// Accepts codes as displayed (`abcde-fghij`) as well as without the
+    /// hyphen or in upper case, since they are typed in by hand.
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        let normalized: String = code
+            .expose_secret()
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if normalized.len() == RECOVERY_CODE_LENGTH
+            && normalized
+                .chars()
+                .all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+        {
+            Ok(Self(Secret::new(normalized)))
+        } else {
+            Err(eyre!("Invalid recovery code"))
+        }
+    }
+
+    /// A fresh set of codes to hand out in one go.
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT).map(|_| Self::default()).collect()
+    }
+
+    /// The code as shown to the user, split in two for readability.
+    pub fn formatted(&self) -> String {
+        let code = self.0.expose_secret();
+        let (first, second) = code.split_at(RECOVERY_CODE_LENGTH / 2);
+        format!("{}-{}", first, second)
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let alphabet = Slice::new(RECOVERY_CODE_ALPHABET).expect("alphabet is not empty");
+        let code = rand::thread_rng()
+            .sample_iter(alphabet)
+            .take(RECOVERY_CODE_LENGTH)
+            .collect();
+        Self(Secret::new(code))
+    }
+}
+
+impl AsRef<Secret<String>> for RecoveryCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::*;
+
+    #[test]
+    fn empty_string() {
+        assert!(RecoveryCode::parse(Secret::new("".to_owned())).is_err());
+    }
+
+    #[test]
+    fn wrong_length() {
+        assert!(RecoveryCode::parse(Secret::new("abcde-fghi".to_owned())).is_err());
+    }
+
+    #[test]
+    fn ambiguous_characters() {
+        assert!(RecoveryCode::parse(Secret::new("abcde-fgh0l".to_owned())).is_err());
+    }
+
+    #[test]
+    fn normalizes_input() {
+        let code = RecoveryCode::parse(Secret::new(" ABCDE-fghjk ".to_owned())).unwrap();
+        assert_eq!(code.as_ref().expose_secret(), "abcdefghjk");
+        assert_eq!(code.formatted(), "abcde-fghjk");
+    }
+
+    #[test]
+    fn default_code_is_valid() {
+        let code = RecoveryCode::default();
+        assert_eq!(
+            RecoveryCode::parse(Secret::new(code.formatted())).unwrap(),
+            code
+        );
+    }
+
+    #[test]
+    fn generate_set() {
+        let codes = RecoveryCode::generate_set();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+    }
+}
@@ -0,0 +1,59 @@
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use super::random_token::{generate_random_token, hash_random_token, is_valid_random_token};
+
+#[derive(Debug, Clone)]
+pub struct RefreshToken(Secret<String>);
+
+impl PartialEq for RefreshToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RefreshToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_valid_random_token(&token) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+
+    /// Digest used as the storage key in place of the token itself.
+    pub fn hash(&self) -> String {
+        hash_random_token(&self.0)
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        Self(generate_random_token())
+    }
+}
+
+impl AsRef<Secret<String>> for RefreshToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::RefreshToken;
+
+    #[test]
+    fn empty_string() {
+        let token = Secret::new("".to_owned());
+        assert!(RefreshToken::parse(token).is_err());
+    }
+
+    #[test]
+    fn default_token_is_valid() {
+        let token = RefreshToken::default();
+        assert!(RefreshToken::parse(token.as_ref().clone()).is_ok());
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use uuid::Uuid;
+
+use super::UserId;
+
+/// Identifies one logged-in device or browser. Unlike the tokens it is not a
+/// secret: it is shown to the user so a session can be picked for revocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId(Uuid);
+
+impl SessionId {
+    pub fn parse(id: &str) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(id).wrap_err("Invalid session id")?;
+        Ok(Self(parsed_id))
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl AsRef<Uuid> for SessionId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl From<Uuid> for SessionId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl Session {
+    pub fn new(user_id: UserId, user_agent: Option<String>, ip_address: Option<String>) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: SessionId::default(),
+            user_id,
+            created_at: now,
+            last_seen: now,
+            user_agent,
+            ip_address,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionId;
+
+    #[test]
+    fn invalid_session_id() {
+        assert!(SessionId::parse("").is_err());
+        assert!(SessionId::parse("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn session_id_round_trip() {
+        let id = SessionId::default();
+        assert_eq!(SessionId::parse(&id.to_string()).unwrap(), id);
+    }
+}
@@ -0,0 +1,219 @@
+use color_eyre::eyre::{eyre, Context, Result};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+
+use super::{Email, TwoFACode};
+
+pub const TOTP_ISSUER: &str = "AuthService";
+pub const TOTP_STEP_SECONDS: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+/// Number of steps either side of the current one that are still accepted,
+/// to absorb clock drift between the server and the authenticator app.
+pub const TOTP_ALLOWED_SKEW_STEPS: u64 = 1;
+
+const TOTP_SECRET_LENGTH: usize = 20; // 160 bits, as recommended by RFC 4226
+const TOTP_MIN_SECRET_LENGTH: usize = 16;
+
+/// Shared secret for an authenticator app, kept in its base32 form since
+/// that is what both the `otpauth://` URI and manual entry expect.
+#[derive(Debug, Clone)]
+pub struct TotpSecret(Secret<String>);
+
+impl PartialEq for TotpSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl TotpSecret {
+    pub fn parse(secret: Secret<String>) -> Result<Self> {
+        let bytes = BASE32_NOPAD
+            .decode(secret.expose_secret().as_bytes())
+            .wrap_err("Invalid TOTP secret")?;
+
+        if bytes.len() < TOTP_MIN_SECRET_LENGTH {
+            return Err(eyre!("TOTP secret is too short"));
+        }
+
+        Ok(Self(secret))
+    }
+
+    /// Provisioning URI understood by authenticator apps, usually rendered
+    /// as a QR code by the client.
+    pub fn otpauth_uri(&self, account: &Email) -> Result<String> {
+        let mut uri = Url::parse("otpauth://totp/").wrap_err("failed to build otpauth URI")?;
+        uri.set_path(&format!(
+            "{}:{}",
+            TOTP_ISSUER,
+            account.as_ref().expose_secret()
+        ));
+        uri.query_pairs_mut()
+            .append_pair("secret", self.0.expose_secret())
+            .append_pair("issuer", TOTP_ISSUER)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &TOTP_DIGITS.to_string())
+            .append_pair("period", &TOTP_STEP_SECONDS.to_string());
+
+        Ok(uri.to_string())
+    }
+
+    /// Code for the time step containing `unix_time`.
+    pub fn generate_code(&self, unix_time: u64) -> Result<TwoFACode> {
+        self.code_for_step(unix_time / TOTP_STEP_SECONDS)
+    }
+
+    /// Returns the time step the code was generated for if it is close
+    /// enough to `unix_time`. Callers keep track of the steps already used,
+    /// since RFC 6238 §5.2 only lets each one be used once.
+    pub fn verify_code(&self, code: &TwoFACode, unix_time: u64) -> Result<Option<u64>> {
+        let current_step = unix_time / TOTP_STEP_SECONDS;
+        let first_step = current_step.saturating_sub(TOTP_ALLOWED_SKEW_STEPS);
+        let last_step = current_step + TOTP_ALLOWED_SKEW_STEPS;
+
+        for step in first_step..=last_step {
+            if self.code_for_step(step)? == *code {
+                return Ok(Some(step));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // HOTP value (RFC 4226) using the time step as the counter.
+    fn code_for_step(&self, step: u64) -> Result<TwoFACode> {
+        let key = BASE32_NOPAD
+            .decode(self.0.expose_secret().as_bytes())
+            .wrap_err("Invalid TOTP secret")?;
+
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(&key).wrap_err("failed to initialise TOTP HMAC")?;
+        mac.update(&step.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = binary % 10u32.pow(TOTP_DIGITS);
+
+        TwoFACode::parse(Secret::new(format!(
+            "{:0width$}",
+            code,
+            width = TOTP_DIGITS as usize
+        )))
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = [0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(Secret::new(BASE32_NOPAD.encode(&bytes)))
+    }
+}
+
+impl AsRef<Secret<String>> for TotpSecret {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::*;
+
+    // Base32 of the ASCII seed "12345678901234567890" used by RFC 6238
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret::parse(Secret::new(RFC_SECRET.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn empty_string() {
+        assert!(TotpSecret::parse(Secret::new("".to_owned())).is_err());
+    }
+
+    #[test]
+    fn not_base32() {
+        assert!(TotpSecret::parse(Secret::new("not-base32!".to_owned())).is_err());
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(TotpSecret::parse(Secret::new("GEZDGNBV".to_owned())).is_err());
+    }
+
+    #[test]
+    fn default_secret_is_valid() {
+        let secret = TotpSecret::default();
+        assert!(TotpSecret::parse(secret.as_ref().clone()).is_ok());
+    }
+
+    #[test]
+    fn rfc_6238_test_vectors() {
+        let secret = rfc_secret();
+        let test_cases = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+
+        for (time, expected) in test_cases {
+            let code = secret.generate_code(time).unwrap();
+            assert_eq!(code.as_ref().expose_secret(), expected, "time {}", time);
+        }
+    }
+
+    #[test]
+    fn verify_accepts_adjacent_steps() {
+        let secret = rfc_secret();
+        let now = 1111111109;
+
+        let previous = secret.generate_code(now - TOTP_STEP_SECONDS).unwrap();
+        let current = secret.generate_code(now).unwrap();
+        let next = secret.generate_code(now + TOTP_STEP_SECONDS).unwrap();
+        let stale = secret.generate_code(now - 3 * TOTP_STEP_SECONDS).unwrap();
+
+        let current_step = now / TOTP_STEP_SECONDS;
+
+        assert_eq!(
+            secret.verify_code(&previous, now).unwrap(),
+            Some(current_step - 1)
+        );
+        assert_eq!(
+            secret.verify_code(&current, now).unwrap(),
+            Some(current_step)
+        );
+        assert_eq!(
+            secret.verify_code(&next, now).unwrap(),
+            Some(current_step + 1)
+        );
+        assert_eq!(secret.verify_code(&stale, now).unwrap(), None);
+    }
+
+    #[test]
+    fn otpauth_uri() {
+        let secret = rfc_secret();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+
+        let uri = secret.otpauth_uri(&email).unwrap();
+
+        assert!(uri.starts_with("otpauth://totp/AuthService:test@example.com?"));
+        assert!(uri.contains(&format!("secret={}", RFC_SECRET)));
+        assert!(uri.contains("issuer=AuthService"));
+        assert!(uri.contains("digits=6"));
+        assert!(uri.contains("period=30"));
+    }
+}
@@ -1,6 +1,9 @@
+use std::hash::{Hash, Hasher};
+
 use color_eyre::eyre::{eyre, Context, Result};
 use rand::Rng;
 use secrecy::{ExposeSecret, Secret};
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
 #[derive(Debug, Clone)]
@@ -8,7 +11,15 @@ pub struct LoginAttemptId(Secret<String>);
 
 impl PartialEq for LoginAttemptId {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        secrets_match(&self.0, &other.0)
+    }
+}
+
+impl Eq for LoginAttemptId {}
+
+impl Hash for LoginAttemptId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.expose_secret().hash(state);
     }
 }
 
@@ -36,9 +47,11 @@ impl AsRef<Secret<String>> for LoginAttemptId {
 #[derive(Debug, Clone)]
 pub struct TwoFACode(Secret<String>);
 
+// Compared in constant time so response timings don't reveal how many leading
+// digits of a guess were right.
 impl PartialEq for TwoFACode {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        secrets_match(&self.0, &other.0)
     }
 }
 
@@ -66,6 +79,13 @@ impl AsRef<Secret<String>> for TwoFACode {
     }
 }
 
+fn secrets_match(a: &Secret<String>, b: &Secret<String>) -> bool {
+    a.expose_secret()
+        .as_bytes()
+        .ct_eq(b.expose_secret().as_bytes())
+        .into()
+}
+
 #[cfg(test)]
 extern crate quickcheck;
 
@@ -108,6 +128,20 @@ mod tests {
         assert!(TwoFACode::parse(code).is_ok());
     }
 
+    #[test]
+    fn compare_2fa_codes() {
+        let code = TwoFACode::parse(Secret::new("123456".to_owned())).unwrap();
+
+        assert_eq!(
+            code,
+            TwoFACode::parse(Secret::new("123456".to_owned())).unwrap()
+        );
+        assert_ne!(
+            code,
+            TwoFACode::parse(Secret::new("123457".to_owned())).unwrap()
+        );
+    }
+
     #[test]
     fn empty_string_login_attempt_id() {
         let code = "";
@@ -1,18 +1,120 @@
+use std::fmt;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use uuid::Uuid;
+
 use super::{Email, Password};
 
+/// Stable identifier of an account. Unlike the email address it never
+/// changes, so it is what tokens and stored records refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn parse(id: &str) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(id).wrap_err("Invalid user id")?;
+        Ok(Self(parsed_id))
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl AsRef<Uuid> for UserId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct User {
+    pub id: UserId,
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub email_verified: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> User {
+    pub fn new(email: Email, password: Password, two_fa_method: TwoFAMethod) -> User {
         User {
+            id: UserId::default(),
             email,
             password,
-            requires_2fa,
+            two_fa_method,
+            email_verified: false,
+        }
+    }
+}
+
+/// Second factor a user has to present after their password.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum TwoFAMethod {
+    #[default]
+    None,
+    Email,
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(method: &str) -> Result<Self> {
+        match method {
+            "none" => Ok(Self::None),
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            _ => Err(eyre!("Invalid 2FA method {}", method)),
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Email => "email",
+            Self::Totp => "totp",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TwoFAMethod, UserId};
+
+    #[test]
+    fn invalid_user_id() {
+        assert!(UserId::parse("").is_err());
+        assert!(UserId::parse("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn user_id_round_trip() {
+        let id = UserId::default();
+        assert_eq!(UserId::parse(&id.to_string()).unwrap(), id);
+    }
+
+    #[test]
+    fn round_trip() {
+        for method in [TwoFAMethod::None, TwoFAMethod::Email, TwoFAMethod::Totp] {
+            assert_eq!(TwoFAMethod::parse(method.as_str()).unwrap(), method);
+        }
+    }
+
+    #[test]
+    fn unknown_method() {
+        assert!(TwoFAMethod::parse("sms").is_err());
+    }
 }
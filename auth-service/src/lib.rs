@@ -4,9 +4,11 @@ extern crate quickcheck_macros;
 
 use app_state::AppState;
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::AddExtension,
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
@@ -15,7 +17,7 @@ use redis::{Client, RedisResult};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
 use utils::tracing::{make_span_with_request_id, on_request, on_response};
 
@@ -28,7 +30,10 @@ pub mod services;
 pub mod utils;
 
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+	IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+	AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
 
     pub address: String,
 }
@@ -41,7 +46,7 @@ impl Application {
 	];
 
 	let cors = CorsLayer::new()
-	    .allow_methods([Method::GET, Method::POST])
+	    .allow_methods([Method::GET, Method::POST, Method::DELETE])
 	    .allow_credentials(true)
 	    .allow_origin(allowed_origins);
 
@@ -50,8 +55,38 @@ impl Application {
 	    .route("/signup", post(signup))
 	    .route("/login", post(login))
 	    .route("/verify-2fa", post(verify_2fa))
+	    .route("/verify-2fa/recovery", post(verify_recovery_code))
+	    .route("/resend-2fa", post(resend_2fa))
+	    .route("/magic-link", post(request_magic_link))
+	    .route("/magic-link/callback", get(magic_link_callback))
+	    .route("/passkeys/register/start", post(start_passkey_registration))
+	    .route("/passkeys/register/finish", post(finish_passkey_registration))
+	    .route("/passkeys/login/start", post(start_passkey_login))
+	    .route("/passkeys/login/finish", post(finish_passkey_login))
+	    .route("/passkeys/2fa/start", post(start_passkey_two_fa))
+	    .route("/passkeys", get(list_passkeys))
+	    .route("/passkeys/:id", delete(delete_passkey))
+	    .route("/recovery-codes/regenerate", post(regenerate_recovery_codes))
+	    .route("/user", get(get_user_info))
 	    .route("/logout", post(logout))
+	    .route("/logout-all", post(logout_all))
+	    .route("/sessions", get(list_sessions))
+	    .route("/sessions/:id", delete(revoke_session))
 	    .route("/verify-token", post(verify_token))
+	    .route("/.well-known/jwks.json", get(get_jwks))
+	    .route("/refresh", post(refresh))
+	    .route("/totp/enroll", post(enroll_totp))
+	    .route("/totp/confirm", post(confirm_totp))
+	    .route("/totp/disable", post(disable_totp))
+	    .route("/verify-email", get(verify_email_link).post(verify_email))
+	    .route("/verify-email/resend", post(resend_verification_email))
+	    .route("/password-reset/request", post(request_password_reset))
+	    .route("/password-reset/confirm", post(confirm_password_reset))
+	    .route("/change-password", post(change_password))
+	    .route("/change-email", post(request_email_change))
+	    .route("/change-email/confirm", get(confirm_email_change_link).post(confirm_email_change))
+	    .route("/account", delete(delete_account))
+	    .route("/account/restore", post(restore_account))
 	    .with_state(app_state)
 	    .layer(cors)
 	    .layer(
@@ -63,7 +98,10 @@ impl Application {
 
 	let listener = tokio::net::TcpListener::bind(address).await?;
 	let address = listener.local_addr()?.to_string();
-	let server = axum::serve(listener, router);
+	let server = axum::serve(
+	    listener,
+	    router.into_make_service_with_connect_info::<SocketAddr>(),
+	);
 
 	Ok(Self { server, address })
     }
@@ -77,15 +115,43 @@ impl Application {
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<PasswordViolationResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordViolationResponse {
+    pub code: String,
+    pub message: String,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
 	log_error_chain(&self);
 
+	let retry_after = match &self {
+	    AuthAPIError::TooManyLoginAttempts(retry_after) => Some(*retry_after),
+	    _ => None,
+	};
+
+	let violations = match &self {
+	    AuthAPIError::PasswordPolicyViolation(violations) => violations
+		.iter()
+		.map(|violation| PasswordViolationResponse {
+		    code: violation.code().to_owned(),
+		    message: violation.to_string(),
+		})
+		.collect(),
+	    _ => Vec::new(),
+	};
+
 	let (status, error_message) = match self {
 	    AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
 	    AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+	    AuthAPIError::PasswordPolicyViolation(_) => (
+		StatusCode::BAD_REQUEST,
+		"Password does not meet the requirements",
+	    ),
 	    AuthAPIError::IncorrectCredentials => {
 		(StatusCode::UNAUTHORIZED, "Incorrect Credentials")
 	    }
@@ -94,13 +160,68 @@ impl IntoResponse for AuthAPIError {
 	    }
 	    AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
 	    AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "JWT is not valid"),
+	    AuthAPIError::RevokedToken => (StatusCode::UNAUTHORIZED, "JWT has been revoked"),
+	    AuthAPIError::MissingRefreshToken => (StatusCode::BAD_REQUEST, "Missing refresh token"),
+	    AuthAPIError::InvalidRefreshToken => {
+		(StatusCode::UNAUTHORIZED, "Refresh token is not valid")
+	    }
+	    AuthAPIError::InvalidPasswordResetToken => (
+		StatusCode::UNAUTHORIZED,
+		"Password reset token is invalid or has expired",
+	    ),
+	    AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email address not verified"),
+	    AuthAPIError::InvalidEmailVerificationToken => (
+		StatusCode::UNAUTHORIZED,
+		"Email verification token is invalid or has expired",
+	    ),
+	    AuthAPIError::InvalidEmailChangeToken => (
+		StatusCode::UNAUTHORIZED,
+		"Email change token is invalid or has expired",
+	    ),
+	    AuthAPIError::InvalidMagicLinkToken => (
+		StatusCode::UNAUTHORIZED,
+		"Magic link is invalid or has expired",
+	    ),
+	    AuthAPIError::InvalidPasskey => {
+		(StatusCode::UNAUTHORIZED, "Passkey could not be verified")
+	    }
+	    AuthAPIError::PasskeyAlreadyRegistered => {
+		(StatusCode::CONFLICT, "Passkey is already registered")
+	    }
+	    AuthAPIError::NoPasskeyRegistered => {
+		(StatusCode::BAD_REQUEST, "No passkey is registered")
+	    }
+	    AuthAPIError::PasskeyNotFound => (StatusCode::NOT_FOUND, "Passkey not found"),
+	    AuthAPIError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+	    AuthAPIError::TooManyLoginAttempts(_) => {
+		(StatusCode::TOO_MANY_REQUESTS, "Too many login attempts")
+	    }
+	    AuthAPIError::TotpAlreadyEnabled => (StatusCode::CONFLICT, "TOTP is already enabled"),
+	    AuthAPIError::TotpNotEnrolled => (
+		StatusCode::BAD_REQUEST,
+		"TOTP enrollment has not been started",
+	    ),
+	    AuthAPIError::TotpNotEnabled => (StatusCode::BAD_REQUEST, "TOTP is not enabled"),
+	    AuthAPIError::TwoFANotEnabled => (StatusCode::BAD_REQUEST, "2FA is not enabled"),
+	    AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
 	};
 
 	let body = Json(ErrorResponse {
 	    error: error_message.to_string(),
+	    violations,
 	});
 
-	(status, body).into_response()
+	let mut response = (status, body).into_response();
+
+	if let Some(retry_after) = retry_after {
+	    // Round up so clients never retry while still locked out
+	    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+	    response
+		.headers_mut()
+		.insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+	}
+
+	response
     }
 }
 
@@ -5,10 +5,21 @@ use auth_service::{
     domain::Email,
     get_postgres_pool, get_redis_client,
     services::{
-	PostgresUserStore, PostmarkEmailClient, RedisBannedTokenStore, RedisTwoFACodeStore,
+	PostgresPasskeyStore, PostgresRecoveryCodeStore, PostgresSessionStore, PostgresUserStore,
+	PostmarkEmailClient, RedisBannedTokenStore, RedisEmailChangeTokenStore,
+	RedisEmailVerificationTokenStore, RedisFailedLoginStore, RedisMagicLinkTokenStore,
+	RedisPasskeyChallengeStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore,
+	RedisTwoFACodeStore,
     },
     utils::{
-	constants::{prod, DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME},
+	account_purge::purge_deleted_accounts,
+	auth::{jwt_keyring, reload_jwt_keyring},
+	constants::{
+	    prod, ACCOUNT_DELETION_GRACE_PERIOD_DAYS, DATABASE_URL, POSTMARK_AUTH_TOKEN,
+	    REDIS_HOST_NAME,
+	},
+	password_hashing::validate_argon2_params,
+	password_policy::password_policy,
 	tracing::init_tracing,
     },
     Application,
@@ -16,25 +27,68 @@ use auth_service::{
 use reqwest::Client;
 use secrecy::Secret;
 use sqlx::PgPool;
-use tokio::sync::RwLock;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::RwLock,
+};
 
 #[tokio::main]
 async fn main() {
     color_eyre::install().expect("Failed to install color_eyre!");
     init_tracing().expect("Failed to initialize tracing!");
 
+    // Fail fast on a bad signing key instead of on the first login
+    jwt_keyring();
+    tokio::spawn(reload_jwt_keyring_on_sighup());
+    // Same for an unreadable breached password list
+    password_policy();
+    // And for Argon2 parameters out of range
+    validate_argon2_params().expect("Invalid Argon2 parameters!");
+
     let pg_pool = configure_postgresql().await;
     let redis_conn = Arc::new(RwLock::new(configure_redis()));
 
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
+    let refresh_token_store =
+	Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+    let session_store = Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone())));
+    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn.clone())));
+    let recovery_code_store =
+	Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+    let password_reset_token_store =
+	Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(redis_conn.clone())));
+    let email_verification_token_store =
+	Arc::new(RwLock::new(RedisEmailVerificationTokenStore::new(redis_conn.clone())));
+    let email_change_token_store =
+	Arc::new(RwLock::new(RedisEmailChangeTokenStore::new(redis_conn.clone())));
+    let magic_link_token_store =
+	Arc::new(RwLock::new(RedisMagicLinkTokenStore::new(redis_conn.clone())));
+    let passkey_store = Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool)));
+    let passkey_challenge_store =
+	Arc::new(RwLock::new(RedisPasskeyChallengeStore::new(redis_conn.clone())));
+    let failed_login_store = Arc::new(RwLock::new(RedisFailedLoginStore::new(redis_conn)));
     let email_client = Arc::new(configure_postmark_email_client());
 
+    // Without a grace period accounts are deleted right away
+    if *ACCOUNT_DELETION_GRACE_PERIOD_DAYS > 0 {
+	tokio::spawn(purge_deleted_accounts(user_store.clone()));
+    }
+
     let app_state = AppState::new(
 	user_store,
 	banned_token_store,
+	refresh_token_store,
+	session_store,
 	two_fa_code_store,
+	recovery_code_store,
+	password_reset_token_store,
+	email_verification_token_store,
+	email_change_token_store,
+	magic_link_token_store,
+	passkey_store,
+	passkey_challenge_store,
+	failed_login_store,
 	email_client,
     );
 
@@ -58,6 +112,19 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
+/// Rotating or retiring a JWT key only needs the keyring file to be updated
+/// and the process to be sent SIGHUP.
+async fn reload_jwt_keyring_on_sighup() {
+    let mut sighup = signal(SignalKind::hangup()).expect("Failed to install SIGHUP handler!");
+
+    while sighup.recv().await.is_some() {
+	match reload_jwt_keyring() {
+	    Ok(()) => tracing::info!("Reloaded JWT keyring"),
+	    Err(e) => tracing::error!("Failed to reload JWT keyring: {:?}", e),
+	}
+    }
+}
+
 fn configure_redis() -> redis::Connection {
     get_redis_client(REDIS_HOST_NAME.to_owned())
 	.expect("Failed to get Redis client!")
@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailChangeToken, EmailChangeTokenStoreError, Password, UserStoreError,
+    },
+    utils::{auth::authenticate, constants::AUTH_SERVICE_URL},
+};
+
+use super::confirm_password;
+
+/// Starts moving the logged in user to a new address. Nothing changes until
+/// the link sent to the new address is followed. If the address already has
+/// an account, its owner is told instead and the response stays the same, so
+/// this can't be used to find out which addresses are registered.
+#[tracing::instrument(name = "Request email change", skip_all)]
+pub async fn request_email_change(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&jar, &state).await?.email;
+
+    let new_email =
+        Email::parse(request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if new_email == email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    confirm_password(&state, &email, &password).await?;
+
+    let new_email_taken = match state.user_store.read().await.get_user(&new_email).await {
+        Ok(_) => true,
+        Err(UserStoreError::UserNotFound) => false,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    state
+        .email_client
+        .send_email(
+            &email,
+            "Email change requested",
+            "A change of the email address of your account was requested. It only takes \
+             effect once confirmed from the new address. If this wasn't you, change your \
+             password immediately.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    match new_email_taken {
+        true => send_email_taken_notice(&state, &new_email).await?,
+        false => send_confirmation_link(&state, email, new_email).await?,
+    }
+
+    let response = Json(ChangeEmailResponse {
+        message: "A confirmation link has been sent to the new address".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+async fn send_confirmation_link(
+    state: &AppState,
+    email: Email,
+    new_email: Email,
+) -> Result<(), AuthAPIError> {
+    let token = EmailChangeToken::default();
+
+    state
+        .email_change_token_store
+        .write()
+        .await
+        .add_token(email, new_email.clone(), token.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let link = format!(
+        "{}/change-email/confirm?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref().expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(
+            &new_email,
+            "Confirm your new email address",
+            &format!(
+                "Use the following link to confirm your new email address: {}",
+                link
+            ),
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+#[tracing::instrument(name = "Send email taken notice", skip_all)]
+async fn send_email_taken_notice(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .email_client
+        .send_email(
+            email,
+            "Email change attempt",
+            "Someone tried to move another account to this email address, which already \
+             belongs to your account. Nothing was changed. If this was you, use a \
+             different address or delete one of the accounts first.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+/// Switches the account to the confirmed address. Sessions refer to the user
+/// by id, so they stay signed in.
+#[tracing::instrument(name = "Confirm email change", skip_all)]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    Json(request): Json<ConfirmEmailChangeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    change_email(&state, request.token).await
+}
+
+/// Target of the link in the confirmation email, which is opened with a GET.
+#[tracing::instrument(name = "Confirm email change link", skip_all)]
+pub async fn confirm_email_change_link(
+    State(state): State<AppState>,
+    Query(request): Query<ConfirmEmailChangeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    change_email(&state, request.token).await
+}
+
+async fn change_email(
+    state: &AppState,
+    token: Secret<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token =
+        EmailChangeToken::parse(token).map_err(|_| AuthAPIError::InvalidEmailChangeToken)?;
+
+    let consumed = state
+        .email_change_token_store
+        .write()
+        .await
+        .consume_token(&token)
+        .await;
+
+    let (email, new_email) = match consumed {
+        Ok(emails) => emails,
+        Err(EmailChangeTokenStoreError::TokenNotFound) => {
+            return Err(AuthAPIError::InvalidEmailChangeToken)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let updated = state
+        .user_store
+        .write()
+        .await
+        .update_email(&email, &new_email)
+        .await;
+
+    match updated {
+        Ok(_) => (),
+        // The address was taken since the change was requested. The response
+        // stays the same, and both addresses are told what happened.
+        Err(UserStoreError::UserAlreadyExists) => {
+            send_email_taken_notice(state, &new_email).await?;
+            send_email_change_failed_notice(state, &email).await?;
+        }
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidEmailChangeToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = Json(ChangeEmailResponse {
+        message: "Email changed successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+async fn send_email_change_failed_notice(
+    state: &AppState,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    state
+        .email_client
+        .send_email(
+            email,
+            "Email change failed",
+            "The email address of your account could not be changed, since the new \
+             address has been registered in the meantime. Your account still uses this \
+             address.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: Secret<String>,
+    pub password: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeEmailResponse {
+    pub message: String,
+}
@@ -0,0 +1,81 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Password},
+    utils::{auth::authenticate_session, password_policy::check_password_policy},
+};
+
+use super::{confirm_password, end_other_sessions};
+
+/// Changes the password of the logged in user. The session making the request
+/// stays signed in; every other session and outstanding reset link is ended.
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (user, session_id) = authenticate_session(&jar, &state).await?;
+
+    let current_password =
+        Password::parse(request.current_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_password_policy(&request.new_password, &user.email)?;
+    let new_password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    confirm_password(&state, &user.email, &current_password).await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&user.email, new_password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // A reset link requested before the change would otherwise still undo it
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .remove_tokens(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    end_other_sessions(&state, &user.id, &session_id).await?;
+
+    state
+        .email_client
+        .send_email(
+            &user.email,
+            "Password changed",
+            "The password of your account was just changed and all other sessions were \
+             signed out. If this wasn't you, reset your password immediately.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(ChangePasswordResponse {
+        message: "Password changed successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
@@ -0,0 +1,262 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, FailedLoginKey, LoginAttemptId, Password, TwoFACode, TwoFAMethod,
+        User, UserStoreError,
+    },
+    utils::{auth::authenticate, constants::ACCOUNT_DELETION_GRACE_PERIOD_DAYS},
+};
+
+use super::{
+    check_lockout, check_two_fa_code, confirm_password, end_all_sessions, record_failed_login,
+    remove_auth_cookies, start_two_fa_challenge, ClientInfo, TwoFactorAuthResponse,
+};
+
+/// Deletes the logged in user's account once their password, and their second
+/// factor if they have one, are confirmed. With a grace period configured the
+/// account is only hidden and can be restored until it gets purged.
+#[tracing::instrument(name = "Delete account", skip_all)]
+pub async fn delete_account(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<DeleteAccountRequest>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+    let user = match authenticate(&jar, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let confirmed = confirm_identity(
+        &state,
+        &user,
+        request.password,
+        request.login_attempt_id,
+        request.two_fa_code,
+    )
+    .await;
+
+    match confirmed {
+        Ok(None) => (),
+        Ok(Some(two_fa_required)) => return (jar, Ok(two_fa_required)),
+        Err(e) => return (jar, Err(e)),
+    }
+
+    if let Err(e) = remove_account(&state, &user).await {
+        return (jar, Err(e));
+    }
+
+    let message = if *ACCOUNT_DELETION_GRACE_PERIOD_DAYS > 0 {
+        "Account scheduled for deletion"
+    } else {
+        "Account deleted"
+    };
+
+    let response = Json(DeleteAccountResponse {
+        message: message.to_owned(),
+    });
+
+    (
+        remove_auth_cookies(jar),
+        Ok((StatusCode::OK, response).into_response()),
+    )
+}
+
+/// Brings back an account deleted within the grace period.
+#[tracing::instrument(name = "Restore account", skip_all)]
+pub async fn restore_account(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<RestoreAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Takes the password like a login does, so it is locked out the same way
+    let account_key = FailedLoginKey::Account(email.clone());
+    let ip_key = client.ip_address.clone().map(FailedLoginKey::IpAddress);
+
+    check_lockout(&state, &account_key, ip_key.as_ref()).await?;
+
+    let restored = state
+        .user_store
+        .write()
+        .await
+        .restore_user(&email, &password)
+        .await;
+
+    match restored {
+        Ok(_) => (),
+        Err(UserStoreError::InvalidCredentials) | Err(UserStoreError::UserNotFound) => {
+            record_failed_login(&state, &email, ip_key.as_ref()).await?;
+
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    state
+        .failed_login_store
+        .write()
+        .await
+        .clear_failures(&account_key)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(DeleteAccountResponse {
+        message: "Account restored".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Has a logged in user confirm their password, and their second factor if
+/// they have one, before a change a stolen session shouldn't be enough for.
+/// Without a code yet, a 2FA challenge is started and the 206 response
+/// announcing it is returned for the caller to send back.
+pub(crate) async fn confirm_identity(
+    state: &AppState,
+    user: &User,
+    password: Secret<String>,
+    login_attempt_id: Option<String>,
+    two_fa_code: Option<Secret<String>>,
+) -> Result<Option<Response>, AuthAPIError> {
+    let password = Password::parse(password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    confirm_password(state, &user.email, &password).await?;
+
+    if user.two_fa_method == TwoFAMethod::None {
+        return Ok(None);
+    }
+
+    let (login_attempt_id, code) = match (login_attempt_id, two_fa_code) {
+        (Some(login_attempt_id), Some(code)) => (login_attempt_id, code),
+        _ => {
+            let login_attempt_id = start_two_fa_challenge(state, user).await?;
+            let response = Json(TwoFactorAuthResponse {
+                message: "2FA required".to_owned(),
+                login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+            });
+
+            return Ok(Some(
+                (StatusCode::PARTIAL_CONTENT, response).into_response(),
+            ));
+        }
+    };
+
+    verify_two_fa_code(state, user, login_attempt_id, code).await?;
+
+    Ok(None)
+}
+
+async fn verify_two_fa_code(
+    state: &AppState,
+    user: &User,
+    login_attempt_id: String,
+    code: Secret<String>,
+) -> Result<(), AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::parse(Secret::new(login_attempt_id))
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let code = TwoFACode::parse(code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_two_fa_code(state, user, &login_attempt_id, &code).await
+}
+
+/// Signs the user out everywhere and drops their pending 2FA codes before
+/// hiding or deleting the account. Refresh tokens go with the sessions;
+/// password reset, verification, email change and magic link tokens expire on
+/// their own and can no longer be redeemed without the account.
+async fn remove_account(state: &AppState, user: &User) -> Result<(), AuthAPIError> {
+    end_all_sessions(state, &user.id).await?;
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_codes(&user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let grace_period_days = *ACCOUNT_DELETION_GRACE_PERIOD_DAYS;
+
+    if grace_period_days > 0 {
+        state
+            .user_store
+            .write()
+            .await
+            .mark_user_deleted(&user.email)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        return state
+            .email_client
+            .send_email(
+                &user.email,
+                "Account scheduled for deletion",
+                &format!(
+                    "Your account will be deleted in {} days. Until then you can restore it \
+                     with your email address and password.",
+                    grace_period_days
+                ),
+            )
+            .await
+            .map_err(AuthAPIError::UnexpectedError);
+    }
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .replace_codes(&user.id, Vec::new())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .delete_user(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .email_client
+        .send_email(
+            &user.email,
+            "Account deleted",
+            "Your account and all data tied to it have been deleted.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: Option<Secret<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreAccountRequest {
+    pub email: Secret<String>,
+    pub password: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccountResponse {
+    pub message: String,
+}
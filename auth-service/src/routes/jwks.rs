@@ -0,0 +1,10 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+
+use crate::utils::auth::jwt_keyring;
+
+/// Publishes the public keys that auth tokens are signed with, so other
+/// services can verify them locally. HS256 keys are never listed.
+#[tracing::instrument(name = "Get JWKS", skip_all)]
+pub async fn get_jwks() -> impl IntoResponse {
+    (StatusCode::OK, Json(jwt_keyring().jwks()))
+}
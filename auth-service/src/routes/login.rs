@@ -1,18 +1,30 @@
+use std::time::Duration;
+
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use color_eyre::eyre::Report;
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, FailedLoginKey, FailedLoginStore, LockoutPolicy, LoginAttemptId,
+        Password, TwoFACode, TwoFAMethod, User, UserStoreError,
+    },
+    utils::constants::{
+        LOGIN_IP_LOCKOUT_THRESHOLD, LOGIN_LOCKOUT_BASE_SECONDS, LOGIN_LOCKOUT_MAX_SECONDS,
+        LOGIN_LOCKOUT_THRESHOLD, REQUIRE_EMAIL_VERIFICATION,
+    },
 };
 
+use super::{start_session, ClientInfo};
+
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email) {
@@ -25,37 +37,221 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let user_store = &state.user_store.read().await;
+    let account_key = FailedLoginKey::Account(email.clone());
+    let ip_key = client.ip_address.clone().map(FailedLoginKey::IpAddress);
+
+    if let Err(e) = check_lockout(&state, &account_key, ip_key.as_ref()).await {
+        return (jar, Err(e));
+    }
+
+    let user_store = state.user_store.read().await;
 
     if user_store.validate_user(&email, &password).await.is_err() {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+        let recorded = record_failed_login(&state, &email, ip_key.as_ref()).await;
+
+        return match recorded {
+            Ok(_) => (jar, Err(AuthAPIError::IncorrectCredentials)),
+            Err(e) => (jar, Err(e)),
+        };
     };
 
-    let user = match user_store.get_user(&email).await {
+    // Only now is the plaintext at hand to replace a hash made with older
+    // settings. The old hash still works, so a failure doesn't fail the login.
+    if let Err(e) = user_store.upgrade_password_hash(&email, &password).await {
+        tracing::error!("Failed to upgrade password hash: {:?}", e);
+    }
+
+    drop(user_store);
+
+    if let Err(e) = state
+        .failed_login_store
+        .write()
+        .await
+        .clear_failures(&account_key)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let user = state.user_store.read().await.get_user(&email).await;
+
+    let user = match user {
         Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    if *REQUIRE_EMAIL_VERIFICATION && !user.email_verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&user, &state, jar, client).await,
+        _ => handle_2fa(&user, &state, jar).await,
     }
 }
 
-#[tracing::instrument(name = "Login handling no 2FA", skip_all)]
-async fn handle_no_2fa(
+/// Rejects the login while the account or the client is locked out, telling
+/// the client how long to wait.
+#[tracing::instrument(name = "Login checking lockout", skip_all)]
+pub(crate) async fn check_lockout(
+    state: &AppState,
+    account_key: &FailedLoginKey,
+    ip_key: Option<&FailedLoginKey>,
+) -> Result<(), AuthAPIError> {
+    let failed_login_store = state.failed_login_store.read().await;
+
+    let mut retry_after = None;
+
+    for key in std::iter::once(account_key).chain(ip_key) {
+        let lockout = failed_login_store
+            .get_lockout(key)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        retry_after = retry_after.max(lockout);
+    }
+
+    match retry_after {
+        Some(retry_after) => Err(AuthAPIError::TooManyLoginAttempts(retry_after)),
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed login against the account and the client, locking either
+/// out once it crosses its threshold. The owner of an existing account is
+/// told when it first gets locked, after responding so the response time
+/// doesn't tell known addresses apart.
+#[tracing::instrument(name = "Login recording failure", skip_all)]
+pub(crate) async fn record_failed_login(
+    state: &AppState,
+    email: &Email,
+    ip_key: Option<&FailedLoginKey>,
+) -> Result<(), AuthAPIError> {
+    let account_policy = lockout_policy(*LOGIN_LOCKOUT_THRESHOLD);
+    let mut failed_login_store = state.failed_login_store.write().await;
+
+    let account_failures = count_failure(
+        &mut *failed_login_store,
+        &FailedLoginKey::Account(email.clone()),
+        &account_policy,
+    )
+    .await?;
+
+    if let Some(ip_key) = ip_key {
+        let ip_policy = lockout_policy(*LOGIN_IP_LOCKOUT_THRESHOLD);
+        count_failure(&mut *failed_login_store, ip_key, &ip_policy).await?;
+    }
+
+    if account_failures == account_policy.threshold {
+        let state = state.clone();
+        let email = email.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = send_lockout_notice(&state, &email).await {
+                tracing::error!("Failed to send lockout notice: {:?}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn send_lockout_notice(state: &AppState, email: &Email) -> Result<(), Report> {
+    // Unknown addresses are locked out all the same, there's just nobody to tell
+    if state.user_store.read().await.get_user(email).await.is_err() {
+        return Ok(());
+    }
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Account temporarily locked",
+            "Logins to your account were blocked for a while after several failed attempts. \
+             If this wasn't you, someone may be trying to guess your password.",
+        )
+        .await
+}
+
+/// Checks the password of a logged in user before a change a stolen session
+/// shouldn't be enough for. Wrong passwords count against the account like
+/// failed logins, so the session can't be used to guess it either.
+#[tracing::instrument(name = "Confirming password", skip_all)]
+pub(crate) async fn confirm_password(
+    state: &AppState,
     email: &Email,
+    password: &Password,
+) -> Result<(), AuthAPIError> {
+    let account_key = FailedLoginKey::Account(email.clone());
+
+    check_lockout(state, &account_key, None).await?;
+
+    let user_store = state.user_store.read().await;
+
+    match user_store.validate_user(email, password).await {
+        Ok(_) => (),
+        Err(UserStoreError::InvalidCredentials) | Err(UserStoreError::UserNotFound) => {
+            record_failed_login(state, email, None).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    drop(user_store);
+
+    state
+        .failed_login_store
+        .write()
+        .await
+        .clear_failures(&account_key)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+async fn count_failure(
+    failed_login_store: &mut (dyn FailedLoginStore + Send + Sync),
+    key: &FailedLoginKey,
+    policy: &LockoutPolicy,
+) -> Result<u32, AuthAPIError> {
+    let failures = failed_login_store
+        .record_failure(key)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if let Some(lockout) = policy.lockout_for(failures) {
+        failed_login_store
+            .lock(key, lockout)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+
+    Ok(failures)
+}
+
+fn lockout_policy(threshold: u32) -> LockoutPolicy {
+    LockoutPolicy {
+        threshold,
+        base: Duration::from_secs(LOGIN_LOCKOUT_BASE_SECONDS),
+        max: Duration::from_secs(LOGIN_LOCKOUT_MAX_SECONDS),
+    }
+}
+
+#[tracing::instrument(name = "Login handling no 2FA", skip_all)]
+pub(crate) async fn handle_no_2fa(
+    user: &User,
+    state: &AppState,
     jar: CookieJar,
+    client: ClientInfo,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(email) {
-        Ok(cookie) => cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    let (auth_cookie, refresh_cookie) = match start_session(state, &user.id, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (
         updated_jar,
@@ -64,34 +260,18 @@ async fn handle_no_2fa(
 }
 
 #[tracing::instrument(name = "Login handling 2FA", skip_all)]
-async fn handle_2fa(
-    email: &Email,
+pub(crate) async fn handle_2fa(
+    user: &User,
     state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let login_attempt_id = LoginAttemptId::default();
-    let two_fa_code = TwoFACode::default();
-
-    if let Err(e) = state
-        .two_fa_code_store
-        .write()
-        .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
-        .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
-    }
-
-    if let Err(e) = state
-        .email_client
-        .send_email(email, "2FA Code", two_fa_code.as_ref().expose_secret())
-        .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
-    }
+    let login_attempt_id = match start_two_fa_challenge(state, user).await {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
 
     let response = TwoFactorAuthResponse {
         message: "2FA required".to_string(),
@@ -107,6 +287,42 @@ async fn handle_2fa(
     )
 }
 
+/// Opens a new 2FA challenge for the user and emails its code to email 2FA
+/// users. The returned login attempt ID is what the code gets verified
+/// against, so each device signing in gets its own challenge.
+pub(crate) async fn start_two_fa_challenge(
+    state: &AppState,
+    user: &User,
+) -> Result<LoginAttemptId, AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::default();
+    // TOTP users read their code from their authenticator app. The generated
+    // code is still stored to tie the login attempt to the user, but never
+    // sent or accepted.
+    let two_fa_code = TwoFACode::default();
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_code(user.id, login_attempt_id.clone(), two_fa_code.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if user.two_fa_method == TwoFAMethod::Email {
+        state
+            .email_client
+            .send_email(
+                &user.email,
+                "2FA Code",
+                two_fa_code.as_ref().expose_secret(),
+            )
+            .await
+            .map_err(AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(login_attempt_id)
+}
+
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum LoginResponse {
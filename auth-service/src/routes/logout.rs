@@ -1,13 +1,15 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::{cookie, CookieJar};
-use secrecy::{ExposeSecret, Secret};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
+    domain::{AuthAPIError, SessionId, UserId},
     utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
 };
 
+use super::{end_session, remove_auth_cookies};
+
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
@@ -21,22 +23,34 @@ pub async fn logout(
     let token = cookie.value().to_owned();
     let token = Secret::new(token);
 
-    if (validate_token(token.expose_secret()).await).is_err() {
-        return (jar, Err(AuthAPIError::InvalidToken));
-    }
+    let validated = validate_token(&token, &state.banned_token_store, &state.session_store).await;
+
+    let claims = match validated {
+        Ok(claims) => claims,
+        Err(e) => return (jar, Err(e)),
+    };
 
     match state
         .banned_token_store
         .write()
         .await
-        .add_banned_token(token.to_owned())
+        .add_banned_token(&claims.jti)
         .await
     {
         Ok(_) => (),
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
     }
 
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    // validate_token already checked both of these
+    let (user_id, session_id) = match (UserId::parse(&claims.sub), SessionId::parse(&claims.sid)) {
+        (Ok(user_id), Ok(session_id)) => (user_id, session_id),
+        _ => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    match end_session(&state, &user_id, &session_id).await {
+        Ok(_) | Err(AuthAPIError::SessionNotFound) => (),
+        Err(e) => return (jar, Err(e)),
+    }
 
-    (jar, Ok(StatusCode::OK))
+    (remove_auth_cookies(jar), Ok(StatusCode::OK))
 }
@@ -0,0 +1,173 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use color_eyre::eyre::Report;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, MagicLinkToken, MagicLinkTokenStoreError, TwoFAMethod, User,
+        UserStoreError,
+    },
+    utils::constants::{AUTH_SERVICE_URL, MAGIC_LINK_COUNTS_AS_FACTOR},
+};
+
+use super::{handle_2fa, handle_no_2fa, ClientInfo};
+
+/// Emails a single-use login link. Accounts with 2FA only get one when links
+/// are configured to count as the first factor.
+#[tracing::instrument(name = "Request magic link", skip_all)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Throttle before looking the account up, so a 429 doesn't reveal whether
+    // the address is registered.
+    let recorded = state
+        .magic_link_token_store
+        .write()
+        .await
+        .record_link_requested(&email, client.ip_address.as_deref())
+        .await;
+
+    match recorded {
+        Ok(_) => (),
+        Err(MagicLinkTokenStoreError::ResendThrottled) => {
+            return Err(AuthAPIError::TooManyRequests)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // The response is the same whether or not a link was sent, so this route
+    // doesn't reveal which emails are registered or which use 2FA.
+    let response = (
+        StatusCode::OK,
+        Json(MagicLinkResponse {
+            message: "If the account exists, a login link has been sent".to_owned(),
+        }),
+    );
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(response),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if !magic_link_allowed(&user) {
+        return Ok(response);
+    }
+
+    // Sending takes long enough to tell known addresses apart by response
+    // time, so it happens after responding.
+    tokio::spawn(async move {
+        if let Err(e) = send_magic_link(&state, &email).await {
+            tracing::error!("Failed to send magic link: {:?}", e);
+        }
+    });
+
+    Ok(response)
+}
+
+#[tracing::instrument(name = "Send magic link", skip_all)]
+async fn send_magic_link(state: &AppState, email: &Email) -> Result<(), Report> {
+    let token = MagicLinkToken::default();
+
+    state
+        .magic_link_token_store
+        .write()
+        .await
+        .add_token(email.clone(), token.clone())
+        .await?;
+
+    let link = format!(
+        "{}/magic-link/callback?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref().expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Your login link",
+            &format!("Use the following link to log in: {}", link),
+        )
+        .await
+}
+
+/// Redeems a magic link. Users without 2FA are logged in like after a password
+/// login; users with 2FA continue with their second factor.
+#[tracing::instrument(name = "Magic link callback", skip_all)]
+pub async fn magic_link_callback(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Query(params): Query<MagicLinkCallbackParams>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let token = match MagicLinkToken::parse(params.token) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidMagicLinkToken)),
+    };
+
+    let consumed = state
+        .magic_link_token_store
+        .write()
+        .await
+        .consume_token(&token)
+        .await;
+
+    let email = match consumed {
+        Ok(email) => email,
+        Err(MagicLinkTokenStoreError::TokenNotFound) => {
+            return (jar, Err(AuthAPIError::InvalidMagicLinkToken))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => {
+            return (jar, Err(AuthAPIError::InvalidMagicLinkToken))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // 2FA may have been turned on since the link was sent. Following the link
+    // proves control of the address, so email verification isn't required.
+    if !magic_link_allowed(&user) {
+        return (jar, Err(AuthAPIError::InvalidMagicLinkToken));
+    }
+
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&user, &state, jar, client).await,
+        _ => handle_2fa(&user, &state, jar).await,
+    }
+}
+
+fn magic_link_allowed(user: &User) -> bool {
+    user.two_fa_method == TwoFAMethod::None || *MAGIC_LINK_COUNTS_AS_FACTOR
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkCallbackParams {
+    pub token: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
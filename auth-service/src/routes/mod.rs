@@ -1,11 +1,37 @@
+mod change_email;
+mod change_password;
+mod delete_account;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod passkeys;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod sessions;
 mod signup;
+mod totp;
+mod user_info;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
+pub use change_email::*;
+pub use change_password::*;
+pub use delete_account::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use passkeys::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
+pub use user_info::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
@@ -0,0 +1,624 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, CredentialId, Email, LoginAttemptId, Passkey, PasskeyCeremony,
+        PasskeyChallenge, PasskeyChallengeStoreError, PasskeyStoreError, User, UserId,
+        UserStoreError,
+    },
+    utils::{
+        auth::authenticate,
+        constants::{
+            PASSKEY_CHALLENGE_TTL_SECONDS, REQUIRE_EMAIL_VERIFICATION, WEBAUTHN_RP_ID,
+            WEBAUTHN_RP_NAME,
+        },
+        webauthn::{
+            verify_assertion, verify_registration, ClientData, CREATE_CEREMONY_TYPE,
+            ES256_ALGORITHM, GET_CEREMONY_TYPE,
+        },
+    },
+};
+
+use super::{confirm_identity, handle_no_2fa, ClientInfo};
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+/// Hands a logged in user the options for creating a passkey for their
+/// account. A passkey signs in on its own, so the password and the second
+/// factor are asked for again, as for deleting the account.
+#[tracing::instrument(name = "Start passkey registration", skip_all)]
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<StartPasskeyRegistrationRequest>,
+) -> Result<Response, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    if let Some(two_fa_required) = confirm_identity(
+        &state,
+        &user,
+        request.password,
+        request.login_attempt_id,
+        request.two_fa_code,
+    )
+    .await?
+    {
+        return Ok(two_fa_required);
+    }
+
+    let passkeys = get_passkeys(&state, &user.id).await?;
+
+    let challenge = PasskeyChallenge::default();
+    add_challenge(
+        &state,
+        challenge.clone(),
+        PasskeyCeremony::Registration { user_id: user.id },
+    )
+    .await?;
+
+    let email = user.email.as_ref().expose_secret();
+
+    let options = PasskeyCreationOptions {
+        challenge: challenge.encoded(),
+        rp: RelyingParty {
+            id: WEBAUTHN_RP_ID.to_owned(),
+            name: WEBAUTHN_RP_NAME.to_owned(),
+        },
+        user: PasskeyUser {
+            id: BASE64URL_NOPAD.encode(user.id.as_ref().as_bytes()),
+            name: email.to_owned(),
+            display_name: email.to_owned(),
+        },
+        pub_key_cred_params: vec![PublicKeyCredentialParameters {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            alg: ES256_ALGORITHM,
+        }],
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        // Keeps authenticators from registering the same passkey twice
+        exclude_credentials: to_descriptors(&passkeys),
+        authenticator_selection: AuthenticatorSelection {
+            resident_key: "required".to_owned(),
+            user_verification: "required".to_owned(),
+        },
+        attestation: "none".to_owned(),
+    };
+
+    Ok((StatusCode::OK, Json(options)).into_response())
+}
+
+/// Stores the passkey created from the options of
+/// [`start_passkey_registration`] and lets the user know it was added.
+#[tracing::instrument(name = "Finish passkey registration", skip_all)]
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<PasskeyRegistration>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let credential_id =
+        CredentialId::parse(&request.id).map_err(|_| AuthAPIError::InvalidPasskey)?;
+    let client_data_json = decode(&request.response.client_data_json)?;
+    let attestation_object = decode(&request.response.attestation_object)?;
+
+    let client_data = ClientData::parse(&client_data_json, CREATE_CEREMONY_TYPE)
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    let expected = PasskeyCeremony::Registration { user_id: user.id };
+
+    if consume_challenge(&state, &client_data.challenge).await? != expected {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let passkey = verify_registration(user.id, &attestation_object)
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    if passkey.credential_id != credential_id {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let added = state.passkey_store.write().await.add_passkey(passkey).await;
+
+    match added {
+        Ok(_) => (),
+        Err(PasskeyStoreError::PasskeyAlreadyExists) => {
+            return Err(AuthAPIError::PasskeyAlreadyRegistered)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    state
+        .email_client
+        .send_email(
+            &user.email,
+            "Passkey added",
+            "A passkey was added to your account. If this wasn't you, someone else may \
+             have access to your account.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasskeyResponse {
+        message: "Passkey registered".to_owned(),
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+/// Lists the passkeys registered for the logged in user, oldest first.
+#[tracing::instrument(name = "List passkeys", skip_all)]
+pub async fn list_passkeys(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let passkeys = get_passkeys(&state, &user.id)
+        .await?
+        .into_iter()
+        .map(|passkey| PasskeySummary {
+            id: passkey.credential_id.encoded(),
+            created_at: passkey.created_at,
+            last_used_at: passkey.last_used_at,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(PasskeysResponse { passkeys })))
+}
+
+/// Removes one of the logged in user's passkeys and lets them know, in case
+/// it wasn't them.
+#[tracing::instrument(name = "Delete passkey", skip_all)]
+pub async fn delete_passkey(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let credential_id = CredentialId::parse(&id).map_err(|_| AuthAPIError::PasskeyNotFound)?;
+
+    let removed = state
+        .passkey_store
+        .write()
+        .await
+        .remove_passkey(&user.id, &credential_id)
+        .await;
+
+    match removed {
+        Ok(_) => (),
+        Err(PasskeyStoreError::PasskeyNotFound) => return Err(AuthAPIError::PasskeyNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    state
+        .email_client
+        .send_email(
+            &user.email,
+            "Passkey removed",
+            "A passkey was removed from your account. If this wasn't you, change your \
+             password and review the passkeys that are left.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasskeyResponse {
+        message: "Passkey removed".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Hands out a challenge for logging in with a passkey instead of a password.
+/// Any of the user's passkeys can answer it, so no account is named.
+#[tracing::instrument(name = "Start passkey login", skip_all)]
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let challenge = PasskeyChallenge::default();
+    add_challenge(&state, challenge.clone(), PasskeyCeremony::Login).await?;
+
+    let options = request_options(&challenge, Vec::new(), "required");
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+/// Logs the owner of the passkey in. The authenticator has to have verified
+/// the user with a PIN or biometrics, which makes a passkey two factors on
+/// its own, so users with 2FA don't get asked for a code.
+#[tracing::instrument(name = "Finish passkey login", skip_all)]
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<PasskeyAssertion>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let passkey = match check_assertion(&state, request, &PasskeyCeremony::Login, true).await {
+        Ok(passkey) => passkey,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&passkey.user_id)
+        .await;
+
+    let user = match user {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidPasskey)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    if *REQUIRE_EMAIL_VERIFICATION && !user.email_verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    handle_no_2fa(&user, &state, jar, client).await
+}
+
+/// Hands out a challenge for completing a pending login attempt with one of
+/// the user's passkeys instead of a 2FA code. The answer goes to
+/// `/verify-2fa`.
+#[tracing::instrument(name = "Start passkey 2FA", skip_all)]
+pub async fn start_passkey_two_fa(
+    State(state): State<AppState>,
+    Json(request): Json<PasskeyTwoFARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let login_attempt_id = LoginAttemptId::parse(Secret::new(request.login_attempt_id))
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = state.user_store.read().await.get_user(&email).await;
+
+    let user = match user {
+        Ok(user) => user,
+        Err(_) => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    let pending = state
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&login_attempt_id)
+        .await;
+
+    match pending {
+        Ok((user_id, _)) if user_id == user.id => (),
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    let passkeys = get_passkeys(&state, &user.id).await?;
+
+    if passkeys.is_empty() {
+        return Err(AuthAPIError::NoPasskeyRegistered);
+    }
+
+    let challenge = PasskeyChallenge::default();
+    add_challenge(
+        &state,
+        challenge.clone(),
+        PasskeyCeremony::SecondFactor {
+            user_id: user.id,
+            login_attempt_id,
+        },
+    )
+    .await?;
+
+    // The password was already checked, so presence on its own is enough
+    let options = request_options(&challenge, to_descriptors(&passkeys), "discouraged");
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+/// Checks an assertion for the challenge of [`start_passkey_two_fa`] and
+/// uses up the pending login attempt, like a correct 2FA code would.
+pub(crate) async fn check_passkey_two_fa(
+    state: &AppState,
+    user: &User,
+    login_attempt_id: &LoginAttemptId,
+    assertion: PasskeyAssertion,
+) -> Result<(), AuthAPIError> {
+    let expected = PasskeyCeremony::SecondFactor {
+        user_id: user.id,
+        login_attempt_id: login_attempt_id.clone(),
+    };
+
+    let passkey = check_assertion(state, assertion, &expected, false).await?;
+
+    if passkey.user_id != user.id {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    match two_fa_code_store.get_code(login_attempt_id).await {
+        Ok((user_id, _)) if user_id == user.id => (),
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    two_fa_code_store
+        .remove_code(login_attempt_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+/// Verifies an assertion against the challenge it was made for, which has to
+/// have been handed out for `expected`, and records the new sign count of the
+/// passkey that made it.
+async fn check_assertion(
+    state: &AppState,
+    assertion: PasskeyAssertion,
+    expected: &PasskeyCeremony,
+    require_user_verification: bool,
+) -> Result<Passkey, AuthAPIError> {
+    let credential_id =
+        CredentialId::parse(&assertion.id).map_err(|_| AuthAPIError::InvalidPasskey)?;
+    let client_data_json = decode(&assertion.response.client_data_json)?;
+    let authenticator_data = decode(&assertion.response.authenticator_data)?;
+    let signature = decode(&assertion.response.signature)?;
+
+    let client_data = ClientData::parse(&client_data_json, GET_CEREMONY_TYPE)
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    if consume_challenge(state, &client_data.challenge).await? != *expected {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let passkey = state
+        .passkey_store
+        .read()
+        .await
+        .get_passkey(&credential_id)
+        .await;
+
+    let passkey = match passkey {
+        Ok(passkey) => passkey,
+        Err(PasskeyStoreError::PasskeyNotFound) => return Err(AuthAPIError::InvalidPasskey),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let sign_count = verify_assertion(
+        &passkey,
+        &client_data,
+        &authenticator_data,
+        &signature,
+        require_user_verification,
+    )
+    .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    state
+        .passkey_store
+        .write()
+        .await
+        .update_sign_count(&credential_id, sign_count)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Passkey {
+        sign_count,
+        last_used_at: Some(Utc::now()),
+        ..passkey
+    })
+}
+
+async fn get_passkeys(state: &AppState, user_id: &UserId) -> Result<Vec<Passkey>, AuthAPIError> {
+    state
+        .passkey_store
+        .read()
+        .await
+        .get_passkeys(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+async fn add_challenge(
+    state: &AppState,
+    challenge: PasskeyChallenge,
+    ceremony: PasskeyCeremony,
+) -> Result<(), AuthAPIError> {
+    state
+        .passkey_challenge_store
+        .write()
+        .await
+        .add_challenge(challenge, ceremony)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+async fn consume_challenge(
+    state: &AppState,
+    challenge: &PasskeyChallenge,
+) -> Result<PasskeyCeremony, AuthAPIError> {
+    let consumed = state
+        .passkey_challenge_store
+        .write()
+        .await
+        .consume_challenge(challenge)
+        .await;
+
+    match consumed {
+        Ok(ceremony) => Ok(ceremony),
+        Err(PasskeyChallengeStoreError::ChallengeNotFound) => Err(AuthAPIError::InvalidPasskey),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    BASE64URL_NOPAD
+        .decode(value.as_bytes())
+        .map_err(|_| AuthAPIError::InvalidPasskey)
+}
+
+fn to_descriptors(passkeys: &[Passkey]) -> Vec<CredentialDescriptor> {
+    passkeys
+        .iter()
+        .map(|passkey| CredentialDescriptor {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            id: passkey.credential_id.encoded(),
+        })
+        .collect()
+}
+
+fn request_options(
+    challenge: &PasskeyChallenge,
+    allow_credentials: Vec<CredentialDescriptor>,
+    user_verification: &str,
+) -> PasskeyRequestOptions {
+    PasskeyRequestOptions {
+        challenge: challenge.encoded(),
+        timeout: PASSKEY_CHALLENGE_TTL_SECONDS * 1000,
+        rp_id: WEBAUTHN_RP_ID.to_owned(),
+        allow_credentials,
+        user_verification: user_verification.to_owned(),
+    }
+}
+
+/// `PublicKeyCredentialCreationOptions` for `navigator.credentials.create()`,
+/// with binary values base64url encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: PasskeyUser,
+    #[serde(rename = "pubKeyCredParams")]
+    pub pub_key_cred_params: Vec<PublicKeyCredentialParameters>,
+    /// In milliseconds.
+    pub timeout: u64,
+    #[serde(rename = "excludeCredentials")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "authenticatorSelection")]
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: String,
+}
+
+/// `PublicKeyCredentialRequestOptions` for `navigator.credentials.get()`,
+/// with binary values base64url encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyRequestOptions {
+    pub challenge: String,
+    /// In milliseconds.
+    pub timeout: u64,
+    #[serde(rename = "rpId")]
+    pub rp_id: String,
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyUser {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyCredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticatorSelection {
+    #[serde(rename = "residentKey")]
+    pub resident_key: String,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+/// The `PublicKeyCredential` returned by `navigator.credentials.create()`.
+#[derive(Deserialize)]
+pub struct PasskeyRegistration {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+/// The `PublicKeyCredential` returned by `navigator.credentials.get()`.
+#[derive(Deserialize)]
+pub struct PasskeyAssertion {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasskeyTwoFARequest {
+    pub email: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyRegistrationRequest {
+    pub password: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: Option<String>,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: Option<Secret<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeysResponse {
+    pub passkeys: Vec<PasskeySummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeySummary {
+    /// Credential ID, base64url encoded.
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,225 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::Report;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, FailedLoginKey, Password, PasswordResetToken,
+        PasswordResetTokenStoreError, UserStoreError,
+    },
+    utils::{constants::AUTH_SERVICE_URL, password_policy::check_password_policy},
+};
+
+use super::{end_all_sessions, ClientInfo};
+
+#[tracing::instrument(name = "Request password reset", skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Throttle before looking the account up, so a 429 doesn't reveal whether
+    // the address is registered.
+    let recorded = state
+        .password_reset_token_store
+        .write()
+        .await
+        .record_reset_requested(&email, client.ip_address.as_deref())
+        .await;
+
+    match recorded {
+        Ok(_) => (),
+        Err(PasswordResetTokenStoreError::ResendThrottled) => {
+            return Err(AuthAPIError::TooManyRequests)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // The response is the same whether or not the account exists, so this
+    // route can't be used to find out which emails are registered.
+    let response = (
+        StatusCode::OK,
+        Json(PasswordResetResponse {
+            message: "If the account exists, a password reset link has been sent".to_owned(),
+        }),
+    );
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => (),
+        Err(UserStoreError::UserNotFound) => return Ok(response),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Sending takes long enough to tell known addresses apart by response
+    // time, so it happens after responding.
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset_link(&state, &email).await {
+            tracing::error!("Failed to send password reset link: {:?}", e);
+        }
+    });
+
+    Ok(response)
+}
+
+#[tracing::instrument(name = "Send password reset link", skip_all)]
+async fn send_password_reset_link(state: &AppState, email: &Email) -> Result<(), Report> {
+    let token = PasswordResetToken::default();
+
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(email.clone(), token.clone())
+        .await?;
+
+    let link = format!(
+        "{}/password-reset.html?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref().expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Password reset",
+            &format!("Use the following link to reset your password: {}", link),
+        )
+        .await
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = PasswordResetToken::parse(request.token)
+        .map_err(|_| AuthAPIError::InvalidPasswordResetToken)?;
+
+    // Validate the new password before redeeming the token so a rejected
+    // password doesn't burn the link. The policy depends on the account, so
+    // the token is only looked up here.
+    let email = state
+        .password_reset_token_store
+        .read()
+        .await
+        .get_email(&token)
+        .await;
+
+    let email = match email {
+        Ok(email) => email,
+        Err(PasswordResetTokenStoreError::TokenNotFound) => {
+            return Err(AuthAPIError::InvalidPasswordResetToken)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    check_password_policy(&request.new_password, &email)?;
+    let password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let consumed = state
+        .password_reset_token_store
+        .write()
+        .await
+        .consume_token(&token)
+        .await;
+
+    match consumed {
+        Ok(_) => (),
+        Err(PasswordResetTokenStoreError::TokenNotFound) => {
+            return Err(AuthAPIError::InvalidPasswordResetToken)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    match state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, password)
+        .await
+    {
+        Ok(_) => (),
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidPasswordResetToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Other links sent before this one shouldn't be able to change it again
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .remove_tokens(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // A reset is how an account is taken back from whoever got into it, so
+    // their sessions and any passkeys they may have added go too. Changing the
+    // password keeps passkeys, since that takes the current one.
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    end_all_sessions(&state, &user.id).await?;
+
+    state
+        .passkey_store
+        .write()
+        .await
+        .remove_passkeys(&user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // The owner just proved who they are, so a lockout from someone guessing
+    // the old password shouldn't keep them out
+    state
+        .failed_login_store
+        .write()
+        .await
+        .clear_failures(&FailedLoginKey::Account(email.clone()))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .email_client
+        .send_email(
+            &email,
+            "Password reset",
+            "The password of your account was just reset and all sessions were signed out. \
+             If this wasn't you, reset your password again immediately.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasswordResetResponse {
+        message: "Password updated successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
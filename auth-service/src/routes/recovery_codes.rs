@@ -0,0 +1,153 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError, TwoFAMethod,
+        UserId,
+    },
+    utils::auth::authenticate,
+};
+
+use super::{record_wrong_code, start_session, ClientInfo};
+
+/// Completes a 2FA login with a recovery code instead of the usual second
+/// factor. Each code works once.
+#[tracing::instrument(name = "Verify recovery code", skip_all)]
+pub async fn verify_recovery_code(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<VerifyRecoveryCodeRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let login_attempt_id = match LoginAttemptId::parse(Secret::new(request.login_attempt_id)) {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let recovery_code = match RecoveryCode::parse(request.recovery_code) {
+        Ok(recovery_code) => recovery_code,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let user = state.user_store.read().await.get_user(&email).await;
+
+    let user = match user {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    let code_tuple = state
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&login_attempt_id)
+        .await;
+
+    match code_tuple {
+        Ok((user_id, _)) if user_id == user.id => (),
+        _ => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    }
+
+    let consumed = state
+        .recovery_code_store
+        .write()
+        .await
+        .consume_code(&user.id, &recovery_code)
+        .await;
+
+    match consumed {
+        Ok(_) => (),
+        Err(RecoveryCodeStoreError::CodeNotFound) => {
+            // Every try costs a round of Argon2 verifications, so wrong codes
+            // count towards the same limit as wrong 2FA codes.
+            let mut two_fa_code_store = state.two_fa_code_store.write().await;
+            if let Err(e) = record_wrong_code(&mut *two_fa_code_store, &login_attempt_id).await {
+                return (jar, Err(e));
+            }
+
+            return (jar, Err(AuthAPIError::IncorrectCredentials));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    if let Err(e) = state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(&login_attempt_id)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let (jwt_cookie, refresh_cookie) = match start_session(&state, &user.id, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let updated_jar = jar.add(jwt_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK))
+}
+
+#[tracing::instrument(name = "Regenerate recovery codes", skip_all)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    if user.two_fa_method == TwoFAMethod::None {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let recovery_codes = issue_recovery_codes(&state, &user.id).await?;
+
+    let response = Json(RecoveryCodesResponse { recovery_codes });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Replaces the user's recovery codes with a fresh set and returns them
+/// formatted for display. This is the only time the codes are readable.
+pub(crate) async fn issue_recovery_codes(
+    state: &AppState,
+    user_id: &UserId,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_set();
+    let formatted = codes.iter().map(RecoveryCode::formatted).collect();
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .replace_codes(user_id, codes)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(formatted)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyRecoveryCodeRequest {
+    pub email: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "recoveryCode")]
+    pub recovery_code: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,99 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError},
+    utils::{
+        auth::{create_refresh_cookie, generate_auth_cookie},
+        constants::REFRESH_COOKIE_NAME,
+    },
+};
+
+use super::{end_all_sessions, remove_auth_cookies};
+
+#[tracing::instrument(name = "Refresh", skip_all)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let cookie = match jar.get(REFRESH_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return (jar, Err(AuthAPIError::MissingRefreshToken)),
+    };
+
+    let current = match RefreshToken::parse(Secret::new(cookie.value().to_owned())) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidRefreshToken)),
+    };
+
+    let next = RefreshToken::default();
+
+    let rotated = state
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(&current, next.clone())
+        .await;
+
+    let (user_id, session_id) = match rotated {
+        Ok(rotated) => rotated,
+        Err(RefreshTokenStoreError::TokenNotFound) => {
+            return (jar, Err(AuthAPIError::InvalidRefreshToken))
+        }
+        Err(RefreshTokenStoreError::TokenReused(user_id)) => {
+            tracing::warn!("Refresh token reuse detected, ending all sessions of the user");
+
+            // Either the thief or the owner holds the latest token, so no
+            // session of the user can be trusted anymore
+            if let Err(e) = end_all_sessions(&state, &user_id).await {
+                return (jar, Err(e));
+            }
+
+            return (
+                remove_auth_cookies(jar),
+                Err(AuthAPIError::InvalidRefreshToken),
+            );
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    let touched = state
+        .session_store
+        .write()
+        .await
+        .touch_session(&session_id)
+        .await;
+
+    match touched {
+        Ok(_) => (),
+        // The session was revoked or expired, so its refresh tokens go too
+        Err(SessionStoreError::SessionNotFound) => {
+            if let Err(e) = state
+                .refresh_token_store
+                .write()
+                .await
+                .revoke_family(&session_id)
+                .await
+            {
+                return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            }
+
+            return (
+                remove_auth_cookies(jar),
+                Err(AuthAPIError::InvalidRefreshToken),
+            );
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let auth_cookie = match generate_auth_cookie(&user_id, &session_id) {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(create_refresh_cookie(&next));
+
+    (updated_jar, Ok(StatusCode::OK))
+}
@@ -0,0 +1,316 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Path, State},
+    http::{header, request::Parts, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{
+    cookie::{self, Cookie},
+    CookieJar,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenStoreError, Session, SessionId, SessionStoreError,
+        UserId,
+    },
+    utils::{
+        auth::{authenticate_session, create_refresh_cookie, generate_auth_cookie},
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME, TRUSTED_PROXIES},
+    },
+};
+
+#[tracing::instrument(name = "List sessions", skip_all)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (user, current_session_id) = authenticate_session(&jar, &state).await?;
+
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            id: session.id.to_string(),
+            created_at: session.created_at,
+            last_seen: session.last_seen,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            current: session.id == current_session_id,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(SessionsResponse { sessions })))
+}
+
+#[tracing::instrument(name = "Revoke session", skip_all)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (user, current_session_id) = match authenticate_session(&jar, &state).await {
+        Ok(authenticated) => authenticated,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let session_id = match SessionId::parse(&id) {
+        Ok(session_id) => session_id,
+        Err(_) => return (jar, Err(AuthAPIError::SessionNotFound)),
+    };
+
+    if let Err(e) = end_session(&state, &user.id, &session_id).await {
+        return (jar, Err(e));
+    }
+
+    let jar = if session_id == current_session_id {
+        remove_auth_cookies(jar)
+    } else {
+        jar
+    };
+
+    (jar, Ok(StatusCode::OK))
+}
+
+/// Signs the user out everywhere, including the session making the request.
+#[tracing::instrument(name = "Logout all", skip_all)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (user, _) = match authenticate_session(&jar, &state).await {
+        Ok(authenticated) => authenticated,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    if let Err(e) = end_all_sessions(&state, &user.id).await {
+        return (jar, Err(e));
+    }
+
+    (remove_auth_cookies(jar), Ok(StatusCode::OK))
+}
+
+/// Records a new session for a successful login and returns the auth and
+/// refresh cookies bound to it.
+#[tracing::instrument(name = "Start session", skip_all)]
+pub(crate) async fn start_session(
+    state: &AppState,
+    user_id: &UserId,
+    client: ClientInfo,
+) -> Result<(Cookie<'static>, Cookie<'static>), AuthAPIError> {
+    let session = Session::new(*user_id, client.user_agent, client.ip_address);
+    let session_id = session.id;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let auth_cookie =
+        generate_auth_cookie(user_id, &session_id).map_err(AuthAPIError::UnexpectedError)?;
+
+    let refresh_token = RefreshToken::default();
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .add_token(session_id, *user_id, refresh_token.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((auth_cookie, create_refresh_cookie(&refresh_token)))
+}
+
+/// Removes the session and its refresh tokens. Auth tokens issued for it
+/// stop validating as soon as the session is gone.
+#[tracing::instrument(name = "End session", skip_all)]
+pub(crate) async fn end_session(
+    state: &AppState,
+    user_id: &UserId,
+    session_id: &SessionId,
+) -> Result<(), AuthAPIError> {
+    let removed = state
+        .session_store
+        .write()
+        .await
+        .remove_session(user_id, session_id)
+        .await;
+
+    match removed {
+        Ok(_) => (),
+        Err(SessionStoreError::SessionNotFound) => return Err(AuthAPIError::SessionNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    revoke_refresh_tokens(state, session_id).await
+}
+
+#[tracing::instrument(name = "End all sessions", skip_all)]
+pub(crate) async fn end_all_sessions(
+    state: &AppState,
+    user_id: &UserId,
+) -> Result<(), AuthAPIError> {
+    let session_ids = state
+        .session_store
+        .write()
+        .await
+        .remove_sessions(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    for session_id in &session_ids {
+        revoke_refresh_tokens(state, session_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Ends every session of the user except `current`, e.g. after a credential
+/// change made from that session.
+#[tracing::instrument(name = "End other sessions", skip_all)]
+pub(crate) async fn end_other_sessions(
+    state: &AppState,
+    user_id: &UserId,
+    current: &SessionId,
+) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    for session in sessions.iter().filter(|session| &session.id != current) {
+        match end_session(state, user_id, &session.id).await {
+            // Already ended concurrently
+            Ok(_) | Err(AuthAPIError::SessionNotFound) => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+async fn revoke_refresh_tokens(
+    state: &AppState,
+    session_id: &SessionId,
+) -> Result<(), AuthAPIError> {
+    let revoked = state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_family(session_id)
+        .await;
+
+    match revoked {
+        Ok(_) | Err(RefreshTokenStoreError::TokenNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+pub(crate) fn remove_auth_cookies(jar: CookieJar) -> CookieJar {
+    jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME))
+        .remove(cookie::Cookie::from(REFRESH_COOKIE_NAME))
+}
+
+/// Where a login comes from, as shown in the session list and used to count
+/// failed logins per client. The address is the peer of the connection;
+/// `X-Forwarded-For` is only followed through the proxies in
+/// `TRUSTED_PROXIES`, since anything further left is up to the client.
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        let forwarded_for: Vec<&str> = parts
+            .headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+
+        let ip_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| client_ip(addr.ip(), &forwarded_for, &TRUSTED_PROXIES))
+            .map(|ip| ip.to_string());
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+// Each proxy appends the address it got the request from, so the client is
+// the right-most hop that wasn't added on behalf of another trusted proxy.
+fn client_ip(peer: IpAddr, forwarded_for: &[&str], trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+
+    for hop in forwarded_for.iter().rev() {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: DateTime<Utc>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    pub current: bool,
+}
@@ -4,40 +4,104 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User},
+    domain::{
+        AuthAPIError, Email, EmailVerificationTokenStoreError, Password, RecoveryCode, TwoFAMethod,
+        User, UserStoreError,
+    },
+    utils::{password_hashing::compute_password_hash, password_policy::check_password_policy},
     AuthRequest,
 };
 
+use super::{issue_recovery_codes, send_verification_email};
+
+/// Creates the account and emails a verification link. If the address is
+/// already registered the owner is emailed instead, and the response looks the
+/// same so signup can't be used to find out which addresses have accounts.
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     State(state): State<AppState>,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let user = request.into_user()?;
+    let user_id = user.id;
+    let email = user.email.clone();
+    let two_fa_method = user.two_fa_method;
+
+    // Adding an existing user fails only after the password was hashed, so both
+    // cases take about as long
+    let added = state.user_store.write().await.add_user(user).await;
+
+    let user_exists = match added {
+        Ok(_) => false,
+        Err(UserStoreError::UserAlreadyExists) => true,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
 
-    let mut user_store = state.user_store.write().await;
+    let email_sent = state
+        .email_verification_token_store
+        .write()
+        .await
+        .record_email_sent(&email)
+        .await;
 
-    if user_store.get_user(&user.email).await.is_ok() {
-        return Err(AuthAPIError::UserAlreadyExists);
+    // If a resend was requested for this address moments ago, skip the email;
+    // the user can ask for another once the throttle expires.
+    match email_sent {
+        Ok(_) if user_exists => send_signup_attempt_email(&state, &email).await?,
+        Ok(_) => send_verification_email(&state, &email).await?,
+        Err(EmailVerificationTokenStoreError::ResendThrottled) => (),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
     }
 
-    match user_store.add_user(user).await {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(AuthAPIError::UnexpectedError(e.into()));
-        }
+    let recovery_codes = match two_fa_method {
+        TwoFAMethod::None => None,
+        _ if user_exists => Some(generate_unstored_recovery_codes().await?),
+        _ => Some(issue_recovery_codes(&state, &user_id).await?),
     };
 
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
 }
 
+/// Codes handed out for an address that already has an account. They are
+/// never stored, but hashed all the same so the response takes as long as
+/// issuing real ones.
+async fn generate_unstored_recovery_codes() -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_set();
+
+    for code in &codes {
+        compute_password_hash(code.as_ref().to_owned())
+            .await
+            .map_err(AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(codes.iter().map(RecoveryCode::formatted).collect())
+}
+
+#[tracing::instrument(name = "Send signup attempt email", skip_all)]
+async fn send_signup_attempt_email(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .email_client
+        .send_email(
+            email,
+            "Signup attempt",
+            "Someone tried to sign up with this email address, which already has an \
+             account. If this was you, log in or reset your password instead. \
+             Otherwise you can ignore this email.",
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
 #[derive(Serialize)]
 pub struct SignupResponse {
     pub message: String,
+    #[serde(rename = "recoveryCodes", skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -52,9 +116,17 @@ impl AuthRequest for SignupRequest {
     fn into_user(self) -> Result<User, AuthAPIError> {
         let email =
             Email::parse(self.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
+        check_password_policy(&self.password, &email)?;
         let password =
             Password::parse(self.password.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-        Ok(User::new(email, password, self.requires_2fa))
+        // Authenticator apps are enrolled after signup, so the choice here
+        // is only between email codes and no second factor.
+        let two_fa_method = match self.requires_2fa {
+            true => TwoFAMethod::Email,
+            false => TwoFAMethod::None,
+        };
+
+        Ok(User::new(email, password, two_fa_method))
     }
 }
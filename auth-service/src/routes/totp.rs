@@ -0,0 +1,206 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, TotpSecret, TwoFACode, TwoFAMethod},
+    utils::auth::authenticate,
+};
+
+use super::issue_recovery_codes;
+
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    // Enrolling again would replace the secret the user's app is using.
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TotpAlreadyEnabled);
+    }
+
+    let secret = TotpSecret::default();
+    let otpauth_uri = secret
+        .otpauth_uri(&user.email)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_totp_secret(&user.email, Some(secret.clone()))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(EnrollTotpResponse {
+        secret: secret.as_ref().expose_secret().to_owned(),
+        otpauth_uri,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<TotpCodeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TotpAlreadyEnabled);
+    }
+
+    let secret = get_totp_secret(&state, &user.email).await?;
+
+    let step = verify_totp_code(&secret, &code)?.ok_or(AuthAPIError::IncorrectCredentials)?;
+    use_totp_step(&state, &user.email, step).await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&user.email, TwoFAMethod::Totp)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let recovery_codes = issue_recovery_codes(&state, &user.id).await?;
+
+    let response = Json(ConfirmTotpResponse {
+        message: "TOTP enabled".to_owned(),
+        recovery_codes,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Disable TOTP", skip_all)]
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<TotpCodeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if user.two_fa_method != TwoFAMethod::Totp {
+        return Err(AuthAPIError::TotpNotEnabled);
+    }
+
+    let secret = get_totp_secret(&state, &user.email).await?;
+
+    let step = verify_totp_code(&secret, &code)?.ok_or(AuthAPIError::IncorrectCredentials)?;
+    use_totp_step(&state, &user.email, step).await?;
+
+    let mut user_store = state.user_store.write().await;
+
+    user_store
+        .set_two_fa_method(&user.email, TwoFAMethod::None)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    user_store
+        .set_totp_secret(&user.email, None)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(user_store);
+
+    // Without a second factor there is nothing left to recover.
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .replace_codes(&user.id, Vec::new())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(TotpResponse {
+        message: "TOTP disabled".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub(crate) async fn get_totp_secret(
+    state: &AppState,
+    email: &Email,
+) -> Result<TotpSecret, AuthAPIError> {
+    state
+        .user_store
+        .read()
+        .await
+        .get_totp_secret(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .ok_or(AuthAPIError::TotpNotEnrolled)
+}
+
+/// Returns the time step of a code from the user's authenticator app, or
+/// `None` if it doesn't match. The step still has to go through
+/// [`use_totp_step`] before the code is accepted.
+pub(crate) fn verify_totp_code(
+    secret: &TotpSecret,
+    code: &TwoFACode,
+) -> Result<Option<u64>, AuthAPIError> {
+    secret
+        .verify_code(code, Utc::now().timestamp() as u64)
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+/// Accepts each TOTP time step only once per user, so a code that was seen
+/// by someone else can't be used again while it's still valid.
+pub(crate) async fn use_totp_step(
+    state: &AppState,
+    email: &Email,
+    step: u64,
+) -> Result<(), AuthAPIError> {
+    let used = state
+        .user_store
+        .write()
+        .await
+        .use_totp_step(email, step)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    match used {
+        true => Ok(()),
+        false => Err(AuthAPIError::IncorrectCredentials),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfirmTotpResponse {
+    pub message: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeRequest {
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: Secret<String>,
+}
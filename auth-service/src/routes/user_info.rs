@@ -0,0 +1,42 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::authenticate};
+
+#[tracing::instrument(name = "Get user info", skip_all)]
+pub async fn get_user_info(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let recovery_codes_remaining = state
+        .recovery_code_store
+        .read()
+        .await
+        .count_codes(&user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(UserInfoResponse {
+        email: user.email.as_ref().expose_secret().to_owned(),
+        email_verified: user.email_verified,
+        two_fa_method: user.two_fa_method.as_str().to_owned(),
+        recovery_codes_remaining,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserInfoResponse {
+    pub email: String,
+    #[serde(rename = "emailVerified")]
+    pub email_verified: bool,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
+    #[serde(rename = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: usize,
+}
@@ -1,18 +1,27 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        TwoFAMethod, User,
+    },
+    utils::constants::MAX_TWO_FA_ATTEMPTS,
+};
+
+use super::{
+    check_passkey_two_fa, get_totp_secret, start_session, use_totp_step, verify_totp_code,
+    ClientInfo, PasskeyAssertion,
 };
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email) {
@@ -25,35 +34,190 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let two_fa_code = match TwoFACode::parse(Secret::new(request.two_fa_code)) {
-        Ok(two_fa_code) => two_fa_code,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    let second_factor = match request.second_factor {
+        SecondFactor::Code { two_fa_code } => match TwoFACode::parse(Secret::new(two_fa_code)) {
+            Ok(two_fa_code) => ParsedSecondFactor::Code(two_fa_code),
+            Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+        },
+        SecondFactor::Passkey { passkey } => ParsedSecondFactor::Passkey(passkey),
+    };
+
+    let user = state.user_store.read().await.get_user(&email).await;
+
+    let user = match user {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    let checked = match second_factor {
+        ParsedSecondFactor::Code(two_fa_code) => {
+            check_two_fa_code(&state, &user, &login_attempt_id, &two_fa_code).await
+        }
+        ParsedSecondFactor::Passkey(passkey) => {
+            check_passkey_two_fa(&state, &user, &login_attempt_id, passkey).await
+        }
+    };
+
+    if let Err(e) = checked {
+        return (jar, Err(e));
+    }
+
+    let (jwt_cookie, refresh_cookie) = match start_session(&state, &user.id, client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
     };
 
+    let updated_jar = jar.add(jwt_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK))
+}
+
+/// Sends email 2FA users a new code for a login they already started, for when
+/// the first email got lost, without making them enter their password again.
+#[tracing::instrument(name = "Resend 2FA code", skip_all)]
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let login_attempt_id = LoginAttemptId::parse(Secret::new(request.login_attempt_id))
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user = state.user_store.read().await.get_user(&email).await;
+
+    let user = match user {
+        Ok(user) => user,
+        Err(_) => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    // TOTP users read their code from their authenticator app
+    if user.two_fa_method != TwoFAMethod::Email {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let two_fa_code = TwoFACode::default();
     let mut two_fa_code_store = state.two_fa_code_store.write().await;
 
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
+    match two_fa_code_store.get_code(&login_attempt_id).await {
+        Ok((user_id, _)) if user_id == user.id => (),
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    }
+
+    match two_fa_code_store
+        .replace_code(&login_attempt_id, two_fa_code.clone())
+        .await
+    {
+        Ok(_) => (),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(TwoFACodeStoreError::ResendThrottled) => return Err(AuthAPIError::TooManyRequests),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    drop(two_fa_code_store);
+
+    state
+        .email_client
+        .send_email(
+            &user.email,
+            "2FA Code",
+            two_fa_code.as_ref().expose_secret(),
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Checks a code against the user's pending challenge for `login_attempt_id`,
+/// or against their authenticator app for TOTP users, and uses the challenge
+/// up when it matches. Other challenges of the user stay pending.
+pub(crate) async fn check_two_fa_code(
+    state: &AppState,
+    user: &User,
+    login_attempt_id: &LoginAttemptId,
+    two_fa_code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    // Looked up before locking the 2FA code store, since login holds the user
+    // store while it takes that lock.
+    let totp_secret = match user.two_fa_method {
+        TwoFAMethod::Totp => Some(get_totp_secret(state, &user.email).await?),
+        _ => None,
+    };
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    let (user_id, stored_code) = match two_fa_code_store.get_code(login_attempt_id).await {
         Ok(tuple) => tuple,
-        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(_) => return Err(AuthAPIError::IncorrectCredentials),
     };
 
-    if !(code_tuple.1 == two_fa_code && code_tuple.0 == login_attempt_id) {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    if user_id != user.id {
+        return Err(AuthAPIError::IncorrectCredentials);
     }
 
-    let jwt_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    let totp_step = match &totp_secret {
+        Some(secret) => verify_totp_code(secret, two_fa_code)?,
+        None => None,
     };
 
-    let updated_jar = jar.add(jwt_cookie);
+    let code_matches = match &totp_secret {
+        Some(_) => totp_step.is_some(),
+        None => stored_code == *two_fa_code,
+    };
 
-    match two_fa_code_store.remove_code(&email).await {
-        Ok(_) => {}
-        Err(e) => return (updated_jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    if !code_matches {
+        record_wrong_code(&mut *two_fa_code_store, login_attempt_id).await?;
+        return Err(AuthAPIError::IncorrectCredentials);
     }
 
-    (updated_jar, Ok(StatusCode::OK))
+    two_fa_code_store
+        .remove_code(login_attempt_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(two_fa_code_store);
+
+    // A replayed TOTP code still uses up the attempt, so it can't be retried
+    match totp_step {
+        Some(step) => use_totp_step(state, &user.email, step).await,
+        None => Ok(()),
+    }
+}
+
+/// Counts a wrong code against a pending challenge and throws the challenge
+/// away once too many were tried, so a new one has to be requested.
+pub(crate) async fn record_wrong_code(
+    two_fa_code_store: &mut (dyn TwoFACodeStore + Send + Sync),
+    login_attempt_id: &LoginAttemptId,
+) -> Result<(), AuthAPIError> {
+    let attempts = match two_fa_code_store
+        .record_failed_attempt(login_attempt_id)
+        .await
+    {
+        Ok(attempts) => attempts,
+        // Expired or already thrown away
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => return Ok(()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if attempts < MAX_TWO_FA_ATTEMPTS {
+        return Ok(());
+    }
+
+    match two_fa_code_store.remove_code(login_attempt_id).await {
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Resend2FARequest {
+    pub email: Secret<String>,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
 }
 
 #[derive(Deserialize)]
@@ -61,6 +225,25 @@ pub struct Verify2FARequest {
     pub email: Secret<String>,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
-    #[serde(rename = "2FACode")]
-    pub two_fa_code: String,
+    #[serde(flatten)]
+    pub second_factor: SecondFactor,
+}
+
+/// Either a code, or a passkey assertion for the challenge from
+/// `/passkeys/2fa/start`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SecondFactor {
+    Code {
+        #[serde(rename = "2FACode")]
+        two_fa_code: String,
+    },
+    Passkey {
+        passkey: PasskeyAssertion,
+    },
+}
+
+enum ParsedSecondFactor {
+    Code(TwoFACode),
+    Passkey(PasskeyAssertion),
 }
@@ -0,0 +1,171 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailVerificationToken, EmailVerificationTokenStoreError,
+        UserStoreError,
+    },
+    utils::constants::AUTH_SERVICE_URL,
+};
+
+#[tracing::instrument(name = "Verify email", skip_all)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    confirm_email(&state, request.token).await
+}
+
+/// Target of the link in the verification email, which is opened with a GET.
+#[tracing::instrument(name = "Verify email link", skip_all)]
+pub async fn verify_email_link(
+    State(state): State<AppState>,
+    Query(request): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    confirm_email(&state, request.token).await
+}
+
+async fn confirm_email(
+    state: &AppState,
+    token: Secret<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = EmailVerificationToken::parse(token)
+        .map_err(|_| AuthAPIError::InvalidEmailVerificationToken)?;
+
+    let email = match state
+        .email_verification_token_store
+        .write()
+        .await
+        .consume_token(&token)
+        .await
+    {
+        Ok(email) => email,
+        Err(EmailVerificationTokenStoreError::TokenNotFound) => {
+            return Err(AuthAPIError::InvalidEmailVerificationToken)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    match state
+        .user_store
+        .write()
+        .await
+        .set_email_verified(&email)
+        .await
+    {
+        Ok(_) => (),
+        Err(UserStoreError::UserNotFound) => {
+            return Err(AuthAPIError::InvalidEmailVerificationToken)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email verified successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Resend verification email", skip_all)]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Throttle before looking the account up, so a 429 doesn't reveal whether
+    // the address is registered.
+    match state
+        .email_verification_token_store
+        .write()
+        .await
+        .record_email_sent(&email)
+        .await
+    {
+        Ok(_) => (),
+        Err(EmailVerificationTokenStoreError::ResendThrottled) => {
+            return Err(AuthAPIError::TooManyRequests)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = (
+        StatusCode::OK,
+        Json(VerifyEmailResponse {
+            message: "Verification email sent if the account exists and is unverified".to_owned(),
+        }),
+    );
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok(response),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if !user.email_verified {
+        send_verification_email(&state, &email).await?;
+    }
+
+    Ok(response)
+}
+
+#[tracing::instrument(name = "Send verification email", skip_all)]
+pub(crate) async fn send_verification_email(
+    state: &AppState,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    let token = EmailVerificationToken::default();
+
+    if let Err(e) = state
+        .email_verification_token_store
+        .write()
+        .await
+        .add_token(email.clone(), token.clone())
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    let link = format!(
+        "{}/verify-email?token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.as_ref().expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Verify your email address",
+            &format!(
+                "Use the following link to verify your email address: {}",
+                link
+            ),
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: Secret<String>,
+}
@@ -1,20 +1,25 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::Secret;
 use serde::Deserialize;
 
 use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
 
 #[tracing::instrument(name = "Verify Token", skip_all)]
 pub async fn verify_token(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    match validate_token(&request.token).await {
-        Ok(_) => Ok(StatusCode::OK.into_response()),
-        Err(_) => Err(AuthAPIError::InvalidToken),
-    }
+    validate_token(
+        &request.token,
+        &state.banned_token_store,
+        &state.session_store,
+    )
+    .await?;
+
+    Ok(StatusCode::OK)
 }
 
 #[derive(Deserialize)]
 pub struct VerifyTokenRequest {
-    pub token: String,
+    pub token: Secret<String>,
 }
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    domain::{Email, EmailChangeToken, EmailChangeTokenStore, EmailChangeTokenStoreError},
+    utils::constants::EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapEmailChangeTokenStore {
+    tokens: HashMap<String, (Email, Email, DateTime<Utc>)>,
+}
+
+#[async_trait::async_trait]
+impl EmailChangeTokenStore for HashmapEmailChangeTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        new_email: Email,
+        token: EmailChangeToken,
+    ) -> Result<(), EmailChangeTokenStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(EMAIL_CHANGE_TOKEN_TTL_SECONDS as i64);
+
+        self.tokens.retain(|_, (_, _, expiry)| *expiry > Utc::now());
+        self.tokens
+            .insert(token.hash(), (email, new_email, expires_at));
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &EmailChangeToken,
+    ) -> Result<(Email, Email), EmailChangeTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((email, new_email, expires_at)) if expires_at > Utc::now() => {
+                Ok((email, new_email))
+            }
+            _ => Err(EmailChangeTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let mut store = HashmapEmailChangeTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let new_email = Email::parse(Secret::new("janedoe@example.com".to_owned())).unwrap();
+        let token = EmailChangeToken::default();
+
+        store
+            .add_token(email.clone(), new_email.clone(), token.clone())
+            .await
+            .unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.consume_token(&token).await, Ok((email, new_email)));
+        // Token already used /////////////////////////////////////////////////
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(EmailChangeTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_expired_token() {
+        let mut store = HashmapEmailChangeTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let new_email = Email::parse(Secret::new("janedoe@example.com".to_owned())).unwrap();
+        let token = EmailChangeToken::default();
+
+        store.tokens.insert(
+            token.hash(),
+            (email, new_email, Utc::now() - Duration::seconds(1)),
+        );
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(EmailChangeTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    domain::{
+        Email, EmailVerificationToken, EmailVerificationTokenStore,
+        EmailVerificationTokenStoreError,
+    },
+    utils::constants::{
+        EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    },
+};
+
+#[derive(Default)]
+pub struct HashmapEmailVerificationTokenStore {
+    tokens: HashMap<String, (Email, DateTime<Utc>)>,
+    last_sent: HashMap<Email, DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for HashmapEmailVerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let expires_at =
+            Utc::now() + Duration::seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS as i64);
+
+        self.tokens.retain(|_, (_, expiry)| *expiry > Utc::now());
+        self.tokens.insert(token.hash(), (email, expires_at));
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((email, expires_at)) if expires_at > Utc::now() => Ok(email),
+            _ => Err(EmailVerificationTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn record_email_sent(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let interval = Duration::seconds(EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS as i64);
+
+        if let Some(sent_at) = self.last_sent.get(email) {
+            if *sent_at + interval > Utc::now() {
+                return Err(EmailVerificationTokenStoreError::ResendThrottled);
+            }
+        }
+
+        self.last_sent.insert(email.clone(), Utc::now());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = EmailVerificationToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+        // Token already used /////////////////////////////////////////////////
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(EmailVerificationTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_expired_token() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = EmailVerificationToken::default();
+
+        store
+            .tokens
+            .insert(token.hash(), (email, Utc::now() - Duration::seconds(1)));
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(EmailVerificationTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_email_sent() {
+        let mut store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.record_email_sent(&email).await, Ok(()));
+        // Sent too recently //////////////////////////////////////////////////
+        assert_eq!(
+            store.record_email_sent(&email).await,
+            Err(EmailVerificationTokenStoreError::ResendThrottled)
+        );
+
+        // Interval elapsed ///////////////////////////////////////////////////
+        store.last_sent.insert(
+            email.clone(),
+            Utc::now() - Duration::seconds(EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS as i64),
+        );
+        assert_eq!(store.record_email_sent(&email).await, Ok(()));
+    }
+}
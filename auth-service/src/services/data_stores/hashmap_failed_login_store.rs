@@ -0,0 +1,119 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    domain::{FailedLoginKey, FailedLoginStore, FailedLoginStoreError},
+    utils::constants::FAILED_LOGIN_WINDOW_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapFailedLoginStore {
+    failures: HashMap<FailedLoginKey, (u32, DateTime<Utc>)>,
+    lockouts: HashMap<FailedLoginKey, DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for HashmapFailedLoginStore {
+    async fn record_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::seconds(FAILED_LOGIN_WINDOW_SECONDS as i64);
+
+        let count = match self.failures.get(key) {
+            Some((count, expiry)) if *expiry > now => count + 1,
+            _ => 1,
+        };
+
+        self.failures.insert(key.clone(), (count, expires_at));
+        Ok(count)
+    }
+
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        self.failures.remove(key);
+        Ok(())
+    }
+
+    async fn lock(
+        &mut self,
+        key: &FailedLoginKey,
+        duration: Duration,
+    ) -> Result<(), FailedLoginStoreError> {
+        let duration = chrono::Duration::from_std(duration)
+            .map_err(|e| FailedLoginStoreError::UnexpectedError(e.into()))?;
+
+        self.lockouts.retain(|_, until| *until > Utc::now());
+        self.lockouts.insert(key.clone(), Utc::now() + duration);
+        Ok(())
+    }
+
+    async fn get_lockout(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<Duration>, FailedLoginStoreError> {
+        Ok(self
+            .lockouts
+            .get(key)
+            .and_then(|until| (*until - Utc::now()).to_std().ok())
+            .filter(|remaining| !remaining.is_zero()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::domain::Email;
+
+    fn account_key() -> FailedLoginKey {
+        FailedLoginKey::Account(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_record_failure() {
+        let mut store = HashmapFailedLoginStore::default();
+        let key = account_key();
+        let other_key = FailedLoginKey::IpAddress("127.0.0.1".to_owned());
+
+        assert_eq!(store.record_failure(&key).await, Ok(1));
+        assert_eq!(store.record_failure(&key).await, Ok(2));
+        assert_eq!(store.record_failure(&other_key).await, Ok(1));
+
+        // Cleared on success /////////////////////////////////////////////////
+        assert_eq!(store.clear_failures(&key).await, Ok(()));
+        assert_eq!(store.record_failure(&key).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_record_failure_after_window() {
+        let mut store = HashmapFailedLoginStore::default();
+        let key = account_key();
+
+        store
+            .failures
+            .insert(key.clone(), (10, Utc::now() - chrono::Duration::seconds(1)));
+
+        assert_eq!(store.record_failure(&key).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_lockout() {
+        let mut store = HashmapFailedLoginStore::default();
+        let key = account_key();
+
+        assert_eq!(store.get_lockout(&key).await, Ok(None));
+
+        assert_eq!(store.lock(&key, Duration::from_secs(60)).await, Ok(()));
+
+        let remaining = store.get_lockout(&key).await.unwrap().unwrap();
+        assert!(remaining > Duration::from_secs(55) && remaining <= Duration::from_secs(60));
+
+        // Expired ////////////////////////////////////////////////////////////
+        store
+            .lockouts
+            .insert(key.clone(), Utc::now() - chrono::Duration::seconds(1));
+        assert_eq!(store.get_lockout(&key).await, Ok(None));
+    }
+}
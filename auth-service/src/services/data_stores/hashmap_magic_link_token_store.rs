@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+
+use crate::{
+    domain::{Email, MagicLinkToken, MagicLinkTokenStore, MagicLinkTokenStoreError},
+    utils::constants::{MAGIC_LINK_RESEND_INTERVAL_SECONDS, MAGIC_LINK_TOKEN_TTL_SECONDS},
+};
+
+#[derive(Default)]
+pub struct HashmapMagicLinkTokenStore {
+    tokens: HashMap<String, (Email, DateTime<Utc>)>,
+    /// When a link was last requested, keyed by address and by IP address.
+    last_requested: HashMap<String, DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl MagicLinkTokenStore for HashmapMagicLinkTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: MagicLinkToken,
+    ) -> Result<(), MagicLinkTokenStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(MAGIC_LINK_TOKEN_TTL_SECONDS as i64);
+
+        self.tokens.retain(|_, (_, expiry)| *expiry > Utc::now());
+        self.tokens.insert(token.hash(), (email, expires_at));
+        Ok(())
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &MagicLinkToken,
+    ) -> Result<Email, MagicLinkTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((email, expires_at)) if expires_at > Utc::now() => Ok(email),
+            _ => Err(MagicLinkTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn record_link_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), MagicLinkTokenStoreError> {
+        let now = Utc::now();
+        let interval = Duration::seconds(MAGIC_LINK_RESEND_INTERVAL_SECONDS as i64);
+
+        let keys: Vec<String> = ip_address
+            .map(|ip_address| format!("ip:{}", ip_address))
+            .into_iter()
+            .chain(std::iter::once(format!(
+                "email:{}",
+                email.lowercase().expose_secret()
+            )))
+            .collect();
+
+        if keys.iter().any(|key| {
+            self.last_requested
+                .get(key)
+                .is_some_and(|requested_at| *requested_at + interval > now)
+        }) {
+            return Err(MagicLinkTokenStoreError::ResendThrottled);
+        }
+
+        for key in keys {
+            self.last_requested.insert(key, now);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let mut store = HashmapMagicLinkTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = MagicLinkToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+        // Token already used /////////////////////////////////////////////////
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(MagicLinkTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_link_requested() {
+        let mut store = HashmapMagicLinkTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let other_email = Email::parse(Secret::new("janedoe@example.com".to_owned())).unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            store.record_link_requested(&email, Some("10.0.0.1")).await,
+            Ok(())
+        );
+        // Same address from elsewhere ////////////////////////////////////////
+        assert_eq!(
+            store.record_link_requested(&email, Some("10.0.0.2")).await,
+            Err(MagicLinkTokenStoreError::ResendThrottled)
+        );
+        // Same IP address for another account ////////////////////////////////
+        assert_eq!(
+            store
+                .record_link_requested(&other_email, Some("10.0.0.1"))
+                .await,
+            Err(MagicLinkTokenStoreError::ResendThrottled)
+        );
+
+        for requested_at in store.last_requested.values_mut() {
+            *requested_at -= Duration::seconds(MAGIC_LINK_RESEND_INTERVAL_SECONDS as i64);
+        }
+
+        assert_eq!(
+            store
+                .record_link_requested(&other_email, Some("10.0.0.1"))
+                .await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_expired_token() {
+        let mut store = HashmapMagicLinkTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = MagicLinkToken::default();
+
+        store
+            .tokens
+            .insert(token.hash(), (email, Utc::now() - Duration::seconds(1)));
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(MagicLinkTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    domain::{
+        PasskeyCeremony, PasskeyChallenge, PasskeyChallengeStore, PasskeyChallengeStoreError,
+    },
+    utils::constants::PASSKEY_CHALLENGE_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapPasskeyChallengeStore {
+    challenges: HashMap<PasskeyChallenge, (PasskeyCeremony, DateTime<Utc>)>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyChallengeStore for HashmapPasskeyChallengeStore {
+    async fn add_challenge(
+        &mut self,
+        challenge: PasskeyChallenge,
+        ceremony: PasskeyCeremony,
+    ) -> Result<(), PasskeyChallengeStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(PASSKEY_CHALLENGE_TTL_SECONDS as i64);
+
+        self.challenges
+            .retain(|_, (_, expiry)| *expiry > Utc::now());
+        self.challenges.insert(challenge, (ceremony, expires_at));
+        Ok(())
+    }
+
+    async fn consume_challenge(
+        &mut self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PasskeyCeremony, PasskeyChallengeStoreError> {
+        match self.challenges.remove(challenge) {
+            Some((ceremony, expires_at)) if expires_at > Utc::now() => Ok(ceremony),
+            _ => Err(PasskeyChallengeStoreError::ChallengeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::UserId;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_challenge() {
+        let mut store = HashmapPasskeyChallengeStore::default();
+        let challenge = PasskeyChallenge::default();
+        let ceremony = PasskeyCeremony::Registration {
+            user_id: UserId::default(),
+        };
+
+        store
+            .add_challenge(challenge.clone(), ceremony.clone())
+            .await
+            .unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.consume_challenge(&challenge).await, Ok(ceremony));
+        // Challenge already used /////////////////////////////////////////////
+        assert_eq!(
+            store.consume_challenge(&challenge).await,
+            Err(PasskeyChallengeStoreError::ChallengeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_expired_challenge() {
+        let mut store = HashmapPasskeyChallengeStore::default();
+        let challenge = PasskeyChallenge::default();
+
+        store.challenges.insert(
+            challenge.clone(),
+            (PasskeyCeremony::Login, Utc::now() - Duration::seconds(1)),
+        );
+
+        assert_eq!(
+            store.consume_challenge(&challenge).await,
+            Err(PasskeyChallengeStoreError::ChallengeNotFound)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{CredentialId, Passkey, PasskeyStore, PasskeyStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapPasskeyStore {
+    passkeys: HashMap<CredentialId, Passkey>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for HashmapPasskeyStore {
+    async fn add_passkey(&mut self, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        if self.passkeys.contains_key(&passkey.credential_id) {
+            return Err(PasskeyStoreError::PasskeyAlreadyExists);
+        }
+
+        self.passkeys.insert(passkey.credential_id.clone(), passkey);
+        Ok(())
+    }
+
+    async fn get_passkey(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<Passkey, PasskeyStoreError> {
+        self.passkeys
+            .get(credential_id)
+            .cloned()
+            .ok_or(PasskeyStoreError::PasskeyNotFound)
+    }
+
+    async fn get_passkeys(&self, user_id: &UserId) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        let mut passkeys: Vec<Passkey> = self
+            .passkeys
+            .values()
+            .filter(|passkey| passkey.user_id == *user_id)
+            .cloned()
+            .collect();
+
+        passkeys.sort_by_key(|passkey| passkey.created_at);
+        Ok(passkeys)
+    }
+
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), PasskeyStoreError> {
+        let passkey = self
+            .passkeys
+            .get_mut(credential_id)
+            .ok_or(PasskeyStoreError::PasskeyNotFound)?;
+
+        passkey.sign_count = sign_count;
+        passkey.last_used_at = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn remove_passkey(
+        &mut self,
+        user_id: &UserId,
+        credential_id: &CredentialId,
+    ) -> Result<(), PasskeyStoreError> {
+        match self.passkeys.get(credential_id) {
+            Some(passkey) if passkey.user_id == *user_id => {
+                self.passkeys.remove(credential_id);
+                Ok(())
+            }
+            _ => Err(PasskeyStoreError::PasskeyNotFound),
+        }
+    }
+
+    async fn remove_passkeys(&mut self, user_id: &UserId) -> Result<(), PasskeyStoreError> {
+        self.passkeys
+            .retain(|_, passkey| passkey.user_id != *user_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passkey(user_id: UserId, credential_id: u8) -> Passkey {
+        Passkey {
+            credential_id: CredentialId::from_bytes(vec![credential_id; 16]).unwrap(),
+            user_id,
+            public_key: vec![4; 65],
+            sign_count: 0,
+            created_at: Utc::now(),
+            last_used_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_passkey() {
+        let mut store = HashmapPasskeyStore::default();
+        let passkey = passkey(UserId::default(), 1);
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.add_passkey(passkey.clone()).await, Ok(()));
+        // Credential already registered //////////////////////////////////////
+        assert_eq!(
+            store.add_passkey(passkey).await,
+            Err(PasskeyStoreError::PasskeyAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_passkey() {
+        let mut store = HashmapPasskeyStore::default();
+        let passkey = passkey(UserId::default(), 1);
+
+        store.add_passkey(passkey.clone()).await.unwrap();
+
+        assert_eq!(store.get_passkey(&passkey.credential_id).await, Ok(passkey));
+        assert_eq!(
+            store
+                .get_passkey(&CredentialId::from_bytes(vec![2; 16]).unwrap())
+                .await,
+            Err(PasskeyStoreError::PasskeyNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_passkeys() {
+        let mut store = HashmapPasskeyStore::default();
+        let user_id = UserId::default();
+
+        store.add_passkey(passkey(user_id, 1)).await.unwrap();
+        store.add_passkey(passkey(user_id, 2)).await.unwrap();
+        store
+            .add_passkey(passkey(UserId::default(), 3))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_passkeys(&user_id).await.unwrap().len(), 2);
+        assert!(store
+            .get_passkeys(&UserId::default())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count() {
+        let mut store = HashmapPasskeyStore::default();
+        let passkey = passkey(UserId::default(), 1);
+
+        store.add_passkey(passkey.clone()).await.unwrap();
+
+        assert_eq!(
+            store.update_sign_count(&passkey.credential_id, 7).await,
+            Ok(())
+        );
+        assert_eq!(
+            store
+                .get_passkey(&passkey.credential_id)
+                .await
+                .unwrap()
+                .sign_count,
+            7
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_passkey() {
+        let mut store = HashmapPasskeyStore::default();
+        let user_id = UserId::default();
+        let passkey = passkey(user_id, 1);
+
+        store.add_passkey(passkey.clone()).await.unwrap();
+
+        // Another user's passkey /////////////////////////////////////////////
+        assert_eq!(
+            store
+                .remove_passkey(&UserId::default(), &passkey.credential_id)
+                .await,
+            Err(PasskeyStoreError::PasskeyNotFound)
+        );
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            store.remove_passkey(&user_id, &passkey.credential_id).await,
+            Ok(())
+        );
+        assert_eq!(
+            store.get_passkey(&passkey.credential_id).await,
+            Err(PasskeyStoreError::PasskeyNotFound)
+        );
+        // Already removed ////////////////////////////////////////////////////
+        assert_eq!(
+            store.remove_passkey(&user_id, &passkey.credential_id).await,
+            Err(PasskeyStoreError::PasskeyNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_passkeys() {
+        let mut store = HashmapPasskeyStore::default();
+        let user_id = UserId::default();
+        let other_user_id = UserId::default();
+
+        store.add_passkey(passkey(user_id, 1)).await.unwrap();
+        store.add_passkey(passkey(user_id, 2)).await.unwrap();
+        store.add_passkey(passkey(other_user_id, 3)).await.unwrap();
+
+        assert_eq!(store.remove_passkeys(&user_id).await, Ok(()));
+        assert!(store.get_passkeys(&user_id).await.unwrap().is_empty());
+        assert_eq!(store.get_passkeys(&other_user_id).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use secrecy::ExposeSecret;
+
+use crate::{
+    domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    utils::constants::{PASSWORD_RESET_RESEND_INTERVAL_SECONDS, PASSWORD_RESET_TOKEN_TTL_SECONDS},
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: HashMap<String, (Email, DateTime<Utc>)>,
+    /// When a reset was last requested, keyed by address and by IP address.
+    last_requested: HashMap<String, DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(PASSWORD_RESET_TOKEN_TTL_SECONDS as i64);
+
+        self.tokens.retain(|_, (_, expiry)| *expiry > Utc::now());
+        self.tokens.insert(token.hash(), (email, expires_at));
+        Ok(())
+    }
+
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        match self.tokens.get(&token.hash()) {
+            Some((email, expires_at)) if *expires_at > Utc::now() => Ok(email.clone()),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        match self.tokens.remove(&token.hash()) {
+            Some((email, expires_at)) if expires_at > Utc::now() => Ok(email),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn remove_tokens(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens
+            .retain(|_, (token_email, _)| token_email != email);
+        Ok(())
+    }
+
+    async fn record_reset_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let now = Utc::now();
+        let interval = Duration::seconds(PASSWORD_RESET_RESEND_INTERVAL_SECONDS as i64);
+
+        let keys: Vec<String> = ip_address
+            .map(|ip_address| format!("ip:{}", ip_address))
+            .into_iter()
+            .chain(std::iter::once(format!(
+                "email:{}",
+                email.lowercase().expose_secret()
+            )))
+            .collect();
+
+        if keys.iter().any(|key| {
+            self.last_requested
+                .get(key)
+                .is_some_and(|requested_at| *requested_at + interval > now)
+        }) {
+            return Err(PasswordResetTokenStoreError::ResendThrottled);
+        }
+
+        for key in keys {
+            self.last_requested.insert(key, now);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = PasswordResetToken::default();
+
+        let response = store.add_token(email.clone(), token.clone()).await;
+
+        assert!(response.is_ok());
+
+        let (stored_email, _) = store.tokens.get(&token.hash()).unwrap();
+        assert_eq!(&email, stored_email);
+    }
+
+    #[tokio::test]
+    async fn test_get_email() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        // Looking the token up doesn't use it up ////////////////////////////
+        assert_eq!(store.get_email(&token).await, Ok(email.clone()));
+        assert_eq!(store.get_email(&token).await, Ok(email.clone()));
+        // Unknown token //////////////////////////////////////////////////////
+        assert_eq!(
+            store.get_email(&PasswordResetToken::default()).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+        // Redeemed token /////////////////////////////////////////////////////
+        store.consume_token(&token).await.unwrap();
+        assert_eq!(
+            store.get_email(&token).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+        // Token already used /////////////////////////////////////////////////
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_tokens() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let other_email = Email::parse(Secret::new("janedoe@example.com".to_owned())).unwrap();
+        let tokens = [PasswordResetToken::default(), PasswordResetToken::default()];
+        let other_token = PasswordResetToken::default();
+
+        for token in &tokens {
+            store.add_token(email.clone(), token.clone()).await.unwrap();
+        }
+        store
+            .add_token(other_email.clone(), other_token.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.remove_tokens(&email).await, Ok(()));
+
+        for token in &tokens {
+            assert_eq!(
+                store.get_email(token).await,
+                Err(PasswordResetTokenStoreError::TokenNotFound)
+            );
+        }
+        // Other accounts keep theirs /////////////////////////////////////////
+        assert_eq!(store.get_email(&other_token).await, Ok(other_email));
+    }
+
+    #[tokio::test]
+    async fn test_consume_expired_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let token = PasswordResetToken::default();
+
+        store
+            .tokens
+            .insert(token.hash(), (email, Utc::now() - Duration::seconds(1)));
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_reset_requested() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let other_email = Email::parse(Secret::new("janedoe@example.com".to_owned())).unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            store.record_reset_requested(&email, Some("10.0.0.1")).await,
+            Ok(())
+        );
+        // Same address from elsewhere ////////////////////////////////////////
+        assert_eq!(
+            store.record_reset_requested(&email, Some("10.0.0.2")).await,
+            Err(PasswordResetTokenStoreError::ResendThrottled)
+        );
+        // Same IP address for another account ////////////////////////////////
+        assert_eq!(
+            store
+                .record_reset_requested(&other_email, Some("10.0.0.1"))
+                .await,
+            Err(PasswordResetTokenStoreError::ResendThrottled)
+        );
+
+        for requested_at in store.last_requested.values_mut() {
+            *requested_at -= Duration::seconds(PASSWORD_RESET_RESEND_INTERVAL_SECONDS as i64);
+        }
+
+        assert_eq!(
+            store
+                .record_reset_requested(&other_email, Some("10.0.0.1"))
+                .await,
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use crate::domain::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    codes: HashMap<UserId, Vec<RecoveryCode>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn replace_codes(
+        &mut self,
+        user_id: &UserId,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        if codes.is_empty() {
+            self.codes.remove(user_id);
+        } else {
+            self.codes.insert(*user_id, codes);
+        }
+        Ok(())
+    }
+
+    async fn consume_code(
+        &mut self,
+        user_id: &UserId,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let codes = self
+            .codes
+            .get_mut(user_id)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        let position = codes
+            .iter()
+            .position(|stored| stored == code)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        codes.remove(position);
+        Ok(())
+    }
+
+    async fn count_codes(&self, user_id: &UserId) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.codes.get(user_id).map_or(0, Vec::len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_code() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let user_id = UserId::default();
+        let codes = RecoveryCode::generate_set();
+
+        store.replace_codes(&user_id, codes.clone()).await.unwrap();
+        assert_eq!(store.count_codes(&user_id).await, Ok(codes.len()));
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.consume_code(&user_id, &codes[0]).await, Ok(()));
+        assert_eq!(store.count_codes(&user_id).await, Ok(codes.len() - 1));
+
+        // Code already burnt /////////////////////////////////////////////////
+        assert_eq!(
+            store.consume_code(&user_id, &codes[0]).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+
+        // Code that was never issued /////////////////////////////////////////
+        assert_eq!(
+            store.consume_code(&user_id, &RecoveryCode::default()).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_codes() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let user_id = UserId::default();
+        let old_codes = RecoveryCode::generate_set();
+        let new_codes = RecoveryCode::generate_set();
+
+        store
+            .replace_codes(&user_id, old_codes.clone())
+            .await
+            .unwrap();
+        store
+            .replace_codes(&user_id, new_codes.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.consume_code(&user_id, &old_codes[0]).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.consume_code(&user_id, &new_codes[0]).await, Ok(()));
+
+        store.replace_codes(&user_id, Vec::new()).await.unwrap();
+        assert_eq!(store.count_codes(&user_id).await, Ok(0));
+    }
+}
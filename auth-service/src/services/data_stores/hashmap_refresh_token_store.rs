@@ -0,0 +1,206 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    domain::{RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId, UserId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<String, (SessionId, bool, DateTime<Utc>)>,
+    families: HashMap<SessionId, UserId>,
+}
+
+impl HashmapRefreshTokenStore {
+    /// Forgets expired tokens, and the families left without any.
+    fn remove_expired(&mut self) {
+        let now = Utc::now();
+
+        self.tokens
+            .retain(|_, (_, _, expires_at)| *expires_at > now);
+
+        let live_families: HashSet<SessionId> = self
+            .tokens
+            .values()
+            .map(|(family_id, _, _)| *family_id)
+            .collect();
+
+        self.families
+            .retain(|family_id, _| live_families.contains(family_id));
+    }
+}
+
+fn get_expiry() -> DateTime<Utc> {
+    Utc::now() + Duration::seconds(REFRESH_TOKEN_TTL_SECONDS)
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        session_id: SessionId,
+        user_id: UserId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.remove_expired();
+
+        self.families.insert(session_id, user_id);
+        self.tokens
+            .insert(token.hash(), (session_id, false, get_expiry()));
+        Ok(())
+    }
+
+    async fn rotate_token(
+        &mut self,
+        current: &RefreshToken,
+        next: RefreshToken,
+    ) -> Result<(UserId, SessionId), RefreshTokenStoreError> {
+        let (family_id, used, expires_at) = *self
+            .tokens
+            .get(&current.hash())
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if expires_at <= Utc::now() {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        let user_id = self
+            .families
+            .get(&family_id)
+            .copied()
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if used {
+            self.families.remove(&family_id);
+            return Err(RefreshTokenStoreError::TokenReused(user_id));
+        }
+
+        self.tokens
+            .insert(current.hash(), (family_id, true, expires_at));
+        self.tokens
+            .insert(next.hash(), (family_id, false, get_expiry()));
+        Ok((user_id, family_id))
+    }
+
+    async fn revoke_family(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.families
+            .remove(session_id)
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rotate_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let user_id = UserId::default();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+
+        let session_id = SessionId::default();
+
+        store
+            .add_token(session_id, user_id, first.clone())
+            .await
+            .unwrap();
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            store.rotate_token(&first, second.clone()).await,
+            Ok((user_id, session_id))
+        );
+
+        // Unknown token //////////////////////////////////////////////////////
+        assert_eq!(
+            store
+                .rotate_token(&RefreshToken::default(), RefreshToken::default())
+                .await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reuse_revokes_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let user_id = UserId::default();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+
+        store
+            .add_token(SessionId::default(), user_id, first.clone())
+            .await
+            .unwrap();
+        store.rotate_token(&first, second.clone()).await.unwrap();
+
+        // Old token presented again //////////////////////////////////////////
+        assert_eq!(
+            store.rotate_token(&first, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenReused(user_id))
+        );
+        // Latest token of the family no longer works /////////////////////////
+        assert_eq!(
+            store.rotate_token(&second, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let user_id = UserId::default();
+        let expired = RefreshToken::default();
+        let other = RefreshToken::default();
+
+        let session_id = SessionId::default();
+
+        store
+            .add_token(session_id, user_id, expired.clone())
+            .await
+            .unwrap();
+
+        store.tokens.get_mut(&expired.hash()).unwrap().2 = Utc::now() - Duration::seconds(1);
+
+        assert_eq!(
+            store.rotate_token(&expired, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+
+        // Expired tokens and their families are forgotten ////////////////////
+        store
+            .add_token(SessionId::default(), user_id, other)
+            .await
+            .unwrap();
+
+        assert!(!store.tokens.contains_key(&expired.hash()));
+        assert!(!store.families.contains_key(&session_id));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let user_id = UserId::default();
+        let token = RefreshToken::default();
+
+        let session_id = SessionId::default();
+
+        store
+            .add_token(session_id, user_id, token.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.revoke_family(&session_id).await, Ok(()));
+        assert_eq!(
+            store.rotate_token(&token, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use chrono::{Duration, Utc};
+
+use crate::{
+    domain::{Session, SessionId, SessionStore, SessionStoreError, UserId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<SessionId, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| !is_expired(session));
+        self.sessions.insert(session.id, session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .filter(|session| !is_expired(session))
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| &session.user_id == user_id && !is_expired(session))
+            .cloned()
+            .collect();
+
+        sessions.sort_by_key(|session| Reverse(session.last_seen));
+        Ok(sessions)
+    }
+
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let session = self
+            .sessions
+            .get_mut(id)
+            .filter(|session| !is_expired(session))
+            .ok_or(SessionStoreError::SessionNotFound)?;
+
+        session.last_seen = Utc::now();
+        Ok(())
+    }
+
+    async fn remove_session(
+        &mut self,
+        user_id: &UserId,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError> {
+        match self.sessions.get(id) {
+            Some(session) if &session.user_id == user_id => {
+                self.sessions.remove(id);
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_sessions(
+        &mut self,
+        user_id: &UserId,
+    ) -> Result<Vec<SessionId>, SessionStoreError> {
+        let ids: Vec<SessionId> = self
+            .sessions
+            .values()
+            .filter(|session| &session.user_id == user_id)
+            .map(|session| session.id)
+            .collect();
+
+        for id in &ids {
+            self.sessions.remove(id);
+        }
+
+        Ok(ids)
+    }
+}
+
+/// Sessions end once they've been idle for as long as a refresh token lives,
+/// matching the Postgres and Redis stores.
+fn is_expired(session: &Session) -> bool {
+    session.last_seen <= Utc::now() - Duration::seconds(REFRESH_TOKEN_TTL_SECONDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_get_session() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let session = Session::new(
+            user_id,
+            Some("Mozilla/5.0".to_owned()),
+            Some("127.0.0.1".to_owned()),
+        );
+
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.get_session(&session.id).await, Ok(session.clone()));
+        assert_eq!(store.get_sessions(&user_id).await, Ok(vec![session]));
+        assert_eq!(
+            store.get_session(&SessionId::default()).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_touch_session() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let session = Session::new(user_id, None, None);
+
+        store.add_session(session.clone()).await.unwrap();
+        store.touch_session(&session.id).await.unwrap();
+
+        let touched = store.get_session(&session.id).await.unwrap();
+        assert!(touched.last_seen >= session.last_seen);
+        assert_eq!(touched.created_at, session.created_at);
+    }
+
+    #[tokio::test]
+    async fn test_expired_session() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let mut session = Session::new(user_id, None, None);
+        session.last_seen = Utc::now() - Duration::seconds(REFRESH_TOKEN_TTL_SECONDS + 1);
+
+        store.sessions.insert(session.id, session.clone());
+
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(store.get_sessions(&user_id).await, Ok(Vec::new()));
+        // Expired sessions can't be brought back ////////////////////////////
+        assert_eq!(
+            store.touch_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        // and are purged when the next one is added //////////////////////////
+        store
+            .add_session(Session::new(user_id, None, None))
+            .await
+            .unwrap();
+        assert!(!store.sessions.contains_key(&session.id));
+    }
+
+    #[tokio::test]
+    async fn test_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let session = Session::new(user_id, None, None);
+
+        store.add_session(session.clone()).await.unwrap();
+
+        // Someone else's session /////////////////////////////////////////////
+        assert_eq!(
+            store.remove_session(&UserId::default(), &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(store.remove_session(&user_id, &session.id).await, Ok(()));
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let first = Session::new(user_id, None, None);
+        let second = Session::new(user_id, None, None);
+        let other = Session::new(UserId::default(), None, None);
+
+        for session in [first.clone(), second.clone(), other.clone()] {
+            store.add_session(session).await.unwrap();
+        }
+
+        let mut removed = store.remove_sessions(&user_id).await.unwrap();
+        removed.sort_by_key(|id| id.to_string());
+
+        let mut expected = vec![first.id, second.id];
+        expected.sort_by_key(|id| id.to_string());
+
+        assert_eq!(removed, expected);
+        assert_eq!(store.get_sessions(&user_id).await, Ok(Vec::new()));
+        assert_eq!(store.get_session(&other.id).await, Ok(other));
+    }
+}
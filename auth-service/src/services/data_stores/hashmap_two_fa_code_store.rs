@@ -1,100 +1,459 @@
 use std::collections::HashMap;
 
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    domain::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, UserId},
+    utils::constants::{
+        MAX_PENDING_TWO_FA_CODES, MAX_TWO_FA_RESENDS, TWO_FA_CODE_TTL_SECONDS,
+        TWO_FA_RESEND_INTERVAL_SECONDS,
+    },
+};
+
+struct PendingCode {
+    user_id: UserId,
+    code: TwoFACode,
+    failed_attempts: u32,
+    resends: u32,
+    sent_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
 
-#[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<LoginAttemptId, PendingCode>,
+    resend_interval: Duration,
+}
+
+impl Default for HashmapTwoFACodeStore {
+    fn default() -> Self {
+        Self {
+            codes: HashMap::new(),
+            resend_interval: Duration::seconds(TWO_FA_RESEND_INTERVAL_SECONDS as i64),
+        }
+    }
+}
+
+impl HashmapTwoFACodeStore {
+    /// Overrides how long a code has to be out before it can be resent.
+    pub fn with_resend_interval(mut self, seconds: u64) -> Self {
+        self.resend_interval = Duration::seconds(seconds as i64);
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
         &mut self,
-        email: Email,
+        user_id: UserId,
         login_attempt: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt, code));
+        let sent_at = Utc::now();
+        let expires_at = sent_at + Duration::seconds(TWO_FA_CODE_TTL_SECONDS as i64);
+
+        self.codes
+            .retain(|_, pending| pending.expires_at > Utc::now());
+
+        // Every code lives equally long, so the one expiring first is the oldest
+        let mut outstanding: Vec<_> = self
+            .codes
+            .iter()
+            .filter(|(_, pending)| pending.user_id == user_id)
+            .map(|(login_attempt, pending)| (pending.expires_at, login_attempt.clone()))
+            .collect();
+        outstanding.sort_by_key(|(expires_at, _)| *expires_at);
+
+        let excess = (outstanding.len() + 1).saturating_sub(MAX_PENDING_TWO_FA_CODES);
+        for (_, login_attempt) in outstanding.into_iter().take(excess) {
+            self.codes.remove(&login_attempt);
+        }
+
+        self.codes.insert(
+            login_attempt,
+            PendingCode {
+                user_id,
+                code,
+                failed_attempts: 0,
+                resends: 0,
+                sent_at,
+                expires_at,
+            },
+        );
         Ok(())
     }
 
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
-            Some((login_attempt, code)) => Ok((login_attempt.clone(), code.clone())),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        login_attempt: &LoginAttemptId,
+    ) -> Result<(UserId, TwoFACode), TwoFACodeStoreError> {
+        match self.codes.get(login_attempt) {
+            Some(pending) if pending.expires_at > Utc::now() => {
+                Ok((pending.user_id, pending.code.clone()))
+            }
+            _ => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(email) {
+    async fn remove_code(
+        &mut self,
+        login_attempt: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        match self.codes.remove(login_attempt) {
             Some(_) => Ok(()),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+
+    async fn replace_code(
+        &mut self,
+        login_attempt: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let now = Utc::now();
+
+        let pending = self
+            .codes
+            .get_mut(login_attempt)
+            .filter(|pending| pending.expires_at > now)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        if pending.resends >= MAX_TWO_FA_RESENDS || pending.sent_at + self.resend_interval > now {
+            return Err(TwoFACodeStoreError::ResendThrottled);
+        }
+
+        pending.code = code;
+        pending.resends += 1;
+        pending.sent_at = now;
+        Ok(())
+    }
+
+    async fn remove_codes(&mut self, user_id: &UserId) -> Result<(), TwoFACodeStoreError> {
+        self.codes.retain(|_, pending| pending.user_id != *user_id);
+        Ok(())
+    }
+
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let pending = self
+            .codes
+            .get_mut(login_attempt)
+            .filter(|pending| pending.expires_at > Utc::now())
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        pending.failed_attempts += 1;
+        Ok(pending.failed_attempts)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use secrecy::Secret;
-
     use super::*;
 
     #[tokio::test]
     async fn test_add_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let user_id = UserId::default();
         let login_attempt = LoginAttemptId::default();
         let code = TwoFACode::default();
 
         let response = store
-            .add_code(email.clone(), login_attempt.clone(), code.clone())
+            .add_code(user_id, login_attempt.clone(), code.clone())
             .await;
 
         assert!(response.is_ok());
 
-        let (stored_login_attempt, stored_code) = store.codes.get(&email).unwrap();
-        assert_eq!(&login_attempt, stored_login_attempt);
-        assert_eq!(&code, stored_code);
+        let pending = store.codes.get(&login_attempt).unwrap();
+        assert_eq!(user_id, pending.user_id);
+        assert_eq!(code, pending.code);
+        assert_eq!(pending.failed_attempts, 0);
     }
 
     #[tokio::test]
     async fn test_get_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let user_id = UserId::default();
         let login_attempt = LoginAttemptId::default();
         let code = TwoFACode::default();
 
-        store
-            .codes
-            .insert(email.clone(), (login_attempt.clone(), code.clone()));
+        let _ = store
+            .add_code(user_id, login_attempt.clone(), code.clone())
+            .await;
 
-        let response = store.get_code(&email).await;
+        let response = store.get_code(&login_attempt).await;
 
         assert!(response.is_ok());
 
-        let (stored_login_attempt, stored_code) = response.unwrap();
-        assert_eq!(login_attempt, stored_login_attempt);
+        let (stored_user_id, stored_code) = response.unwrap();
+        assert_eq!(user_id, stored_user_id);
         assert_eq!(code, stored_code);
     }
 
+    #[tokio::test]
+    async fn test_get_expired_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt = LoginAttemptId::default();
+
+        let _ = store
+            .add_code(
+                UserId::default(),
+                login_attempt.clone(),
+                TwoFACode::default(),
+            )
+            .await;
+        store.codes.get_mut(&login_attempt).unwrap().expires_at = Utc::now() - Duration::seconds(1);
+
+        assert_eq!(
+            store.get_code(&login_attempt).await.map(|_| ()),
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(
+            store.record_failed_attempt(&login_attempt).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_codes() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let user_id = UserId::default();
+        let first_attempt = LoginAttemptId::default();
+        let second_attempt = LoginAttemptId::default();
+        let first_code = TwoFACode::default();
+        let second_code = TwoFACode::default();
+
+        let _ = store
+            .add_code(user_id, first_attempt.clone(), first_code.clone())
+            .await;
+        let _ = store
+            .add_code(user_id, second_attempt.clone(), second_code.clone())
+            .await;
+
+        assert_eq!(
+            store.get_code(&first_attempt).await.map(|(_, code)| code),
+            Ok(first_code)
+        );
+        assert_eq!(
+            store.get_code(&second_attempt).await.map(|(_, code)| code),
+            Ok(second_code.clone())
+        );
+
+        // Finishing one attempt leaves the other pending /////////////////////
+        assert_eq!(store.remove_code(&first_attempt).await, Ok(()));
+        assert_eq!(
+            store.get_code(&second_attempt).await.map(|(_, code)| code),
+            Ok(second_code)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_codes_are_capped() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let user_id = UserId::default();
+        let other_user_attempt = LoginAttemptId::default();
+
+        let _ = store
+            .add_code(
+                UserId::default(),
+                other_user_attempt.clone(),
+                TwoFACode::default(),
+            )
+            .await;
+
+        let mut attempts = Vec::new();
+        for i in 0..=MAX_PENDING_TWO_FA_CODES {
+            let login_attempt = LoginAttemptId::default();
+            let _ = store
+                .add_code(user_id, login_attempt.clone(), TwoFACode::default())
+                .await;
+            // Keeps the order unambiguous
+            store.codes.get_mut(&login_attempt).unwrap().expires_at -=
+                Duration::seconds((MAX_PENDING_TWO_FA_CODES - i) as i64);
+            attempts.push(login_attempt);
+        }
+
+        assert_eq!(
+            store.get_code(&attempts[0]).await.map(|_| ()),
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        for login_attempt in &attempts[1..] {
+            assert!(store.get_code(login_attempt).await.is_ok());
+        }
+        assert!(store.get_code(&other_user_attempt).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_remove_code() {
         let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap();
+        let user_id = UserId::default();
         let login_attempt = LoginAttemptId::default();
         let code = TwoFACode::default();
 
-        store
-            .codes
-            .insert(email.clone(), (login_attempt.clone(), code.clone()));
+        let _ = store
+            .add_code(user_id, login_attempt.clone(), code.clone())
+            .await;
 
-        let response = store.remove_code(&email).await;
+        let response = store.remove_code(&login_attempt).await;
 
         assert!(response.is_ok());
-        assert!(store.codes.get(&email).is_none());
+        assert!(!store.codes.contains_key(&login_attempt));
+    }
+
+    #[tokio::test]
+    async fn test_replace_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        // No pending code ////////////////////////////////////////////////////
+        assert_eq!(
+            store
+                .replace_code(&login_attempt, TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        let _ = store
+            .add_code(
+                UserId::default(),
+                login_attempt.clone(),
+                TwoFACode::default(),
+            )
+            .await;
+        let _ = store.record_failed_attempt(&login_attempt).await;
+
+        // Too soon after the first code //////////////////////////////////////
+        assert_eq!(
+            store
+                .replace_code(&login_attempt, TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::ResendThrottled)
+        );
+
+        store.codes.get_mut(&login_attempt).unwrap().sent_at -=
+            Duration::seconds(TWO_FA_RESEND_INTERVAL_SECONDS as i64);
+
+        assert_eq!(
+            store.replace_code(&login_attempt, code.clone()).await,
+            Ok(())
+        );
+
+        let pending = store.codes.get(&login_attempt).unwrap();
+        assert_eq!(pending.code, code);
+        assert_eq!(pending.failed_attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_replace_code_limit() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let login_attempt = LoginAttemptId::default();
+
+        let _ = store
+            .add_code(
+                UserId::default(),
+                login_attempt.clone(),
+                TwoFACode::default(),
+            )
+            .await;
+
+        for _ in 0..MAX_TWO_FA_RESENDS {
+            store.codes.get_mut(&login_attempt).unwrap().sent_at -=
+                Duration::seconds(TWO_FA_RESEND_INTERVAL_SECONDS as i64);
+
+            assert_eq!(
+                store
+                    .replace_code(&login_attempt, TwoFACode::default())
+                    .await,
+                Ok(())
+            );
+        }
+
+        store.codes.get_mut(&login_attempt).unwrap().sent_at -=
+            Duration::seconds(TWO_FA_RESEND_INTERVAL_SECONDS as i64);
+
+        assert_eq!(
+            store
+                .replace_code(&login_attempt, TwoFACode::default())
+                .await,
+            Err(TwoFACodeStoreError::ResendThrottled)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_code_with_resend_interval() {
+        let mut store = HashmapTwoFACodeStore::default().with_resend_interval(0);
+        let login_attempt = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        let _ = store
+            .add_code(
+                UserId::default(),
+                login_attempt.clone(),
+                TwoFACode::default(),
+            )
+            .await;
+
+        assert_eq!(
+            store.replace_code(&login_attempt, code.clone()).await,
+            Ok(())
+        );
+        assert_eq!(store.codes.get(&login_attempt).unwrap().code, code);
+    }
+
+    #[tokio::test]
+    async fn test_remove_codes() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let user_id = UserId::default();
+        let other_user_attempt = LoginAttemptId::default();
+
+        let _ = store
+            .add_code(user_id, LoginAttemptId::default(), TwoFACode::default())
+            .await;
+        let _ = store
+            .add_code(user_id, LoginAttemptId::default(), TwoFACode::default())
+            .await;
+        let _ = store
+            .add_code(
+                UserId::default(),
+                other_user_attempt.clone(),
+                TwoFACode::default(),
+            )
+            .await;
+
+        assert_eq!(store.remove_codes(&user_id).await, Ok(()));
+
+        assert_eq!(store.codes.len(), 1);
+        assert!(store.codes.contains_key(&other_user_attempt));
+    }
+
+    #[tokio::test]
+    async fn test_record_failed_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let user_id = UserId::default();
+        let login_attempt = LoginAttemptId::default();
+
+        // No pending code ////////////////////////////////////////////////////
+        assert_eq!(
+            store.record_failed_attempt(&login_attempt).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        let _ = store
+            .add_code(user_id, login_attempt.clone(), TwoFACode::default())
+            .await;
+
+        assert_eq!(store.record_failed_attempt(&login_attempt).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&login_attempt).await, Ok(2));
+
+        // Other attempts keep their own count ////////////////////////////////
+        let other_attempt = LoginAttemptId::default();
+        let _ = store
+            .add_code(user_id, other_attempt.clone(), TwoFACode::default())
+            .await;
+
+        assert_eq!(store.record_failed_attempt(&other_attempt).await, Ok(1));
+        assert_eq!(store.record_failed_attempt(&login_attempt).await, Ok(3));
     }
 }
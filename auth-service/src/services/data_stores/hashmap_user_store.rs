@@ -1,20 +1,34 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
+
 use crate::domain::Email;
 use crate::domain::Password;
+use crate::domain::TotpSecret;
+use crate::domain::TwoFAMethod;
 use crate::domain::User;
+use crate::domain::UserId;
 use crate::domain::UserStore;
 use crate::domain::UserStoreError;
 
 #[derive(Default, Clone)]
 pub struct HashmapUserStore {
     users: HashMap<Email, User>,
+    deleted_users: HashMap<Email, (User, DateTime<Utc>)>,
+    /// Secret and time step of the last accepted code of each TOTP user.
+    totp_secrets: HashMap<Email, (TotpSecret, Option<u64>)>,
+}
+
+impl HashmapUserStore {
+    fn is_taken(&self, email: &Email) -> bool {
+        self.users.contains_key(email) || self.deleted_users.contains_key(email)
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+        if self.is_taken(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         }
 
@@ -29,6 +43,14 @@ impl UserStore for HashmapUserStore {
         }
     }
 
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.users
+            .values()
+            .find(|user| &user.id == id)
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
     async fn validate_user(
         &self,
         email: &Email,
@@ -45,6 +67,179 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn upgrade_password_hash(
+        &self,
+        email: &Email,
+        _password: &Password,
+    ) -> Result<(), UserStoreError> {
+        // Passwords are kept as they are, so there's never anything to upgrade
+        match self.users.contains_key(email) {
+            true => Ok(()),
+            false => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn update_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        if self.is_taken(new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let mut user = self
+            .users
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+
+        user.email = new_email.clone();
+        user.email_verified = true;
+        self.users.insert(new_email.clone(), user);
+
+        if let Some(secret) = self.totp_secrets.remove(email) {
+            self.totp_secrets.insert(new_email.clone(), secret);
+        }
+        Ok(())
+    }
+
+    async fn set_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.email_verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.two_fa_method = method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: Option<TotpSecret>,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        match secret {
+            Some(secret) => self.totp_secrets.insert(email.clone(), (secret, None)),
+            None => self.totp_secrets.remove(email),
+        };
+        Ok(())
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<Option<TotpSecret>, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(self
+            .totp_secrets
+            .get(email)
+            .map(|(secret, _)| secret.clone()))
+    }
+
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        match self.totp_secrets.get_mut(email) {
+            Some((_, last_step)) if last_step.is_none_or(|last_step| step > last_step) => {
+                *last_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let removed = self.users.remove(email).is_some();
+        let removed_deleted = self.deleted_users.remove(email).is_some();
+
+        if !removed && !removed_deleted {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.totp_secrets.remove(email);
+        Ok(())
+    }
+
+    async fn mark_user_deleted(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self
+            .users
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+
+        self.deleted_users.insert(email.clone(), (user, Utc::now()));
+        Ok(())
+    }
+
+    async fn restore_user(
+        &mut self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        match self.deleted_users.get(email) {
+            Some((user, _)) if user.password.eq(password) => (),
+            Some(_) => return Err(UserStoreError::InvalidCredentials),
+            None => return Err(UserStoreError::UserNotFound),
+        }
+
+        if let Some((user, _)) = self.deleted_users.remove(email) {
+            self.users.insert(email.clone(), user);
+        }
+        Ok(())
+    }
+
+    async fn purge_deleted_users(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<usize, UserStoreError> {
+        let expired: Vec<Email> = self
+            .deleted_users
+            .iter()
+            .filter(|(_, (_, deleted_at))| *deleted_at < deleted_before)
+            .map(|(email, _)| email.clone())
+            .collect();
+
+        for email in &expired {
+            self.deleted_users.remove(email);
+            self.totp_secrets.remove(email);
+        }
+
+        Ok(expired.len())
+    }
 }
 
 #[cfg(test)]
@@ -57,9 +252,11 @@ mod tests {
     async fn test_add_user() {
         let mut users = HashmapUserStore::default();
         let user1 = User {
+            id: UserId::default(),
             email: Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
             password: Password::parse(Secret::new("password".to_owned())).unwrap(),
-            requires_2fa: true,
+            two_fa_method: TwoFAMethod::Email,
+            email_verified: false,
         };
 
         // Ok scenario ////////////////////////////////////////////////////////
@@ -75,9 +272,11 @@ mod tests {
     async fn test_get_user() {
         let mut users = HashmapUserStore::default();
         let user = User {
+            id: UserId::default(),
             email: Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
             password: Password::parse(Secret::new("password".to_owned())).unwrap(),
-            requires_2fa: true,
+            two_fa_method: TwoFAMethod::Email,
+            email_verified: false,
         };
         users.users.insert(user.email.clone(), user.clone());
         // Ok scenario ////////////////////////////////////////////////////////
@@ -93,13 +292,36 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(user.clone()).await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.get_user_by_id(&user.id).await, Ok(user));
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users.get_user_by_id(&UserId::default()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_user() {
         let mut users = HashmapUserStore::default();
         let user = User {
+            id: UserId::default(),
             email: Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
             password: Password::parse(Secret::new("password".to_owned())).unwrap(),
-            requires_2fa: true,
+            two_fa_method: TwoFAMethod::Email,
+            email_verified: false,
         };
 
         let _ = users.add_user(user.clone()).await;
@@ -134,4 +356,388 @@ mod tests {
             Err(UserStoreError::InvalidCredentials)
         );
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut users = HashmapUserStore::default();
+        let user = User {
+            id: UserId::default(),
+            email: Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            password: Password::parse(Secret::new("password".to_owned())).unwrap(),
+            two_fa_method: TwoFAMethod::Email,
+            email_verified: false,
+        };
+        let new_password = Password::parse(Secret::new("new_password".to_owned())).unwrap();
+
+        let _ = users.add_user(user.clone()).await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .update_password(&user.email, new_password.clone())
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            users.validate_user(&user.email, &new_password).await,
+            Ok(())
+        );
+        assert_eq!(
+            users.validate_user(&user.email, &user.password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .update_password(
+                    &Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap(),
+                    new_password
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_password_hash() {
+        let mut users = HashmapUserStore::default();
+        let user = User {
+            id: UserId::default(),
+            email: Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            password: Password::parse(Secret::new("password".to_owned())).unwrap(),
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
+        };
+
+        let _ = users.add_user(user.clone()).await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .upgrade_password_hash(&user.email, &user.password)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            users.validate_user(&user.email, &user.password).await,
+            Ok(())
+        );
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .upgrade_password_hash(
+                    &Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap(),
+                    &user.password
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_email() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+        let other = User::new(
+            Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+        let new_email = Email::parse(Secret::new("janedoe@example.com".to_owned())).unwrap();
+
+        let _ = users.add_user(user.clone()).await;
+        let _ = users.add_user(other.clone()).await;
+
+        // UserAlreadyExists //////////////////////////////////////////////////
+        assert_eq!(
+            users.update_email(&user.email, &other.email).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.update_email(&user.email, &new_email).await, Ok(()));
+        assert_eq!(
+            users.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        let updated = users.get_user(&new_email).await.unwrap();
+        assert_eq!(updated.id, user.id);
+        assert_eq!(updated.email, new_email);
+        assert!(updated.email_verified);
+
+        // UserNotFound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .update_email(
+                    &user.email,
+                    &Email::parse(Secret::new("someone@example.com".to_owned())).unwrap()
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_email_verified() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(user.clone()).await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.set_email_verified(&user.email).await, Ok(()));
+        assert!(users.get_user(&user.email).await.unwrap().email_verified);
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .set_email_verified(
+                    &Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap()
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(user.clone()).await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .set_two_fa_method(&user.email, TwoFAMethod::Totp)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            users.get_user(&user.email).await.unwrap().two_fa_method,
+            TwoFAMethod::Totp
+        );
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .set_two_fa_method(
+                    &Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap(),
+                    TwoFAMethod::Totp
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_totp_secret() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+        let secret = TotpSecret::default();
+
+        let _ = users.add_user(user.clone()).await;
+
+        // No secret yet //////////////////////////////////////////////////////
+        assert_eq!(users.get_totp_secret(&user.email).await, Ok(None));
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .set_totp_secret(&user.email, Some(secret.clone()))
+                .await,
+            Ok(())
+        );
+        assert_eq!(users.get_totp_secret(&user.email).await, Ok(Some(secret)));
+
+        // Cleared ////////////////////////////////////////////////////////////
+        assert_eq!(users.set_totp_secret(&user.email, None).await, Ok(()));
+        assert_eq!(users.get_totp_secret(&user.email).await, Ok(None));
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .get_totp_secret(
+                    &Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap()
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_use_totp_step() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(user.clone()).await;
+
+        // No secret //////////////////////////////////////////////////////////
+        assert_eq!(users.use_totp_step(&user.email, 10).await, Ok(false));
+
+        let _ = users
+            .set_totp_secret(&user.email, Some(TotpSecret::default()))
+            .await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.use_totp_step(&user.email, 10).await, Ok(true));
+        assert_eq!(users.use_totp_step(&user.email, 11).await, Ok(true));
+        // Same or earlier step ///////////////////////////////////////////////
+        assert_eq!(users.use_totp_step(&user.email, 11).await, Ok(false));
+        assert_eq!(users.use_totp_step(&user.email, 10).await, Ok(false));
+        // New secret starts over /////////////////////////////////////////////
+        let _ = users
+            .set_totp_secret(&user.email, Some(TotpSecret::default()))
+            .await;
+        assert_eq!(users.use_totp_step(&user.email, 10).await, Ok(true));
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .use_totp_step(
+                    &Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap(),
+                    10
+                )
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(user.clone()).await;
+        let _ = users
+            .set_totp_secret(&user.email, Some(TotpSecret::default()))
+            .await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.delete_user(&user.email).await, Ok(()));
+        assert_eq!(
+            users.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert!(users.totp_secrets.is_empty());
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users.delete_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        // The address is free again //////////////////////////////////////////
+        assert_eq!(users.add_user(user).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_mark_user_deleted_and_restore() {
+        let mut users = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(user.clone()).await;
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.mark_user_deleted(&user.email).await, Ok(()));
+        assert_eq!(
+            users.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(
+            users.get_user_by_id(&user.id).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        // The address stays taken ////////////////////////////////////////////
+        assert_eq!(
+            users.add_user(user.clone()).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+
+        // InvalidCredentials /////////////////////////////////////////////////
+        assert_eq!(
+            users
+                .restore_user(
+                    &user.email,
+                    &Password::parse(Secret::new("wrong_password".to_owned())).unwrap()
+                )
+                .await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+
+        // Restored ///////////////////////////////////////////////////////////
+        assert_eq!(
+            users.restore_user(&user.email, &user.password).await,
+            Ok(())
+        );
+        assert_eq!(users.get_user(&user.email).await, Ok(user.clone()));
+
+        // UserNotfound ///////////////////////////////////////////////////////
+        assert_eq!(
+            users.restore_user(&user.email, &user.password).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_purge_deleted_users() {
+        let mut users = HashmapUserStore::default();
+        let deleted = User::new(
+            Email::parse(Secret::new("johndoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+        let active = User::new(
+            Email::parse(Secret::new("marydoe@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password".to_owned())).unwrap(),
+            TwoFAMethod::None,
+        );
+
+        let _ = users.add_user(deleted.clone()).await;
+        let _ = users.add_user(active.clone()).await;
+        let _ = users.mark_user_deleted(&deleted.email).await;
+
+        // Still within the grace period //////////////////////////////////////
+        let before_deletion = Utc::now() - chrono::Duration::try_days(1).unwrap();
+        assert_eq!(users.purge_deleted_users(before_deletion).await, Ok(0));
+
+        // Ok scenario ////////////////////////////////////////////////////////
+        assert_eq!(users.purge_deleted_users(Utc::now()).await, Ok(1));
+        assert_eq!(
+            users.restore_user(&deleted.email, &deleted.password).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(users.get_user(&active.email).await, Ok(active));
+    }
 }
@@ -1,7 +1,5 @@
 use std::collections::HashSet;
 
-use secrecy::{ExposeSecret, Secret};
-
 use crate::domain::{BannedTokenStore, BannedTokenStoreError};
 
 #[derive(Default, Clone)]
@@ -11,16 +9,13 @@ pub struct HashsetBannedTokenStore {
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_banned_token(
-        &mut self,
-        token: Secret<String>,
-    ) -> Result<(), BannedTokenStoreError> {
-        self.tokens.insert(token.expose_secret().to_owned());
+    async fn add_banned_token(&mut self, jti: &str) -> Result<(), BannedTokenStoreError> {
+        self.tokens.insert(jti.to_owned());
         Ok(())
     }
 
-    async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.tokens.contains(token.expose_secret()))
+    async fn is_banned_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.tokens.contains(jti))
     }
 }
 
@@ -28,24 +23,22 @@ impl BannedTokenStore for HashsetBannedTokenStore {
 async fn test_add_banned_token() {
     let mut store = HashsetBannedTokenStore::default();
 
-    let token = "token".to_owned();
-    let token = Secret::new(token);
+    let jti = "jti";
 
-    let result = store.add_banned_token(token.clone()).await;
+    let result = store.add_banned_token(jti).await;
 
     assert!(result.is_ok());
-    assert!(store.tokens.contains(token.expose_secret()));
+    assert!(store.tokens.contains(jti));
 }
 
 #[tokio::test]
 async fn test_is_banned_token() {
     let mut store = HashsetBannedTokenStore::default();
 
-    let token = "token".to_owned();
-    let token = Secret::new(token);
-    store.tokens.insert(token.expose_secret().to_owned());
+    let jti = "jti";
+    store.tokens.insert(jti.to_owned());
 
-    let result = store.is_banned_token(&token).await;
+    let result = store.is_banned_token(jti).await;
 
     assert!(result.unwrap());
 }
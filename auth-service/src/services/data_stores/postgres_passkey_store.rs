@@ -0,0 +1,159 @@
+use sqlx::PgPool;
+
+use crate::domain::{CredentialId, Passkey, PasskeyStore, PasskeyStoreError, UserId};
+
+pub struct PostgresPasskeyStore {
+    pool: PgPool,
+}
+
+impl PostgresPasskeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for PostgresPasskeyStore {
+    #[tracing::instrument(name = "Adding passkey to PostgreSQL", skip_all)]
+    async fn add_passkey(&mut self, passkey: Passkey) -> Result<(), PasskeyStoreError> {
+        sqlx::query!(
+            r#"INSERT INTO passkeys (credential_id, user_id, public_key, sign_count, created_at)
+	       VALUES ($1, $2, $3, $4, $5)
+	       "#,
+            passkey.credential_id.as_ref(),
+            passkey.user_id.as_ref(),
+            &passkey.public_key,
+            i64::from(passkey.sign_count),
+            passkey.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => {
+                PasskeyStoreError::PasskeyAlreadyExists
+            }
+            _ => PasskeyStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving passkey from PostgreSQL", skip_all)]
+    async fn get_passkey(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<Passkey, PasskeyStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT user_id, public_key, sign_count, created_at, last_used_at
+	       FROM passkeys
+	       WHERE credential_id = $1"#,
+            credential_id.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?
+        .ok_or(PasskeyStoreError::PasskeyNotFound)?;
+
+        Ok(Passkey {
+            credential_id: credential_id.clone(),
+            user_id: row.user_id.into(),
+            public_key: row.public_key,
+            sign_count: to_sign_count(row.sign_count)?,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving passkeys from PostgreSQL", skip_all)]
+    async fn get_passkeys(&self, user_id: &UserId) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        let rows = sqlx::query!(
+            r#"SELECT credential_id, public_key, sign_count, created_at, last_used_at
+	       FROM passkeys
+	       WHERE user_id = $1
+	       ORDER BY created_at"#,
+            user_id.as_ref(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(Passkey {
+                    credential_id: CredentialId::from_bytes(row.credential_id)
+                        .map_err(PasskeyStoreError::UnexpectedError)?,
+                    user_id: *user_id,
+                    public_key: row.public_key,
+                    sign_count: to_sign_count(row.sign_count)?,
+                    created_at: row.created_at,
+                    last_used_at: row.last_used_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Updating passkey sign count in PostgreSQL", skip_all)]
+    async fn update_sign_count(
+        &mut self,
+        credential_id: &CredentialId,
+        sign_count: u32,
+    ) -> Result<(), PasskeyStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE passkeys
+	       SET sign_count = $2, last_used_at = NOW()
+	       WHERE credential_id = $1"#,
+            credential_id.as_ref(),
+            i64::from(sign_count),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::PasskeyNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing passkey from PostgreSQL", skip_all)]
+    async fn remove_passkey(
+        &mut self,
+        user_id: &UserId,
+        credential_id: &CredentialId,
+    ) -> Result<(), PasskeyStoreError> {
+        let result = sqlx::query!(
+            r#"DELETE FROM passkeys
+	       WHERE credential_id = $1 AND user_id = $2"#,
+            credential_id.as_ref(),
+            user_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::PasskeyNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing passkeys of user from PostgreSQL", skip_all)]
+    async fn remove_passkeys(&mut self, user_id: &UserId) -> Result<(), PasskeyStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM passkeys
+	       WHERE user_id = $1"#,
+            user_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+fn to_sign_count(sign_count: i64) -> Result<u32, PasskeyStoreError> {
+    u32::try_from(sign_count).map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))
+}
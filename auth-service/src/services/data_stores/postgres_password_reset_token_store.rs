@@ -0,0 +1,145 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    utils::constants::{PASSWORD_RESET_RESEND_INTERVAL_SECONDS, PASSWORD_RESET_TOKEN_TTL_SECONDS},
+};
+
+pub struct PostgresPasswordResetTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresPasswordResetTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for PostgresPasswordResetTokenStore {
+    #[tracing::instrument(name = "Adding password reset token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        sqlx::query!(r#"DELETE FROM password_reset_tokens WHERE expires_at <= NOW()"#)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"INSERT INTO password_reset_tokens (token_hash, email, expires_at)
+	       VALUES ($1, $2, NOW() + make_interval(secs => $3))
+	       "#,
+            token.hash(),
+            email.as_ref().expose_secret(),
+            PASSWORD_RESET_TOKEN_TTL_SECONDS as f64,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting password reset token from PostgreSQL", skip_all)]
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT email FROM password_reset_tokens
+	       WHERE token_hash = $1 AND expires_at > NOW()"#,
+            token.hash(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?
+        .ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        Email::parse(Secret::new(row.email)).map_err(PasswordResetTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Consuming password reset token from PostgreSQL", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let row = sqlx::query!(
+            r#"DELETE FROM password_reset_tokens
+	       WHERE token_hash = $1 AND expires_at > NOW()
+	       RETURNING email"#,
+            token.hash(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?
+        .ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        Email::parse(Secret::new(row.email)).map_err(PasswordResetTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Removing password reset tokens from PostgreSQL", skip_all)]
+    async fn remove_tokens(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM password_reset_tokens WHERE lower(email) = lower($1)"#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording password reset request in PostgreSQL", skip_all)]
+    async fn record_reset_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let keys = ip_address
+            .map(|ip_address| format!("ip:{}", ip_address))
+            .into_iter()
+            .chain(std::iter::once(format!(
+                "email:{}",
+                email.lowercase().expose_secret()
+            )));
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        for key in keys {
+            // The conflicting row is only replaced once the interval has
+            // passed, so nothing is affected while the key is throttled.
+            let result = sqlx::query!(
+                r#"INSERT INTO password_reset_requests (key, requested_at)
+	           VALUES ($1, NOW())
+	           ON CONFLICT (key) DO UPDATE SET requested_at = NOW()
+	           WHERE password_reset_requests.requested_at
+	               <= NOW() - make_interval(secs => $2)"#,
+                key,
+                PASSWORD_RESET_RESEND_INTERVAL_SECONDS as f64,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+            if result.rows_affected() == 0 {
+                return Err(PasswordResetTokenStoreError::ResendThrottled);
+            }
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| PasswordResetTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
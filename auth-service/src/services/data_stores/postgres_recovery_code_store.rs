@@ -0,0 +1,127 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError, UserId},
+    utils::password_hashing::{compute_password_hash, verify_password_hash},
+};
+
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    #[tracing::instrument(name = "Replacing recovery codes in PostgreSQL", skip_all)]
+    async fn replace_codes(
+        &mut self,
+        user_id: &UserId,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        // Hash up front so the transaction isn't held open while Argon2 runs.
+        let mut code_hashes = Vec::with_capacity(codes.len());
+        for code in codes {
+            let code_hash = compute_password_hash(code.as_ref().to_owned())
+                .await
+                .map_err(RecoveryCodeStoreError::UnexpectedError)?;
+            code_hashes.push(code_hash);
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"DELETE FROM recovery_codes WHERE user_id = $1"#,
+            user_id.as_ref(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        for code_hash in code_hashes {
+            sqlx::query!(
+                r#"INSERT INTO recovery_codes (id, user_id, code_hash)
+	           VALUES ($1, $2, $3)
+	           "#,
+                Uuid::new_v4(),
+                user_id.as_ref(),
+                code_hash.expose_secret(),
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming recovery code from PostgreSQL", skip_all)]
+    async fn consume_code(
+        &mut self,
+        user_id: &UserId,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let rows = sqlx::query!(
+            r#"SELECT id, code_hash
+	       FROM recovery_codes
+	       WHERE user_id = $1"#,
+            user_id.as_ref(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        for row in rows {
+            if verify_password_hash(Secret::new(row.code_hash), code.as_ref().to_owned())
+                .await
+                .is_err()
+            {
+                continue;
+            }
+
+            let result = sqlx::query!(r#"DELETE FROM recovery_codes WHERE id = $1"#, row.id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+            // Another request burnt the same code in the meantime.
+            if result.rows_affected() == 0 {
+                return Err(RecoveryCodeStoreError::CodeNotFound);
+            }
+
+            return Ok(());
+        }
+
+        Err(RecoveryCodeStoreError::CodeNotFound)
+    }
+
+    #[tracing::instrument(name = "Counting recovery codes in PostgreSQL", skip_all)]
+    async fn count_codes(&self, user_id: &UserId) -> Result<usize, RecoveryCodeStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!"
+	       FROM recovery_codes
+	       WHERE user_id = $1"#,
+            user_id.as_ref(),
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| RecoveryCodeStoreError::UnexpectedError(e.into()))?;
+
+        Ok(row.count as usize)
+    }
+}
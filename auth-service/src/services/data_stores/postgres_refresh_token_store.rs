@@ -0,0 +1,167 @@
+use sqlx::PgPool;
+
+use crate::{
+    domain::{RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId, UserId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        session_id: SessionId,
+        user_id: UserId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let family_id = session_id.as_ref();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"INSERT INTO refresh_token_families (id, user_id)
+	       VALUES ($1, $2)
+	       "#,
+            family_id,
+            user_id.as_ref(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"INSERT INTO refresh_tokens (token_hash, family_id, expires_at)
+	       VALUES ($1, $2, NOW() + make_interval(secs => $3))
+	       "#,
+            token.hash(),
+            family_id,
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rotating refresh token in PostgreSQL", skip_all)]
+    async fn rotate_token(
+        &mut self,
+        current: &RefreshToken,
+        next: RefreshToken,
+    ) -> Result<(UserId, SessionId), RefreshTokenStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        let row = sqlx::query!(
+            r#"SELECT t.family_id, t.used, f.user_id, f.revoked
+	       FROM refresh_tokens t
+	       JOIN refresh_token_families f ON f.id = t.family_id
+	       WHERE t.token_hash = $1 AND t.expires_at > NOW()
+	       FOR UPDATE"#,
+            current.hash(),
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?
+        .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if row.revoked {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        if row.used {
+            sqlx::query!(
+                r#"UPDATE refresh_token_families
+	           SET revoked = TRUE
+	           WHERE id = $1"#,
+                row.family_id,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+            transaction
+                .commit()
+                .await
+                .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+            return Err(RefreshTokenStoreError::TokenReused(UserId::from(
+                row.user_id,
+            )));
+        }
+
+        sqlx::query!(
+            r#"UPDATE refresh_tokens
+	       SET used = TRUE
+	       WHERE token_hash = $1"#,
+            current.hash(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"INSERT INTO refresh_tokens (token_hash, family_id, expires_at)
+	       VALUES ($1, $2, NOW() + make_interval(secs => $3))
+	       "#,
+            next.hash(),
+            row.family_id,
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok((UserId::from(row.user_id), SessionId::from(row.family_id)))
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_family(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE refresh_token_families
+	       SET revoked = TRUE
+	       WHERE id = $1"#,
+            session_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+}
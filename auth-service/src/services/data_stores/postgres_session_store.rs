@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{Session, SessionId, SessionStore, SessionStoreError, UserId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE last_seen <= NOW() - make_interval(secs => $1)"#,
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"INSERT INTO sessions (id, user_id, created_at, last_seen, user_agent, ip_address)
+	       VALUES ($1, $2, $3, $4, $5, $6)
+	       "#,
+            session.id.as_ref(),
+            session.user_id.as_ref(),
+            session.created_at,
+            session.last_seen,
+            session.user_agent,
+            session.ip_address,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting session from PostgreSQL", skip_all)]
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT id, user_id, created_at, last_seen, user_agent, ip_address
+	       FROM sessions
+	       WHERE id = $1 AND last_seen > NOW() - make_interval(secs => $2)"#,
+            id.as_ref(),
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?
+        .ok_or(SessionStoreError::SessionNotFound)?;
+
+        Ok(to_session(
+            row.id,
+            row.user_id,
+            row.created_at,
+            row.last_seen,
+            row.user_agent,
+            row.ip_address,
+        ))
+    }
+
+    #[tracing::instrument(name = "Getting user sessions from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let rows = sqlx::query!(
+            r#"SELECT id, user_id, created_at, last_seen, user_agent, ip_address
+	       FROM sessions
+	       WHERE user_id = $1 AND last_seen > NOW() - make_interval(secs => $2)
+	       ORDER BY last_seen DESC"#,
+            user_id.as_ref(),
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                to_session(
+                    row.id,
+                    row.user_id,
+                    row.created_at,
+                    row.last_seen,
+                    row.user_agent,
+                    row.ip_address,
+                )
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Touching session in PostgreSQL", skip_all)]
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE sessions
+	       SET last_seen = NOW()
+	       WHERE id = $1 AND last_seen > NOW() - make_interval(secs => $2)"#,
+            id.as_ref(),
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing session from PostgreSQL", skip_all)]
+    async fn remove_session(
+        &mut self,
+        user_id: &UserId,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"DELETE FROM sessions WHERE id = $1 AND user_id = $2"#,
+            id.as_ref(),
+            user_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing user sessions from PostgreSQL", skip_all)]
+    async fn remove_sessions(
+        &mut self,
+        user_id: &UserId,
+    ) -> Result<Vec<SessionId>, SessionStoreError> {
+        let rows = sqlx::query!(
+            r#"DELETE FROM sessions WHERE user_id = $1 RETURNING id"#,
+            user_id.as_ref(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| SessionId::from(row.id))
+            .collect())
+    }
+}
+
+fn to_session(
+    id: Uuid,
+    user_id: Uuid,
+    created_at: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+) -> Session {
+    Session {
+        id: SessionId::from(id),
+        user_id: UserId::from(user_id),
+        created_at,
+        last_seen,
+        user_agent,
+        ip_address,
+    }
+}
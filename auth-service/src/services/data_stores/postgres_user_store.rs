@@ -1,22 +1,49 @@
-use color_eyre::eyre::{Context, Result};
-
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
-};
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
-use tokio::task;
+use uuid::Uuid;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::{
+    domain::{Email, Password, TotpSecret, TwoFAMethod, User, UserId, UserStore, UserStoreError},
+    utils::{
+        crypto::{decrypt_secret, encrypt_secret},
+        password_hashing::{
+            compute_password_hash, hash_password, needs_rehash, pepper_password,
+            verify_password_hash, Peppers,
+        },
+    },
+};
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    peppers: Peppers,
+}
+
+lazy_static! {
+    // Checked against when the user doesn't exist, so an unknown address takes
+    // as long to reject as a wrong password
+    static ref DUMMY_PASSWORD_HASH: Secret<String> = Secret::new(
+        hash_password(&Secret::new("dummy-password".to_owned()))
+            .expect("failed to compute dummy password hash")
+    );
 }
 
 impl PostgresUserStore {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        // Hash up front rather than on the first login with an unknown address
+        lazy_static::initialize(&DUMMY_PASSWORD_HASH);
+
+        Self {
+            pool,
+            peppers: Peppers::configured(),
+        }
+    }
+
+    /// Overrides the configured peppers.
+    pub fn with_peppers(mut self, peppers: Peppers) -> Self {
+        self.peppers = peppers;
+        self
     }
 }
 
@@ -24,21 +51,27 @@ impl PostgresUserStore {
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let password_hash = compute_password_hash(user.password.as_ref().to_owned())
-            .await
-            .map_err(UserStoreError::UnexpectedError)?;
+        let (password_hash, pepper_version) =
+            hash_with_pepper(&self.peppers, &user.password).await?;
 
         sqlx::query!(
-            r#"INSERT INTO users (email, password_hash, requires_2fa)
-	       VALUES ($1, $2, $3)
+            r#"INSERT INTO users
+	           (id, email, password_hash, pepper_version, two_fa_method, email_verified)
+	       VALUES ($1, $2, $3, $4, $5, $6)
 	       "#,
+            user.id.as_ref(),
             user.email.as_ref().expose_secret(),
             &password_hash.expose_secret(),
-            user.requires_2fa,
+            pepper_version,
+            user.two_fa_method.as_str(),
+            user.email_verified,
         )
         .execute(&self.pool)
         .await
-        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
         Ok(())
     }
@@ -46,22 +79,45 @@ impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, username: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
-            r#"SELECT email, password_hash, requires_2fa
+            r#"SELECT id, email, password_hash, two_fa_method, email_verified
 	       FROM users
-	       WHERE email = $1"#,
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
             username.as_ref().expose_secret(),
         )
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
         .map(|row| {
-            Ok(User {
-                email: Email::parse(Secret::new(row.email))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                password: Password::parse(Secret::new(row.password_hash))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                requires_2fa: row.requires_2fa,
-            })
+            to_user(
+                row.id,
+                row.email,
+                row.password_hash,
+                row.two_fa_method,
+                row.email_verified,
+            )
+        })
+        .ok_or(UserStoreError::UserNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query!(
+            r#"SELECT id, email, password_hash, two_fa_method, email_verified
+	       FROM users
+	       WHERE id = $1 AND deleted_at IS NULL"#,
+            id.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            to_user(
+                row.id,
+                row.email,
+                row.password_hash,
+                row.two_fa_method,
+                row.email_verified,
+            )
         })
         .ok_or(UserStoreError::UserNotFound)?
     }
@@ -72,64 +128,511 @@ impl UserStore for PostgresUserStore {
         username: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        let user = self.get_user(username).await?;
+        let row = sqlx::query!(
+            r#"SELECT password_hash, pepper_version
+	       FROM users
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            username.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let Some(row) = row else {
+            let pepper_version = self.peppers.current_version();
+            let _ = verify_with_pepper(
+                &self.peppers,
+                DUMMY_PASSWORD_HASH.clone(),
+                pepper_version,
+                password,
+            )
+            .await;
+
+            return Err(UserStoreError::UserNotFound);
+        };
+
+        verify_with_pepper(
+            &self.peppers,
+            Secret::new(row.password_hash),
+            row.pepper_version,
+            password,
+        )
+        .await
+    }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let (password_hash, pepper_version) = hash_with_pepper(&self.peppers, &password).await?;
 
-        if verify_password_hash(
-            user.password.as_ref().to_owned(),
-            password.as_ref().to_owned(),
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET password_hash = $2, pepper_version = $3, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+            &password_hash.expose_secret(),
+            pepper_version,
         )
+        .execute(&self.pool)
         .await
-        .is_err()
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Upgrading user password hash in PostgreSQL", skip_all)]
+    async fn upgrade_password_hash(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT password_hash, pepper_version
+	       FROM users
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        if !needs_rehash(&Secret::new(row.password_hash.clone()))
+            && row.pepper_version == self.peppers.current_version()
         {
-            return Err(UserStoreError::InvalidCredentials);
+            return Ok(());
+        }
+
+        let (password_hash, pepper_version) = hash_with_pepper(&self.peppers, password).await?;
+
+        // Leave the hash alone if the password changed in the meantime
+        sqlx::query!(
+            r#"UPDATE users
+	       SET password_hash = $2, pepper_version = $3, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND password_hash = $4"#,
+            email.as_ref().expose_secret(),
+            &password_hash.expose_secret(),
+            pepper_version,
+            row.password_hash,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user email in PostgreSQL", skip_all)]
+    async fn update_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+    ) -> Result<(), UserStoreError> {
+        // Rows referencing the user follow along through ON UPDATE CASCADE
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET email = $2, email_verified = TRUE, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+            new_email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking user email as verified in PostgreSQL", skip_all)]
+    async fn set_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET email_verified = TRUE, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA method in PostgreSQL", skip_all)]
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET two_fa_method = $2, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+            method.as_str(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Storing user TOTP secret in PostgreSQL", skip_all)]
+    async fn set_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: Option<TotpSecret>,
+    ) -> Result<(), UserStoreError> {
+        let encrypted_secret = secret
+            .map(|secret| encrypt_secret(secret.as_ref()))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET totp_secret = $2, totp_last_step = NULL, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+            encrypted_secret,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving user TOTP secret from PostgreSQL", skip_all)]
+    async fn get_totp_secret(&self, email: &Email) -> Result<Option<TotpSecret>, UserStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT totp_secret
+	       FROM users
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        row.totp_secret
+            .map(|encrypted| decrypt_secret(&encrypted).and_then(TotpSecret::parse))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Recording used TOTP step in PostgreSQL", skip_all)]
+    async fn use_totp_step(&mut self, email: &Email, step: u64) -> Result<bool, UserStoreError> {
+        let step = i64::try_from(step).map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Compared in the update itself so concurrent requests can't both
+        // get the same step accepted
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET totp_last_step = $2
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL AND totp_secret IS NOT NULL
+	       AND (totp_last_step IS NULL OR totp_last_step < $2)"#,
+            email.as_ref().expose_secret(),
+            step,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(name = "Deleting user from PostgreSQL", skip_all)]
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        // Sessions, refresh tokens and recovery codes go with it through ON DELETE CASCADE
+        let result = sqlx::query!(
+            r#"DELETE FROM users
+	       WHERE lower(email) = lower($1)"#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking user as deleted in PostgreSQL", skip_all)]
+    async fn mark_user_deleted(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET deleted_at = NOW(), updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NULL"#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
         }
 
         Ok(())
     }
+
+    #[tracing::instrument(name = "Restoring deleted user in PostgreSQL", skip_all)]
+    async fn restore_user(
+        &mut self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let row = sqlx::query!(
+            r#"SELECT password_hash, pepper_version
+	       FROM users
+	       WHERE lower(email) = lower($1) AND deleted_at IS NOT NULL"#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Hash anyway so the response time doesn't reveal which addresses
+        // belong to deleted accounts
+        let Some(row) = row else {
+            let pepper_version = self.peppers.current_version();
+            let _ = verify_with_pepper(
+                &self.peppers,
+                DUMMY_PASSWORD_HASH.clone(),
+                pepper_version,
+                password,
+            )
+            .await;
+
+            return Err(UserStoreError::UserNotFound);
+        };
+
+        verify_with_pepper(
+            &self.peppers,
+            Secret::new(row.password_hash),
+            row.pepper_version,
+            password,
+        )
+        .await?;
+
+        let result = sqlx::query!(
+            r#"UPDATE users
+	       SET deleted_at = NULL, updated_at = NOW()
+	       WHERE lower(email) = lower($1) AND deleted_at IS NOT NULL"#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Purging deleted users from PostgreSQL", skip_all)]
+    async fn purge_deleted_users(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<usize, UserStoreError> {
+        let result = sqlx::query!(
+            r#"DELETE FROM users
+	       WHERE deleted_at < $1"#,
+            deleted_before,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(result.rows_affected() as usize)
+    }
 }
 
-#[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: Secret<String>,
-    password_candidate: Secret<String>,
-) -> Result<()> {
-    let current_span: tracing::Span = tracing::Span::current();
-    let hash_result = task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(expected_password_hash.expose_secret())?;
-
-            Argon2::default()
-                .verify_password(
-                    password_candidate.expose_secret().as_bytes(),
-                    &expected_password_hash,
-                )
-                .wrap_err("failed to verify password hash")
-        })
-    })
-    .await;
+/// Hashes the password with the current pepper applied, if there is one, and
+/// returns the pepper version to store alongside the hash.
+async fn hash_with_pepper(
+    peppers: &Peppers,
+    password: &Password,
+) -> Result<(Secret<String>, Option<i32>), UserStoreError> {
+    let (pepper_version, password) = match &peppers.current {
+        Some((version, pepper)) => (
+            Some(*version),
+            pepper_password(password.as_ref(), pepper).map_err(UserStoreError::UnexpectedError)?,
+        ),
+        None => (None, password.as_ref().to_owned()),
+    };
 
-    hash_result?
+    let password_hash = compute_password_hash(password)
+        .await
+        .map_err(UserStoreError::UnexpectedError)?;
+
+    Ok((password_hash, pepper_version))
 }
 
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
-    let current_span: tracing::Span = tracing::Span::current();
-    let compute_result = task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(1500, 2, 1, None)?,
-            )
-            .hash_password(password.expose_secret().as_bytes(), &salt)?
-            .to_string();
+/// Checks the password against a hash made with the pepper of the given
+/// version, or without one for hashes from before a pepper was configured.
+async fn verify_with_pepper(
+    peppers: &Peppers,
+    password_hash: Secret<String>,
+    pepper_version: Option<i32>,
+    password: &Password,
+) -> Result<(), UserStoreError> {
+    let password = match pepper_version {
+        Some(version) => peppers
+            .get(version)
+            .and_then(|pepper| pepper_password(password.as_ref(), pepper))
+            .map_err(UserStoreError::UnexpectedError)?,
+        None => password.as_ref().to_owned(),
+    };
 
-            Ok(Secret::new(password_hash))
-        })
+    verify_password_hash(password_hash, password)
+        .await
+        .map_err(|_| UserStoreError::InvalidCredentials)
+}
+
+fn to_user(
+    id: Uuid,
+    email: String,
+    password_hash: String,
+    two_fa_method: String,
+    email_verified: bool,
+) -> Result<User, UserStoreError> {
+    Ok(User {
+        id: UserId::from(id),
+        email: Email::parse(Secret::new(email)).map_err(UserStoreError::UnexpectedError)?,
+        password: Password::parse(Secret::new(password_hash))
+            .map_err(UserStoreError::UnexpectedError)?,
+        two_fa_method: TwoFAMethod::parse(&two_fa_method)
+            .map_err(UserStoreError::UnexpectedError)?,
+        email_verified,
     })
-    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    compute_result?
+    fn password() -> Password {
+        Password::parse(Secret::new("password123".to_owned())).unwrap()
+    }
+
+    fn peppers() -> Peppers {
+        Peppers {
+            current: Some((2, Secret::new("current-pepper".to_owned()))),
+            previous: vec![(1, Secret::new("previous-pepper".to_owned()))],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_with_current_pepper() {
+        let (password_hash, pepper_version) =
+            hash_with_pepper(&peppers(), &password()).await.unwrap();
+
+        assert_eq!(pepper_version, Some(2));
+        assert_eq!(
+            verify_with_pepper(
+                &peppers(),
+                password_hash.clone(),
+                pepper_version,
+                &password()
+            )
+            .await,
+            Ok(())
+        );
+        // The pepper is part of the hash /////////////////////////////////////
+        assert_eq!(
+            verify_with_pepper(&peppers(), password_hash, None, &password()).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_with_previous_pepper() {
+        let previous_peppers = Peppers {
+            current: Some((1, Secret::new("previous-pepper".to_owned()))),
+            previous: vec![],
+        };
+        let (password_hash, pepper_version) = hash_with_pepper(&previous_peppers, &password())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            verify_with_pepper(
+                &peppers(),
+                password_hash.clone(),
+                pepper_version,
+                &password()
+            )
+            .await,
+            Ok(())
+        );
+        // Wrong password /////////////////////////////////////////////////////
+        assert_eq!(
+            verify_with_pepper(
+                &peppers(),
+                password_hash.clone(),
+                pepper_version,
+                &Password::parse(Secret::new("wrong-password".to_owned())).unwrap()
+            )
+            .await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        // Pepper no longer configured ////////////////////////////////////////
+        assert!(matches!(
+            verify_with_pepper(
+                &Peppers::default(),
+                password_hash,
+                pepper_version,
+                &password()
+            )
+            .await,
+            Err(UserStoreError::UnexpectedError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_without_pepper() {
+        let (password_hash, pepper_version) = hash_with_pepper(&Peppers::default(), &password())
+            .await
+            .unwrap();
+
+        assert_eq!(pepper_version, None);
+        assert_eq!(
+            verify_with_pepper(&peppers(), password_hash, pepper_version, &password()).await,
+            Ok(())
+        );
+    }
 }
@@ -2,7 +2,6 @@ use std::sync::Arc;
 
 use color_eyre::eyre::Context;
 use redis::{Commands, Connection};
-use secrecy::{ExposeSecret, Secret};
 use tokio::sync::RwLock;
 
 use crate::{
@@ -23,11 +22,8 @@ impl RedisBannedTokenStore {
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "Adding token to banned tokens", skip_all)]
-    async fn add_banned_token(
-        &mut self,
-        token: Secret<String>,
-    ) -> Result<(), BannedTokenStoreError> {
-        let key = get_key(&token);
+    async fn add_banned_token(&mut self, jti: &str) -> Result<(), BannedTokenStoreError> {
+        let key = get_key(jti);
 
         let ttl: u64 = TOKEN_TTL_SECONDS
             .try_into()
@@ -36,7 +32,7 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         let mut conn = self.conn.write().await;
 
-        conn.set_ex(key.expose_secret(), true, ttl)
+        conn.set_ex(&key, true, ttl)
             .wrap_err("failed to set banned token in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
@@ -44,13 +40,13 @@ impl BannedTokenStore for RedisBannedTokenStore {
     }
 
     #[tracing::instrument(name = "Checking if token is banned", skip_all)]
-    async fn is_banned_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        let key = get_key(token);
+    async fn is_banned_token(&self, jti: &str) -> Result<bool, BannedTokenStoreError> {
+        let key = get_key(jti);
 
         let mut conn = self.conn.write().await;
 
         let is_banned: bool = conn
-            .exists(key.expose_secret())
+            .exists(&key)
             .wrap_err("failed to check if token exists in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
@@ -61,10 +57,6 @@ impl BannedTokenStore for RedisBannedTokenStore {
 const BANNED_TOKENS_KEY_PREFIX: &str = "banned_token:";
 
 #[tracing::instrument(name = "Building key format for redis", skip_all)]
-fn get_key(token: &Secret<String>) -> Secret<String> {
-    Secret::new(format!(
-        "{}{}",
-        BANNED_TOKENS_KEY_PREFIX,
-        token.expose_secret()
-    ))
+fn get_key(jti: &str) -> String {
+    format!("{}{}", BANNED_TOKENS_KEY_PREFIX, jti)
 }
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, EmailChangeToken, EmailChangeTokenStore, EmailChangeTokenStoreError},
+    utils::constants::EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisEmailChangeTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisEmailChangeTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailChangeTokenStore for RedisEmailChangeTokenStore {
+    #[tracing::instrument(name = "Adding email change token", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        new_email: Email,
+        token: EmailChangeToken,
+    ) -> Result<(), EmailChangeTokenStoreError> {
+        let key = get_key(&token);
+
+        let value = serde_json::to_string(&EmailChangeRecord {
+            email: email.as_ref().expose_secret().to_owned(),
+            new_email: new_email.as_ref().expose_secret().to_owned(),
+        })
+        .wrap_err("failed to serialize email change record")
+        .map_err(EmailChangeTokenStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        conn.set_ex(&key, value, EMAIL_CHANGE_TOKEN_TTL_SECONDS)
+            .wrap_err("failed to set email change token in Redis")
+            .map_err(EmailChangeTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming email change token", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &EmailChangeToken,
+    ) -> Result<(Email, Email), EmailChangeTokenStoreError> {
+        let key = get_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        let value: Option<String> = conn
+            .get_del(&key)
+            .wrap_err("failed to consume email change token in Redis")
+            .map_err(EmailChangeTokenStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(EmailChangeTokenStoreError::TokenNotFound)?;
+
+        let record: EmailChangeRecord = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize email change record")
+            .map_err(EmailChangeTokenStoreError::UnexpectedError)?;
+
+        let email = Email::parse(Secret::new(record.email))
+            .map_err(EmailChangeTokenStoreError::UnexpectedError)?;
+        let new_email = Email::parse(Secret::new(record.new_email))
+            .map_err(EmailChangeTokenStoreError::UnexpectedError)?;
+
+        Ok((email, new_email))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EmailChangeRecord {
+    email: String,
+    new_email: String,
+}
+
+const EMAIL_CHANGE_TOKEN_PREFIX: &str = "email_change_token:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_key(token: &EmailChangeToken) -> String {
+    format!("{}{}", EMAIL_CHANGE_TOKEN_PREFIX, token.hash())
+}
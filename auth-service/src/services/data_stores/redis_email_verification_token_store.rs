@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        Email, EmailVerificationToken, EmailVerificationTokenStore,
+        EmailVerificationTokenStoreError,
+    },
+    utils::constants::{
+        EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    },
+};
+
+pub struct RedisEmailVerificationTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisEmailVerificationTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for RedisEmailVerificationTokenStore {
+    #[tracing::instrument(name = "Adding email verification token", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let key = get_token_key(&token);
+
+        let mut conn = self.conn.write().await;
+
+        conn.set_ex(
+            &key,
+            email.as_ref().expose_secret(),
+            EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+        )
+        .wrap_err("failed to set email verification token in Redis")
+        .map_err(EmailVerificationTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming email verification token", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError> {
+        let key = get_token_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        let email: Option<String> = conn
+            .get_del(&key)
+            .wrap_err("failed to consume email verification token in Redis")
+            .map_err(EmailVerificationTokenStoreError::UnexpectedError)?;
+
+        let email = email.ok_or(EmailVerificationTokenStoreError::TokenNotFound)?;
+
+        Email::parse(Secret::new(email)).map_err(EmailVerificationTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Recording verification email send", skip_all)]
+    async fn record_email_sent(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let key = get_throttle_key(email);
+
+        let mut conn = self.conn.write().await;
+
+        // SET NX only succeeds when no email was sent within the interval.
+        let was_set: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS)
+            .query(&mut *conn)
+            .wrap_err("failed to set email verification throttle in Redis")
+            .map_err(EmailVerificationTokenStoreError::UnexpectedError)?;
+
+        if was_set.is_none() {
+            return Err(EmailVerificationTokenStoreError::ResendThrottled);
+        }
+
+        Ok(())
+    }
+}
+
+const EMAIL_VERIFICATION_TOKEN_PREFIX: &str = "email_verification_token:";
+const EMAIL_VERIFICATION_THROTTLE_PREFIX: &str = "email_verification_throttle:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_token_key(token: &EmailVerificationToken) -> String {
+    format!("{}{}", EMAIL_VERIFICATION_TOKEN_PREFIX, token.hash())
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_throttle_key(email: &Email) -> String {
+    format!(
+        "{}{}",
+        EMAIL_VERIFICATION_THROTTLE_PREFIX,
+        email.lowercase().expose_secret()
+    )
+}
@@ -0,0 +1,104 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{FailedLoginKey, FailedLoginStore, FailedLoginStoreError},
+    utils::constants::FAILED_LOGIN_WINDOW_SECONDS,
+};
+
+pub struct RedisFailedLoginStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisFailedLoginStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedLoginStore for RedisFailedLoginStore {
+    #[tracing::instrument(name = "Recording failed login", skip_all)]
+    async fn record_failure(&mut self, key: &FailedLoginKey) -> Result<u32, FailedLoginStoreError> {
+        let key = get_failures_key(key);
+
+        let mut conn = self.conn.write().await;
+
+        // Every failure pushes the expiry back, so the count only resets after
+        // a full window without any.
+        let (count,): (u32,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, FAILED_LOGIN_WINDOW_SECONDS as i64)
+            .ignore()
+            .query(&mut *conn)
+            .wrap_err("failed to record failed login in Redis")
+            .map_err(FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(count)
+    }
+
+    #[tracing::instrument(name = "Clearing failed logins", skip_all)]
+    async fn clear_failures(&mut self, key: &FailedLoginKey) -> Result<(), FailedLoginStoreError> {
+        let key = get_failures_key(key);
+
+        let mut conn = self.conn.write().await;
+
+        conn.del(&key)
+            .wrap_err("failed to clear failed logins in Redis")
+            .map_err(FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Locking logins", skip_all)]
+    async fn lock(
+        &mut self,
+        key: &FailedLoginKey,
+        duration: Duration,
+    ) -> Result<(), FailedLoginStoreError> {
+        let key = get_lockout_key(key);
+
+        let mut conn = self.conn.write().await;
+
+        conn.set_ex(&key, true, duration.as_secs().max(1))
+            .wrap_err("failed to set login lockout in Redis")
+            .map_err(FailedLoginStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting login lockout", skip_all)]
+    async fn get_lockout(
+        &self,
+        key: &FailedLoginKey,
+    ) -> Result<Option<Duration>, FailedLoginStoreError> {
+        let key = get_lockout_key(key);
+
+        let mut conn = self.conn.write().await;
+
+        // TTL is negative when the key doesn't exist
+        let ttl: i64 = conn
+            .ttl(&key)
+            .wrap_err("failed to get login lockout from Redis")
+            .map_err(FailedLoginStoreError::UnexpectedError)?;
+
+        Ok((ttl > 0).then(|| Duration::from_secs(ttl as u64)))
+    }
+}
+
+const FAILED_LOGINS_PREFIX: &str = "failed_logins:";
+const LOGIN_LOCKOUT_PREFIX: &str = "login_lockout:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_failures_key(key: &FailedLoginKey) -> String {
+    format!("{}{}", FAILED_LOGINS_PREFIX, key)
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_lockout_key(key: &FailedLoginKey) -> String {
+    format!("{}{}", LOGIN_LOCKOUT_PREFIX, key)
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, MagicLinkToken, MagicLinkTokenStore, MagicLinkTokenStoreError},
+    utils::constants::{MAGIC_LINK_RESEND_INTERVAL_SECONDS, MAGIC_LINK_TOKEN_TTL_SECONDS},
+};
+
+pub struct RedisMagicLinkTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisMagicLinkTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkTokenStore for RedisMagicLinkTokenStore {
+    #[tracing::instrument(name = "Adding magic link token", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: MagicLinkToken,
+    ) -> Result<(), MagicLinkTokenStoreError> {
+        let key = get_key(&token);
+
+        let mut conn = self.conn.write().await;
+
+        conn.set_ex(
+            &key,
+            email.as_ref().expose_secret(),
+            MAGIC_LINK_TOKEN_TTL_SECONDS,
+        )
+        .wrap_err("failed to set magic link token in Redis")
+        .map_err(MagicLinkTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming magic link token", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &MagicLinkToken,
+    ) -> Result<Email, MagicLinkTokenStoreError> {
+        let key = get_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        let email: Option<String> = conn
+            .get_del(&key)
+            .wrap_err("failed to consume magic link token in Redis")
+            .map_err(MagicLinkTokenStoreError::UnexpectedError)?;
+
+        let email = email.ok_or(MagicLinkTokenStoreError::TokenNotFound)?;
+
+        Email::parse(Secret::new(email)).map_err(MagicLinkTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Recording magic link request", skip_all)]
+    async fn record_link_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), MagicLinkTokenStoreError> {
+        let keys = ip_address
+            .map(|ip_address| format!("ip:{}", ip_address))
+            .into_iter()
+            .chain(std::iter::once(format!(
+                "email:{}",
+                email.lowercase().expose_secret()
+            )));
+
+        let mut conn = self.conn.write().await;
+
+        for key in keys {
+            // SET NX only succeeds when no link was requested within the
+            // interval.
+            let was_set: Option<String> = redis::cmd("SET")
+                .arg(get_throttle_key(&key))
+                .arg(true)
+                .arg("NX")
+                .arg("EX")
+                .arg(MAGIC_LINK_RESEND_INTERVAL_SECONDS)
+                .query(&mut *conn)
+                .wrap_err("failed to set magic link throttle in Redis")
+                .map_err(MagicLinkTokenStoreError::UnexpectedError)?;
+
+            if was_set.is_none() {
+                return Err(MagicLinkTokenStoreError::ResendThrottled);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const MAGIC_LINK_TOKEN_PREFIX: &str = "magic_link_token:";
+const MAGIC_LINK_THROTTLE_PREFIX: &str = "magic_link_throttle:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_key(token: &MagicLinkToken) -> String {
+    format!("{}{}", MAGIC_LINK_TOKEN_PREFIX, token.hash())
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_throttle_key(key: &str) -> String {
+    format!("{}{}", MAGIC_LINK_THROTTLE_PREFIX, key)
+}
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        LoginAttemptId, PasskeyCeremony, PasskeyChallenge, PasskeyChallengeStore,
+        PasskeyChallengeStoreError, UserId,
+    },
+    utils::constants::PASSKEY_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisPasskeyChallengeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasskeyChallengeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyChallengeStore for RedisPasskeyChallengeStore {
+    #[tracing::instrument(name = "Adding passkey challenge", skip_all)]
+    async fn add_challenge(
+        &mut self,
+        challenge: PasskeyChallenge,
+        ceremony: PasskeyCeremony,
+    ) -> Result<(), PasskeyChallengeStoreError> {
+        let key = get_key(&challenge);
+
+        let value = serde_json::to_string(&CeremonyRecord::from(ceremony))
+            .wrap_err("failed to serialize passkey ceremony")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        conn.set_ex(&key, value, PASSKEY_CHALLENGE_TTL_SECONDS)
+            .wrap_err("failed to set passkey challenge in Redis")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming passkey challenge", skip_all)]
+    async fn consume_challenge(
+        &mut self,
+        challenge: &PasskeyChallenge,
+    ) -> Result<PasskeyCeremony, PasskeyChallengeStoreError> {
+        let key = get_key(challenge);
+
+        let mut conn = self.conn.write().await;
+
+        let value: Option<String> = conn
+            .get_del(&key)
+            .wrap_err("failed to consume passkey challenge in Redis")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(PasskeyChallengeStoreError::ChallengeNotFound)?;
+
+        let record: CeremonyRecord = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize passkey ceremony")
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)?;
+
+        record
+            .into_ceremony()
+            .map_err(PasskeyChallengeStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum CeremonyRecord {
+    Registration(String),
+    Login,
+    SecondFactor(String, String),
+}
+
+impl From<PasskeyCeremony> for CeremonyRecord {
+    fn from(ceremony: PasskeyCeremony) -> Self {
+        match ceremony {
+            PasskeyCeremony::Registration { user_id } => Self::Registration(user_id.to_string()),
+            PasskeyCeremony::Login => Self::Login,
+            PasskeyCeremony::SecondFactor {
+                user_id,
+                login_attempt_id,
+            } => Self::SecondFactor(
+                user_id.to_string(),
+                login_attempt_id.as_ref().expose_secret().to_owned(),
+            ),
+        }
+    }
+}
+
+impl CeremonyRecord {
+    fn into_ceremony(self) -> color_eyre::Result<PasskeyCeremony> {
+        Ok(match self {
+            Self::Registration(user_id) => PasskeyCeremony::Registration {
+                user_id: UserId::parse(&user_id)?,
+            },
+            Self::Login => PasskeyCeremony::Login,
+            Self::SecondFactor(user_id, login_attempt_id) => PasskeyCeremony::SecondFactor {
+                user_id: UserId::parse(&user_id)?,
+                login_attempt_id: LoginAttemptId::parse(Secret::new(login_attempt_id))?,
+            },
+        })
+    }
+}
+
+const PASSKEY_CHALLENGE_PREFIX: &str = "passkey_challenge:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_key(challenge: &PasskeyChallenge) -> String {
+    format!("{}{}", PASSKEY_CHALLENGE_PREFIX, challenge.encoded())
+}
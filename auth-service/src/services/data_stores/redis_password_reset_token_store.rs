@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    utils::constants::{PASSWORD_RESET_RESEND_INTERVAL_SECONDS, PASSWORD_RESET_TOKEN_TTL_SECONDS},
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    #[tracing::instrument(name = "Adding password reset token", skip_all)]
+    async fn add_token(
+        &mut self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(&token);
+        let index_key = get_index_key(&email);
+
+        let mut conn = self.conn.write().await;
+
+        // The index of the account's tokens lives as long as the newest one,
+        // so `remove_tokens` can find them all.
+        redis::pipe()
+            .atomic()
+            .set_ex(
+                &key,
+                email.as_ref().expose_secret(),
+                PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            )
+            .ignore()
+            .sadd(&index_key, &key)
+            .ignore()
+            .expire(&index_key, PASSWORD_RESET_TOKEN_TTL_SECONDS as i64)
+            .ignore()
+            .query::<()>(&mut *conn)
+            .wrap_err("failed to set password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting password reset token", skip_all)]
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let key = get_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        let email: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let email = email.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        Email::parse(Secret::new(email)).map_err(PasswordResetTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Consuming password reset token", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let key = get_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        // GETDEL reads and invalidates the token in a single round trip, so
+        // two concurrent requests can't both redeem it.
+        let email: Option<String> = conn
+            .get_del(&key)
+            .wrap_err("failed to consume password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        let email = email.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        Email::parse(Secret::new(email)).map_err(PasswordResetTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Removing password reset tokens", skip_all)]
+    async fn remove_tokens(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        let index_key = get_index_key(email);
+
+        let mut conn = self.conn.write().await;
+
+        let mut keys: Vec<String> = conn
+            .smembers(&index_key)
+            .wrap_err("failed to get password reset tokens from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        keys.push(index_key);
+
+        conn.del(&keys)
+            .wrap_err("failed to remove password reset tokens from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording password reset request", skip_all)]
+    async fn record_reset_requested(
+        &mut self,
+        email: &Email,
+        ip_address: Option<&str>,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let keys = ip_address
+            .map(|ip_address| format!("ip:{}", ip_address))
+            .into_iter()
+            .chain(std::iter::once(format!(
+                "email:{}",
+                email.lowercase().expose_secret()
+            )));
+
+        let mut conn = self.conn.write().await;
+
+        for key in keys {
+            // SET NX only succeeds when no reset was requested within the
+            // interval.
+            let was_set: Option<String> = redis::cmd("SET")
+                .arg(get_throttle_key(&key))
+                .arg(true)
+                .arg("NX")
+                .arg("EX")
+                .arg(PASSWORD_RESET_RESEND_INTERVAL_SECONDS)
+                .query(&mut *conn)
+                .wrap_err("failed to set password reset throttle in Redis")
+                .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+            if was_set.is_none() {
+                return Err(PasswordResetTokenStoreError::ResendThrottled);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+const PASSWORD_RESET_INDEX_PREFIX: &str = "password_reset_tokens:";
+const PASSWORD_RESET_THROTTLE_PREFIX: &str = "password_reset_throttle:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_key(token: &PasswordResetToken) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, token.hash())
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_index_key(email: &Email) -> String {
+    format!(
+        "{}{}",
+        PASSWORD_RESET_INDEX_PREFIX,
+        email.lowercase().expose_secret()
+    )
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_throttle_key(key: &str) -> String {
+    format!("{}{}", PASSWORD_RESET_THROTTLE_PREFIX, key)
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection, ErrorKind, RedisError, RedisResult};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{RefreshToken, RefreshTokenStore, RefreshTokenStoreError, SessionId, UserId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token", skip_all)]
+    async fn add_token(
+        &mut self,
+        session_id: SessionId,
+        user_id: UserId,
+        token: RefreshToken,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let family_id = session_id.to_string();
+        let ttl = get_ttl()?;
+
+        let mut conn = self.conn.write().await;
+
+        conn.set_ex(get_family_key(&family_id), user_id.to_string(), ttl)
+            .wrap_err("failed to set refresh token family in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        set_token_record(&mut conn, &token, &family_id, ttl)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rotating refresh token", skip_all)]
+    async fn rotate_token(
+        &mut self,
+        current: &RefreshToken,
+        next: RefreshToken,
+    ) -> Result<(UserId, SessionId), RefreshTokenStoreError> {
+        let token_key = get_token_key(current);
+        let ttl = get_ttl()?;
+
+        let mut conn = self.conn.write().await;
+
+        // The token is watched, so when another instance rotates it at the
+        // same time the transaction fails and is retried, seeing it as used.
+        let rotation = redis::transaction(&mut *conn, &[&token_key], |conn, pipe| {
+            let record: Option<String> = conn.get(&token_key)?;
+
+            let record: RefreshTokenRecord = match record {
+                Some(record) => serde_json::from_str(&record).map_err(|e| {
+                    RedisError::from((
+                        ErrorKind::TypeError,
+                        "failed to deserialize refresh token record",
+                        e.to_string(),
+                    ))
+                })?,
+                None => return Ok(Some(Rotation::NotFound)),
+            };
+
+            let family_key = get_family_key(&record.family_id);
+
+            let user_id: Option<String> = conn.get(&family_key)?;
+
+            let user_id = match user_id {
+                Some(user_id) => user_id,
+                None => return Ok(Some(Rotation::NotFound)),
+            };
+
+            if record.used {
+                return Ok(Some(Rotation::Reused(user_id, family_key)));
+            }
+
+            let used_record = serialize_record(&record.family_id, true)?;
+            let next_record = serialize_record(&record.family_id, false)?;
+
+            // Keep the rotated token around until it would have expired
+            // anyway, so presenting it again is detected as reuse.
+            pipe.cmd("SET")
+                .arg(&token_key)
+                .arg(used_record)
+                .arg("KEEPTTL")
+                .ignore()
+                .set_ex(get_token_key(&next), next_record, ttl)
+                .ignore()
+                .set_ex(&family_key, &user_id, ttl)
+                .ignore()
+                .query::<Option<()>>(conn)
+                .map(|result| result.map(|_| Rotation::Rotated(user_id, record.family_id)))
+        })
+        .wrap_err("failed to rotate refresh token in Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        match rotation {
+            Rotation::NotFound => Err(RefreshTokenStoreError::TokenNotFound),
+            Rotation::Reused(user_id, family_key) => {
+                conn.del(&family_key)
+                    .wrap_err("failed to revoke refresh token family in Redis")
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+                let user_id =
+                    UserId::parse(&user_id).map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+                Err(RefreshTokenStoreError::TokenReused(user_id))
+            }
+            Rotation::Rotated(user_id, family_id) => {
+                let user_id =
+                    UserId::parse(&user_id).map_err(RefreshTokenStoreError::UnexpectedError)?;
+                let session_id = SessionId::parse(&family_id)
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+                Ok((user_id, session_id))
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family", skip_all)]
+    async fn revoke_family(
+        &mut self,
+        session_id: &SessionId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let removed: u64 = conn
+            .del(get_family_key(&session_id.to_string()))
+            .wrap_err("failed to revoke refresh token family in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        if removed == 0 {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenRecord {
+    family_id: String,
+    used: bool,
+}
+
+fn get_ttl() -> Result<u64, RefreshTokenStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .wrap_err("failed to convert TTL to u64")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+/// Outcome of looking a token up for rotation, decided inside the transaction.
+enum Rotation {
+    NotFound,
+    Reused(String, String),
+    Rotated(String, String),
+}
+
+fn serialize_record(family_id: &str, used: bool) -> RedisResult<String> {
+    serde_json::to_string(&RefreshTokenRecord {
+        family_id: family_id.to_owned(),
+        used,
+    })
+    .map_err(|e| {
+        RedisError::from((
+            ErrorKind::TypeError,
+            "failed to serialize refresh token record",
+            e.to_string(),
+        ))
+    })
+}
+
+fn set_token_record(
+    conn: &mut Connection,
+    token: &RefreshToken,
+    family_id: &str,
+    ttl: u64,
+) -> Result<(), RefreshTokenStoreError> {
+    let record = serialize_record(family_id, false)
+        .wrap_err("failed to serialize refresh token record")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+    conn.set_ex(get_token_key(token), record, ttl)
+        .wrap_err("failed to set refresh token in Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+const REFRESH_TOKEN_PREFIX: &str = "refresh_token:";
+const REFRESH_TOKEN_FAMILY_PREFIX: &str = "refresh_token_family:";
+
+fn get_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_PREFIX, token.hash())
+}
+
+fn get_family_key(family_id: &str) -> String {
+    format!("{}{}", REFRESH_TOKEN_FAMILY_PREFIX, family_id)
+}
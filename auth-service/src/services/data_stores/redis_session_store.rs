@@ -0,0 +1,244 @@
+use std::{cmp::Reverse, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Session, SessionId, SessionStore, SessionStoreError, UserId},
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisSessionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    #[tracing::instrument(name = "Adding session to Redis", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let ttl = get_ttl()?;
+        let user_key = get_user_key(&session.user_id);
+
+        let mut conn = self.conn.write().await;
+
+        set_session_record(&mut conn, &session, ttl)?;
+
+        conn.sadd(&user_key, session.id.to_string())
+            .wrap_err("failed to add session to user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        conn.expire(&user_key, ttl as i64)
+            .wrap_err("failed to set expiry of user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting session from Redis", skip_all)]
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        get_session_record(&mut conn, id)?.ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    #[tracing::instrument(name = "Getting user sessions from Redis", skip_all)]
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let user_key = get_user_key(user_id);
+
+        let mut conn = self.conn.write().await;
+
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = Vec::new();
+
+        for id in ids {
+            let session = match SessionId::parse(&id) {
+                Ok(session_id) => get_session_record(&mut conn, &session_id)?,
+                Err(_) => None,
+            };
+
+            match session {
+                Some(session) => sessions.push(session),
+                // The session expired on its own, drop it from the index
+                None => conn
+                    .srem(&user_key, &id)
+                    .wrap_err("failed to remove expired session from Redis")
+                    .map_err(SessionStoreError::UnexpectedError)?,
+            }
+        }
+
+        sessions.sort_by_key(|session| Reverse(session.last_seen));
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Touching session in Redis", skip_all)]
+    async fn touch_session(&mut self, id: &SessionId) -> Result<(), SessionStoreError> {
+        let ttl = get_ttl()?;
+
+        let mut conn = self.conn.write().await;
+
+        let mut session =
+            get_session_record(&mut conn, id)?.ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen = Utc::now();
+
+        set_session_record(&mut conn, &session, ttl)?;
+
+        conn.expire(get_user_key(&session.user_id), ttl as i64)
+            .wrap_err("failed to set expiry of user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing session from Redis", skip_all)]
+    async fn remove_session(
+        &mut self,
+        user_id: &UserId,
+        id: &SessionId,
+    ) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        match get_session_record(&mut conn, id)? {
+            Some(session) if &session.user_id == user_id => (),
+            _ => return Err(SessionStoreError::SessionNotFound),
+        }
+
+        conn.del(get_session_key(id))
+            .wrap_err("failed to delete session from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        conn.srem(get_user_key(user_id), id.to_string())
+            .wrap_err("failed to remove session from user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing user sessions from Redis", skip_all)]
+    async fn remove_sessions(
+        &mut self,
+        user_id: &UserId,
+    ) -> Result<Vec<SessionId>, SessionStoreError> {
+        let user_key = get_user_key(user_id);
+
+        let mut conn = self.conn.write().await;
+
+        let ids: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let ids: Vec<SessionId> = ids
+            .iter()
+            .filter_map(|id| SessionId::parse(id).ok())
+            .collect();
+
+        for id in &ids {
+            conn.del(get_session_key(id))
+                .wrap_err("failed to delete session from Redis")
+                .map_err(SessionStoreError::UnexpectedError)?;
+        }
+
+        conn.del(&user_key)
+            .wrap_err("failed to delete user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(ids)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionRecord {
+    user_id: String,
+    created_at: i64,
+    last_seen: i64,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+fn get_ttl() -> Result<u64, SessionStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .wrap_err("failed to convert TTL to u64")
+        .map_err(SessionStoreError::UnexpectedError)
+}
+
+fn get_session_record(
+    conn: &mut Connection,
+    id: &SessionId,
+) -> Result<Option<Session>, SessionStoreError> {
+    let value: Option<String> = conn
+        .get(get_session_key(id))
+        .wrap_err("failed to get session from Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let record: SessionRecord = match value {
+        Some(value) => serde_json::from_str(&value)
+            .wrap_err("failed to deserialize session record")
+            .map_err(SessionStoreError::UnexpectedError)?,
+        None => return Ok(None),
+    };
+
+    let user_id = UserId::parse(&record.user_id).map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(Some(Session {
+        id: *id,
+        user_id,
+        created_at: from_timestamp(record.created_at)?,
+        last_seen: from_timestamp(record.last_seen)?,
+        user_agent: record.user_agent,
+        ip_address: record.ip_address,
+    }))
+}
+
+fn set_session_record(
+    conn: &mut Connection,
+    session: &Session,
+    ttl: u64,
+) -> Result<(), SessionStoreError> {
+    let record = serde_json::to_string(&SessionRecord {
+        user_id: session.user_id.to_string(),
+        created_at: session.created_at.timestamp(),
+        last_seen: session.last_seen.timestamp(),
+        user_agent: session.user_agent.clone(),
+        ip_address: session.ip_address.clone(),
+    })
+    .wrap_err("failed to serialize session record")
+    .map_err(SessionStoreError::UnexpectedError)?;
+
+    conn.set_ex(get_session_key(&session.id), record, ttl)
+        .wrap_err("failed to set session in Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(())
+}
+
+fn from_timestamp(timestamp: i64) -> Result<DateTime<Utc>, SessionStoreError> {
+    DateTime::from_timestamp(timestamp, 0).ok_or(SessionStoreError::UnexpectedError(eyre!(
+        "invalid session timestamp {}",
+        timestamp
+    )))
+}
+
+const SESSION_PREFIX: &str = "session:";
+const USER_SESSIONS_PREFIX: &str = "user_sessions:";
+
+fn get_session_key(id: &SessionId) -> String {
+    format!("{}{}", SESSION_PREFIX, id)
+}
+
+fn get_user_key(user_id: &UserId) -> String {
+    format!("{}{}", USER_SESSIONS_PREFIX, user_id)
+}
@@ -1,20 +1,42 @@
 use std::sync::Arc;
 
+use chrono::Utc;
 use color_eyre::eyre::Context;
 use redis::{Commands, Connection};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::{
+    domain::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, UserId},
+    utils::constants::{
+        MAX_PENDING_TWO_FA_CODES, MAX_TWO_FA_RESENDS, TWO_FA_CODE_TTL_SECONDS,
+        TWO_FA_RESEND_INTERVAL_SECONDS,
+    },
+};
 
+/// Keeps each challenge under its login attempt ID, next to a sorted set per
+/// user of their attempt IDs scored by expiry time, which is what the cap and
+/// `remove_codes` work from.
 pub struct RedisTwoFACodeStore {
     conn: Arc<RwLock<Connection>>,
+    resend_interval_seconds: u64,
 }
 
 impl RedisTwoFACodeStore {
     pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            resend_interval_seconds: TWO_FA_RESEND_INTERVAL_SECONDS,
+        }
+    }
+
+    /// Overrides how long a code has to be out before it can be resent.
+    /// Redis expiries are whole seconds, so this has to be at least one.
+    pub fn with_resend_interval(mut self, seconds: u64) -> Self {
+        self.resend_interval_seconds = seconds.max(1);
+        self
     }
 }
 
@@ -23,76 +45,302 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name = "Adding 2FA code", skip_all)]
     async fn add_code(
         &mut self,
-        email: Email,
+        user_id: UserId,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
+        let login_attempt_id = login_attempt_id.as_ref().expose_secret();
+        let index_key = get_index_key(&user_id);
 
         let value = TwoFATuple(
-            login_attempt_id.as_ref().expose_secret().to_string(),
+            user_id.to_string(),
             code.as_ref().expose_secret().to_string(),
         );
 
         let value = serde_json::to_string(&value)
             .wrap_err("failed to serialize 2FA tuple")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let now = Utc::now().timestamp();
+        let expires_at = now + TWO_FA_CODE_TTL_SECONDS as i64;
+
         let mut conn = self.conn.write().await;
 
-        conn.set_ex(&key, value, TEN_MINUTES)
+        // A new code starts with a clean slate of attempts and can't be resent
+        // right away. The index lives as long as the newest code in it, and
+        // forgets the ones that expired.
+        let (outstanding,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .set_ex(get_key(login_attempt_id), value, TWO_FA_CODE_TTL_SECONDS)
+            .ignore()
+            .del(get_attempts_key(login_attempt_id))
+            .ignore()
+            .set_ex(
+                get_resend_throttle_key(login_attempt_id),
+                true,
+                self.resend_interval_seconds,
+            )
+            .ignore()
+            .zrembyscore(&index_key, "-inf", now)
+            .ignore()
+            .zadd(&index_key, login_attempt_id, expires_at)
+            .ignore()
+            .expire(&index_key, TWO_FA_CODE_TTL_SECONDS as i64)
+            .ignore()
+            .zrange(&index_key, 0, -1)
+            .query(&mut *conn)
             .wrap_err("failed to set 2FA code")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
+        let excess = outstanding.len().saturating_sub(MAX_PENDING_TWO_FA_CODES);
+
+        if excess > 0 {
+            remove_attempts(&mut conn, &index_key, &outstanding[..excess])?;
+        }
+
         Ok(())
     }
 
     #[tracing::instrument(name = "Getting 2FA code", skip_all)]
     async fn get_code(
         &self,
-        email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(UserId, TwoFACode), TwoFACodeStoreError> {
+        let key = get_key(login_attempt_id.as_ref().expose_secret());
 
         let mut conn = self.conn.write().await;
 
-        let value: String = redis::Cmd::get(&key)
+        let value: Option<String> = redis::Cmd::get(&key)
             .query(&mut conn)
-            .wrap_err("failed to deserialize 2FA tuple")
+            .wrap_err("failed to get 2FA code")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let TwoFATuple(login_attempt_id, code) = serde_json::from_str(&value)
-            .wrap_err("failed to deserialize 2FA tuple")
+        let value = value.ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        parse_tuple(&value)
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code", skip_all)]
+    async fn remove_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let (user_id, _) = self.get_code(login_attempt_id).await?;
+
+        let mut conn = self.conn.write().await;
+
+        remove_attempts(
+            &mut conn,
+            &get_index_key(&user_id),
+            &[login_attempt_id.as_ref().expose_secret().to_owned()],
+        )
+    }
+
+    #[tracing::instrument(name = "Replacing 2FA code", skip_all)]
+    async fn replace_code(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let login_attempt_id = login_attempt_id.as_ref().expose_secret();
+        let key = get_key(login_attempt_id);
+        let resends_key = get_resends_key(login_attempt_id);
+
+        let mut conn = self.conn.write().await;
+
+        let ttl: i64 = conn
+            .ttl(&key)
+            .wrap_err("failed to get 2FA code expiry")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let value: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get 2FA code")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // TTL is negative when there is no pending code
+        let value = match value {
+            Some(value) if ttl > 0 => value,
+            _ => return Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        };
+
+        // SET NX only succeeds when no code was sent within the interval.
+        let was_set: Option<String> = redis::cmd("SET")
+            .arg(get_resend_throttle_key(login_attempt_id))
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.resend_interval_seconds)
+            .query(&mut *conn)
+            .wrap_err("failed to set 2FA resend throttle")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if was_set.is_none() {
+            return Err(TwoFACodeStoreError::ResendThrottled);
+        }
+
+        let (resends,): (u32,) = redis::pipe()
+            .atomic()
+            .incr(&resends_key, 1)
+            .expire(&resends_key, ttl)
+            .ignore()
+            .query(&mut *conn)
+            .wrap_err("failed to count 2FA resends")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if resends > MAX_TWO_FA_RESENDS {
+            return Err(TwoFACodeStoreError::ResendThrottled);
+        }
+
+        let (user_id, _) = parse_tuple(&value)?;
+
+        let value = TwoFATuple(
+            user_id.to_string(),
+            code.as_ref().expose_secret().to_string(),
+        );
+
+        let value = serde_json::to_string(&value)
+            .wrap_err("failed to serialize 2FA tuple")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let login_attempt_id = LoginAttemptId::parse(Secret::new(login_attempt_id))
+        conn.set_ex(&key, value, ttl as u64)
+            .wrap_err("failed to replace 2FA code")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
-        let code =
-            TwoFACode::parse(Secret::new(code)).map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        Ok((login_attempt_id, code))
+        Ok(())
     }
 
-    #[tracing::instrument(name = "Removing 2FA code", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
+    #[tracing::instrument(name = "Removing 2FA codes", skip_all)]
+    async fn remove_codes(&mut self, user_id: &UserId) -> Result<(), TwoFACodeStoreError> {
+        let index_key = get_index_key(user_id);
 
         let mut conn = self.conn.write().await;
 
-        conn.del(&key)
-            .wrap_err("failed to remove 2FA code")
+        let outstanding: Vec<String> = conn
+            .zrange(&index_key, 0, -1)
+            .wrap_err("failed to get pending 2FA codes")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        remove_attempts(&mut conn, &index_key, &outstanding)?;
+
+        conn.del(&index_key)
+            .wrap_err("failed to remove 2FA code index")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
         Ok(())
     }
+
+    #[tracing::instrument(name = "Recording failed 2FA attempt", skip_all)]
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<u32, TwoFACodeStoreError> {
+        let login_attempt_id = login_attempt_id.as_ref().expose_secret();
+        let key = get_key(login_attempt_id);
+        let attempts_key = get_attempts_key(login_attempt_id);
+
+        let mut conn = self.conn.write().await;
+
+        let ttl: i64 = conn
+            .ttl(&key)
+            .wrap_err("failed to get 2FA code expiry")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // TTL is negative when there is no pending code
+        if ttl <= 0 {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        // The count goes away together with the code it belongs to
+        let (attempts,): (u32,) = redis::pipe()
+            .atomic()
+            .incr(&attempts_key, 1)
+            .expire(&attempts_key, ttl)
+            .ignore()
+            .query(&mut *conn)
+            .wrap_err("failed to record failed 2FA attempt")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(attempts)
+    }
+}
+
+fn remove_attempts(
+    conn: &mut Connection,
+    index_key: &str,
+    login_attempt_ids: &[String],
+) -> Result<(), TwoFACodeStoreError> {
+    if login_attempt_ids.is_empty() {
+        return Ok(());
+    }
+
+    let keys: Vec<String> = login_attempt_ids
+        .iter()
+        .flat_map(|id| {
+            [
+                get_key(id),
+                get_attempts_key(id),
+                get_resends_key(id),
+                get_resend_throttle_key(id),
+            ]
+        })
+        .collect();
+
+    redis::pipe()
+        .atomic()
+        .del(keys)
+        .ignore()
+        .zrem(index_key, login_attempt_ids)
+        .ignore()
+        .query(conn)
+        .wrap_err("failed to remove 2FA codes")
+        .map_err(TwoFACodeStoreError::UnexpectedError)
+}
+
+fn parse_tuple(value: &str) -> Result<(UserId, TwoFACode), TwoFACodeStoreError> {
+    let TwoFATuple(user_id, code) = serde_json::from_str(value)
+        .wrap_err("failed to deserialize 2FA tuple")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    let user_id = Uuid::parse_str(&user_id)
+        .map(UserId::from)
+        .wrap_err("failed to parse user ID of 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+    let code = TwoFACode::parse(Secret::new(code)).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    Ok((user_id, code))
 }
 
 #[derive(Serialize, Deserialize)]
 struct TwoFATuple(pub String, pub String);
 
-const TEN_MINUTES: u64 = 10 * 60;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
+const TWO_FA_RESENDS_PREFIX: &str = "two_fa_resends:";
+const TWO_FA_RESEND_THROTTLE_PREFIX: &str = "two_fa_resend_throttle:";
+const TWO_FA_INDEX_PREFIX: &str = "two_fa_codes:";
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, login_attempt_id)
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_attempts_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_ATTEMPTS_PREFIX, login_attempt_id)
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_resends_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_RESENDS_PREFIX, login_attempt_id)
+}
+
+#[tracing::instrument(name = "Building key format for redis", skip_all)]
+fn get_resend_throttle_key(login_attempt_id: &str) -> String {
+    format!("{}{}", TWO_FA_RESEND_THROTTLE_PREFIX, login_attempt_id)
+}
 
 #[tracing::instrument(name = "Building key format for redis", skip_all)]
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
+fn get_index_key(user_id: &UserId) -> String {
+    format!("{}{}", TWO_FA_INDEX_PREFIX, user_id)
 }
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::app_state::UserStoreType;
+
+use super::constants::{ACCOUNT_DELETION_GRACE_PERIOD_DAYS, ACCOUNT_PURGE_INTERVAL_SECONDS};
+
+/// Periodically deletes accounts whose grace period has run out. Meant to be
+/// spawned once at startup; it never returns.
+pub async fn purge_deleted_accounts(user_store: UserStoreType) {
+    let grace_period = chrono::Duration::days(*ACCOUNT_DELETION_GRACE_PERIOD_DAYS as i64);
+    let mut interval = tokio::time::interval(Duration::from_secs(ACCOUNT_PURGE_INTERVAL_SECONDS));
+
+    loop {
+        interval.tick().await;
+
+        let deleted_before = Utc::now() - grace_period;
+
+        let purged = user_store
+            .write()
+            .await
+            .purge_deleted_users(deleted_before)
+            .await;
+
+        match purged {
+            Ok(0) => (),
+            Ok(count) => tracing::info!("Purged {} deleted accounts", count),
+            Err(e) => tracing::error!("Failed to purge deleted accounts: {:?}", e),
+        }
+    }
+}
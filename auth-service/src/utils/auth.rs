@@ -1,15 +1,54 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
-use secrecy::ExposeSecret;
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use lazy_static::lazy_static;
+use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
 
-use crate::domain::Email;
+use crate::{
+    app_state::{AppState, BannedTokenStoreType, SessionStoreType},
+    domain::{
+        AuthAPIError, RefreshToken, SessionId, SessionStoreError, User, UserId, UserStoreError,
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{JWT_AUDIENCE, JWT_COOKIE_NAME, JWT_ISSUER, REFRESH_COOKIE_NAME},
+    jwt_keyring::JwtKeyring,
+};
 
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 30 * 24 * 60 * 60; // 30 days
+
+lazy_static! {
+    static ref JWT_KEYRING: RwLock<Arc<JwtKeyring>> = RwLock::new(Arc::new(
+        JwtKeyring::from_config().expect("invalid JWT keyring configuration")
+    ));
+}
+
+/// Keyring currently in use. Callers hold on to the snapshot, so a reload
+/// never swaps keys halfway through signing or validating a token.
+pub fn jwt_keyring() -> Arc<JwtKeyring> {
+    JWT_KEYRING
+        .read()
+        .expect("JWT keyring lock poisoned")
+        .clone()
+}
+
+/// Re-reads the keyring configuration, e.g. after a new key was added or an
+/// old one retired. The keyring in use is kept if the new one fails to load.
+#[tracing::instrument(name = "Reload JWT keyring", skip_all)]
+pub fn reload_jwt_keyring() -> Result<()> {
+    let keyring = JwtKeyring::from_config()?;
+    *JWT_KEYRING.write().expect("JWT keyring lock poisoned") = Arc::new(keyring);
+    Ok(())
+}
 
 #[derive(Debug)]
 pub enum GenerateTokenError {
@@ -18,23 +57,31 @@ pub enum GenerateTokenError {
 }
 
 #[tracing::instrument(name = "Generate Auth Cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(user_id: &UserId, session_id: &SessionId) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(user_id, session_id)?;
     Ok(create_auth_cookie(token))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
-    sub: String,
-    exp: usize,
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub nbf: usize,
+    pub jti: String,
+    pub iss: String,
+    pub aud: String,
+    pub sid: String,
 }
 
 #[tracing::instrument(name = "Generate Auth Token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String> {
+fn generate_auth_token(user_id: &UserId, session_id: &SessionId) -> Result<String> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .wrap_err("failed to create 10 minute time delta")?;
 
-    let exp = Utc::now()
+    let now = Utc::now();
+
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(eyre!("failed to add 10 minutes to current time"))?
         .timestamp();
@@ -44,21 +91,34 @@ fn generate_auth_token(email: &Email) -> Result<String> {
         exp
     ))?;
 
-    let sub = email.as_ref().expose_secret().to_owned();
+    let iat: usize = now.timestamp().try_into().wrap_err(format!(
+        "failed to convert issue time to usize. iat time: {}",
+        now.timestamp()
+    ))?;
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp,
+        iat,
+        nbf: iat,
+        jti: Uuid::new_v4().to_string(),
+        iss: JWT_ISSUER.to_owned(),
+        aud: JWT_AUDIENCE.to_owned(),
+        sid: session_id.to_string(),
+    };
 
     create_token(&claims)
 }
 
 #[tracing::instrument(name = "Create Token", skip_all)]
 fn create_token(claims: &Claims) -> Result<String> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-    )
-    .wrap_err("failed to create token")
+    let keyring = jwt_keyring();
+    let key = keyring.signing_key();
+
+    let mut header = Header::new(key.algorithm());
+    header.kid = key.kid().map(str::to_owned);
+
+    encode(&header, &claims, key.encoding_key()).wrap_err("failed to create token")
 }
 
 #[tracing::instrument(name = "Create Auth Cookie", skip_all)]
@@ -71,27 +131,123 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
-#[tracing::instrument(name = "Validate Token", skip(token))]
-pub async fn validate_token(token: &str) -> Result<Claims> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+#[tracing::instrument(name = "Create Refresh Cookie", skip_all)]
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    let cookie = Cookie::build((
+        REFRESH_COOKIE_NAME,
+        token.as_ref().expose_secret().to_owned(),
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .max_age(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS))
+    .build();
+    cookie
+}
+
+/// The one place auth tokens are checked: signature, expiry, issuer, audience,
+/// whether the token was revoked at logout and whether its session is still
+/// active. Forged and expired tokens are rejected before any store is hit.
+#[tracing::instrument(name = "Validate Token", skip_all)]
+pub async fn validate_token(
+    token: &Secret<String>,
+    banned_token_store: &BannedTokenStoreType,
+    session_store: &SessionStoreType,
+) -> Result<Claims, AuthAPIError> {
+    let claims = decode_token(token.expose_secret()).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let session_id = SessionId::parse(&claims.sid).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let is_banned = banned_token_store
+        .read()
+        .await
+        .is_banned_token(&claims.jti)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if is_banned {
+        return Err(AuthAPIError::RevokedToken);
+    }
+
+    let session = match session_store.read().await.get_session(&session_id).await {
+        Ok(session) => session,
+        Err(SessionStoreError::SessionNotFound) => return Err(AuthAPIError::RevokedToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if session.user_id.to_string() != claims.sub {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(claims)
+}
+
+#[tracing::instrument(name = "Decode Token", skip(token))]
+fn decode_token(token: &str) -> Result<Claims> {
+    let header = decode_header(token).wrap_err("failed to decode token header")?;
+
+    let keyring = jwt_keyring();
+    let key = keyring
+        .verification_key(header.kid.as_deref())
+        .ok_or(eyre!("unknown key id {:?}", header.kid))?;
+
+    let mut validation = Validation::new(key.algorithm());
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(&[JWT_AUDIENCE.as_str()]);
+    validation.set_required_spec_claims(&["exp", "nbf", "sub", "iss", "aud"]);
+    validation.validate_nbf = true;
+
+    decode::<Claims>(token, key.decoding_key(), &validation)
+        .map(|data| data.claims)
+        .wrap_err("failed to decode token")
+}
+
+/// Resolves the user behind the request's JWT cookie.
+#[tracing::instrument(name = "Authenticate", skip_all)]
+pub async fn authenticate(jar: &CookieJar, state: &AppState) -> Result<User, AuthAPIError> {
+    authenticate_session(jar, state).await.map(|(user, _)| user)
+}
+
+/// Like [`authenticate`], also returning the session the cookie belongs to.
+#[tracing::instrument(name = "Authenticate session", skip_all)]
+pub async fn authenticate_session(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<(User, SessionId), AuthAPIError> {
+    let token = match jar.get(JWT_COOKIE_NAME) {
+        Some(cookie) => Secret::new(cookie.value().to_owned()),
+        None => return Err(AuthAPIError::MissingToken),
+    };
+
+    let claims = validate_token(&token, &state.banned_token_store, &state.session_store).await?;
+
+    let user_id = UserId::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    let session_id = SessionId::parse(&claims.sid).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let user = match state.user_store.read().await.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    Ok((user, session_id))
 }
 
 #[cfg(test)]
 mod tests {
     use secrecy::Secret;
 
+    use crate::{
+        domain::{Session, SessionStore},
+        services::{HashmapSessionStore, HashsetBannedTokenStore},
+    };
+
     use super::*;
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let user_id = UserId::default();
+        let cookie = generate_auth_cookie(&user_id, &SessionId::default()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -110,19 +266,61 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_create_refresh_cookie() {
+        let token = RefreshToken::default();
+        let cookie = create_refresh_cookie(&token);
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.value(), token.as_ref().expose_secret().as_str());
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let user_id = UserId::default();
+        let result = generate_auth_token(&user_id, &SessionId::default()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
+    struct Stores {
+        banned_token_store: BannedTokenStoreType,
+        session_store: SessionStoreType,
+        user_id: UserId,
+        session_id: SessionId,
+    }
+
+    /// Stores holding one active session for a single user.
+    async fn stores() -> Stores {
+        let user_id = UserId::default();
+        let session = Session::new(user_id, None, None);
+        let session_id = session.id;
+
+        let mut session_store = HashmapSessionStore::default();
+        session_store.add_session(session).await.unwrap();
+
+        Stores {
+            banned_token_store: Arc::new(tokio::sync::RwLock::new(
+                HashsetBannedTokenStore::default(),
+            )),
+            session_store: Arc::new(tokio::sync::RwLock::new(session_store)),
+            user_id,
+            session_id,
+        }
+    }
+
+    async fn validate(token: &Secret<String>, stores: &Stores) -> Result<Claims, AuthAPIError> {
+        validate_token(token, &stores.banned_token_store, &stores.session_store).await
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let result = validate_token(&token).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        let stores = stores().await;
+        let token = Secret::new(generate_auth_token(&stores.user_id, &stores.session_id).unwrap());
+        let result = validate(&token, &stores).await.unwrap();
+        assert_eq!(result.sub, stores.user_id.to_string());
+        assert_eq!(result.sid, stores.session_id.to_string());
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -130,12 +328,95 @@ mod tests {
             .timestamp();
 
         assert!(result.exp > exp as usize);
+        assert_eq!(result.iss, JWT_ISSUER.as_str());
+        assert_eq!(result.aud, JWT_AUDIENCE.as_str());
+        assert!(result.iat <= result.nbf && result.nbf < result.exp);
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_has_unique_jti() {
+        let user_id = UserId::default();
+        let session_id = SessionId::default();
+        let first = decode_token(&generate_auth_token(&user_id, &session_id).unwrap()).unwrap();
+        let second = decode_token(&generate_auth_token(&user_id, &session_id).unwrap()).unwrap();
+        assert_ne!(first.jti, second.jti);
+    }
+
+    fn claims_for(stores: &Stores, iss: &str, aud: &str, nbf_offset: i64) -> Claims {
+        let now = Utc::now().timestamp();
+        Claims {
+            sub: stores.user_id.to_string(),
+            exp: (now + TOKEN_TTL_SECONDS) as usize,
+            iat: now as usize,
+            nbf: (now + nbf_offset) as usize,
+            jti: Uuid::new_v4().to_string(),
+            iss: iss.to_owned(),
+            aud: aud.to_owned(),
+            sid: stores.session_id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_issuer_or_audience() {
+        let stores = stores().await;
+        let wrong_issuer = claims_for(&stores, "other-issuer", &JWT_AUDIENCE, 0);
+        let wrong_audience = claims_for(&stores, &JWT_ISSUER, "other-audience", 0);
+
+        for claims in [wrong_issuer, wrong_audience] {
+            let token = Secret::new(create_token(&claims).unwrap());
+            let result = validate(&token, &stores).await;
+            assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_not_yet_valid() {
+        let stores = stores().await;
+        let claims = claims_for(&stores, &JWT_ISSUER, &JWT_AUDIENCE, 5 * 60);
+        let token = Secret::new(create_token(&claims).unwrap());
+        let result = validate(&token, &stores).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
     }
 
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
-        let token = "invalid token".to_owned();
-        let result = validate_token(&token).await;
-        assert!(result.is_err());
+        let token = Secret::new("invalid token".to_owned());
+        let result = validate(&token, &stores().await).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_banned_token() {
+        let stores = stores().await;
+        let token = Secret::new(generate_auth_token(&stores.user_id, &stores.session_id).unwrap());
+
+        let jti = decode_token(token.expose_secret()).unwrap().jti;
+        stores
+            .banned_token_store
+            .write()
+            .await
+            .add_banned_token(&jti)
+            .await
+            .unwrap();
+
+        let result = validate(&token, &stores).await;
+        assert!(matches!(result, Err(AuthAPIError::RevokedToken)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_session() {
+        let stores = stores().await;
+        let token = Secret::new(generate_auth_token(&stores.user_id, &stores.session_id).unwrap());
+
+        stores
+            .session_store
+            .write()
+            .await
+            .remove_session(&stores.user_id, &stores.session_id)
+            .await
+            .unwrap();
+
+        let result = validate(&token, &stores).await;
+        assert!(matches!(result, Err(AuthAPIError::RevokedToken)));
     }
 }
@@ -1,13 +1,68 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use secrecy::Secret;
-use std::env as std_env;
+use std::{env as std_env, net::IpAddr, str::FromStr};
 
 lazy_static! {
     pub static ref JWT_SECRET: Secret<String> = set_token();
     pub static ref DATABASE_URL: Secret<String> = set_postgres_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = set_postmark_auth_token();
+    pub static ref AUTH_SERVICE_URL: String = set_auth_service_url();
+    pub static ref REQUIRE_EMAIL_VERIFICATION: bool = set_require_email_verification();
+    pub static ref MAGIC_LINK_COUNTS_AS_FACTOR: bool = set_magic_link_counts_as_factor();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref WEBAUTHN_ORIGIN: String = set_webauthn_origin();
+    pub static ref TOTP_ENCRYPTION_KEY: Secret<String> = set_totp_encryption_key();
+    pub static ref JWT_ALGORITHM: String = set_jwt_algorithm();
+    pub static ref JWT_PRIVATE_KEY_PATH: Option<String> = set_jwt_private_key_path();
+    pub static ref JWT_KEY_ID: Option<String> = set_jwt_key_id();
+    pub static ref JWT_KEYRING_PATH: Option<String> = set_jwt_keyring_path();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCE: String = set_jwt_audience();
+    pub static ref ACCOUNT_DELETION_GRACE_PERIOD_DAYS: u64 = set_account_deletion_grace_period();
+    pub static ref LOGIN_LOCKOUT_THRESHOLD: u32 = set_login_lockout_threshold(
+        env::LOGIN_LOCKOUT_THRESHOLD_ENV_VAR,
+        DEFAULT_LOGIN_LOCKOUT_THRESHOLD
+    );
+    pub static ref TRUSTED_PROXIES: Vec<IpAddr> = set_trusted_proxies();
+    pub static ref LOGIN_IP_LOCKOUT_THRESHOLD: u32 = set_login_lockout_threshold(
+        env::LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR,
+        DEFAULT_LOGIN_IP_LOCKOUT_THRESHOLD
+    );
+    pub static ref ARGON2_MEMORY_KIB: u32 =
+        set_argon2_param(env::ARGON2_MEMORY_KIB_ENV_VAR, DEFAULT_ARGON2_MEMORY_KIB);
+    pub static ref ARGON2_ITERATIONS: u32 =
+        set_argon2_param(env::ARGON2_ITERATIONS_ENV_VAR, DEFAULT_ARGON2_ITERATIONS);
+    pub static ref ARGON2_PARALLELISM: u32 =
+        set_argon2_param(env::ARGON2_PARALLELISM_ENV_VAR, DEFAULT_ARGON2_PARALLELISM);
+    pub static ref PASSWORD_PEPPER: Option<Secret<String>> = set_password_pepper();
+    pub static ref PASSWORD_PEPPER_VERSION: i32 = set_password_pepper_version();
+    pub static ref PASSWORD_PREVIOUS_PEPPERS: Vec<(i32, Secret<String>)> =
+        set_password_previous_peppers();
+    pub static ref PASSWORD_MIN_LENGTH: usize = set_password_policy_param(
+        env::PASSWORD_MIN_LENGTH_ENV_VAR,
+        DEFAULT_PASSWORD_MIN_LENGTH
+    );
+    pub static ref PASSWORD_MAX_LENGTH: usize = set_password_policy_param(
+        env::PASSWORD_MAX_LENGTH_ENV_VAR,
+        DEFAULT_PASSWORD_MAX_LENGTH
+    );
+    pub static ref PASSWORD_REQUIRE_LOWERCASE: bool =
+        set_password_policy_flag(env::PASSWORD_REQUIRE_LOWERCASE_ENV_VAR, false);
+    pub static ref PASSWORD_REQUIRE_UPPERCASE: bool =
+        set_password_policy_flag(env::PASSWORD_REQUIRE_UPPERCASE_ENV_VAR, false);
+    pub static ref PASSWORD_REQUIRE_DIGIT: bool =
+        set_password_policy_flag(env::PASSWORD_REQUIRE_DIGIT_ENV_VAR, false);
+    pub static ref PASSWORD_REQUIRE_SYMBOL: bool =
+        set_password_policy_flag(env::PASSWORD_REQUIRE_SYMBOL_ENV_VAR, false);
+    pub static ref PASSWORD_MIN_STRENGTH: u8 = set_password_policy_param(
+        env::PASSWORD_MIN_STRENGTH_ENV_VAR,
+        DEFAULT_PASSWORD_MIN_STRENGTH
+    );
+    pub static ref PASSWORD_REJECT_EMAIL: bool =
+        set_password_policy_flag(env::PASSWORD_REJECT_EMAIL_ENV_VAR, true);
+    pub static ref BREACHED_PASSWORDS_PATH: Option<String> = set_breached_passwords_path();
 }
 
 fn set_token() -> Secret<String> {
@@ -38,20 +93,273 @@ fn set_postmark_auth_token() -> Secret<String> {
     Secret::new(secret)
 }
 
+fn set_totp_encryption_key() -> Secret<String> {
+    dotenv().ok();
+    let secret =
+        std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set");
+    if secret.is_empty() {
+        panic!("TOTP_ENCRYPTION_KEY must not be empty");
+    }
+    Secret::new(secret)
+}
+
+fn set_password_pepper() -> Option<Secret<String>> {
+    dotenv().ok();
+    std_env::var(env::PASSWORD_PEPPER_ENV_VAR)
+        .ok()
+        .filter(|pepper| !pepper.is_empty())
+        .map(Secret::new)
+}
+
+fn set_password_pepper_version() -> i32 {
+    dotenv().ok();
+    match std_env::var(env::PASSWORD_PEPPER_VERSION_ENV_VAR) {
+        Ok(version) if !version.is_empty() => version
+            .parse()
+            .expect("PASSWORD_PEPPER_VERSION must be a number"),
+        _ => DEFAULT_PASSWORD_PEPPER_VERSION,
+    }
+}
+
+/// Peppers that were current before, as `version:pepper` pairs separated by
+/// commas. Hashes made with them still verify until they are upgraded.
+fn set_password_previous_peppers() -> Vec<(i32, Secret<String>)> {
+    dotenv().ok();
+    let peppers = std_env::var(env::PASSWORD_PREVIOUS_PEPPERS_ENV_VAR).unwrap_or_default();
+
+    peppers
+        .split(',')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (version, pepper) = entry
+                .split_once(':')
+                .filter(|(_, pepper)| !pepper.is_empty())
+                .expect("PASSWORD_PREVIOUS_PEPPERS entries must look like version:pepper");
+            let version = version
+                .parse()
+                .expect("PASSWORD_PREVIOUS_PEPPERS versions must be numbers");
+
+            (version, Secret::new(pepper.to_owned()))
+        })
+        .collect()
+}
+
+fn set_password_policy_param<T: FromStr>(env_var: &str, default: T) -> T {
+    dotenv().ok();
+    std_env::var(env_var)
+        .ok()
+        .and_then(|param| param.parse().ok())
+        .unwrap_or(default)
+}
+
+fn set_password_policy_flag(env_var: &str, default: bool) -> bool {
+    dotenv().ok();
+    match std_env::var(env_var).map(|value| value.to_lowercase()) {
+        Ok(value) if matches!(value.as_str(), "true" | "1" | "yes") => true,
+        Ok(value) if matches!(value.as_str(), "false" | "0" | "no") => false,
+        _ => default,
+    }
+}
+
+fn set_breached_passwords_path() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::BREACHED_PASSWORDS_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn set_jwt_algorithm() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ALGORITHM_ENV_VAR).unwrap_or(DEFAULT_JWT_ALGORITHM.to_owned())
+}
+
+fn set_jwt_private_key_path() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_PRIVATE_KEY_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn set_jwt_key_id() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_KEY_ID_ENV_VAR)
+        .ok()
+        .filter(|kid| !kid.is_empty())
+}
+
+fn set_jwt_keyring_path() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_KEYRING_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR).unwrap_or(DEFAULT_JWT_ISSUER.to_owned())
+}
+
+fn set_jwt_audience() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_AUDIENCE_ENV_VAR).unwrap_or(DEFAULT_JWT_AUDIENCE.to_owned())
+}
+
+fn set_account_deletion_grace_period() -> u64 {
+    dotenv().ok();
+    std_env::var(env::ACCOUNT_DELETION_GRACE_PERIOD_DAYS_ENV_VAR)
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_ACCOUNT_DELETION_GRACE_PERIOD_DAYS)
+}
+
+fn set_login_lockout_threshold(env_var: &str, default: u32) -> u32 {
+    dotenv().ok();
+    std_env::var(env_var)
+        .ok()
+        .and_then(|threshold| threshold.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Addresses of the reverse proxies in front of the service, separated by
+/// commas. `X-Forwarded-For` is only read from requests they hand over.
+fn set_trusted_proxies() -> Vec<IpAddr> {
+    dotenv().ok();
+    let proxies = std_env::var(env::TRUSTED_PROXIES_ENV_VAR).unwrap_or_default();
+
+    proxies
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse()
+                .expect("TRUSTED_PROXIES must be a comma separated list of IP addresses")
+        })
+        .collect()
+}
+
+fn set_argon2_param(env_var: &str, default: u32) -> u32 {
+    dotenv().ok();
+    std_env::var(env_var)
+        .ok()
+        .and_then(|param| param.parse().ok())
+        .unwrap_or(default)
+}
+
 fn set_redis_host() -> String {
     dotenv().ok();
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_auth_service_url() -> String {
+    dotenv().ok();
+    std_env::var(env::AUTH_SERVICE_URL_ENV_VAR).unwrap_or(DEFAULT_AUTH_SERVICE_URL.to_owned())
+}
+
+fn set_require_email_verification() -> bool {
+    dotenv().ok();
+    match std_env::var(env::REQUIRE_EMAIL_VERIFICATION_ENV_VAR) {
+        Ok(value) => !matches!(value.to_lowercase().as_str(), "false" | "0" | "no"),
+        Err(_) => true,
+    }
+}
+
+// Off by default: a magic link stands in for the password, so accounts with
+// 2FA only accept one when it's configured to count as just the first factor.
+fn set_magic_link_counts_as_factor() -> bool {
+    dotenv().ok();
+    match std_env::var(env::MAGIC_LINK_COUNTS_AS_FACTOR_ENV_VAR) {
+        Ok(value) => matches!(value.to_lowercase().as_str(), "true" | "1" | "yes"),
+        Err(_) => false,
+    }
+}
+
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+fn set_webauthn_origin() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_ORIGIN_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_ORIGIN.to_owned())
+}
+
 pub mod env {
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const REQUIRE_EMAIL_VERIFICATION_ENV_VAR: &str = "REQUIRE_EMAIL_VERIFICATION";
+    pub const MAGIC_LINK_COUNTS_AS_FACTOR_ENV_VAR: &str = "MAGIC_LINK_COUNTS_AS_FACTOR";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const PASSWORD_PEPPER_ENV_VAR: &str = "PASSWORD_PEPPER";
+    pub const PASSWORD_PEPPER_VERSION_ENV_VAR: &str = "PASSWORD_PEPPER_VERSION";
+    pub const PASSWORD_PREVIOUS_PEPPERS_ENV_VAR: &str = "PASSWORD_PREVIOUS_PEPPERS";
+    pub const PASSWORD_MIN_LENGTH_ENV_VAR: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_REQUIRE_LOWERCASE_ENV_VAR: &str = "PASSWORD_REQUIRE_LOWERCASE";
+    pub const PASSWORD_REQUIRE_UPPERCASE_ENV_VAR: &str = "PASSWORD_REQUIRE_UPPERCASE";
+    pub const PASSWORD_REQUIRE_DIGIT_ENV_VAR: &str = "PASSWORD_REQUIRE_DIGIT";
+    pub const PASSWORD_REQUIRE_SYMBOL_ENV_VAR: &str = "PASSWORD_REQUIRE_SYMBOL";
+    pub const PASSWORD_MIN_STRENGTH_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH";
+    pub const PASSWORD_REJECT_EMAIL_ENV_VAR: &str = "PASSWORD_REJECT_EMAIL";
+    pub const BREACHED_PASSWORDS_PATH_ENV_VAR: &str = "BREACHED_PASSWORDS_PATH";
+    pub const JWT_ALGORITHM_ENV_VAR: &str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_KEY_ID_ENV_VAR: &str = "JWT_KEY_ID";
+    pub const JWT_KEYRING_PATH_ENV_VAR: &str = "JWT_KEYRING_PATH";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_AUDIENCE_ENV_VAR: &str = "JWT_AUDIENCE";
+    pub const ACCOUNT_DELETION_GRACE_PERIOD_DAYS_ENV_VAR: &str =
+        "ACCOUNT_DELETION_GRACE_PERIOD_DAYS";
+    pub const LOGIN_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_LOCKOUT_THRESHOLD";
+    pub const LOGIN_IP_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_IP_LOCKOUT_THRESHOLD";
+    pub const TRUSTED_PROXIES_ENV_VAR: &str = "TRUSTED_PROXIES";
+    pub const ARGON2_MEMORY_KIB_ENV_VAR: &str = "ARGON2_MEMORY_KIB";
+    pub const ARGON2_ITERATIONS_ENV_VAR: &str = "ARGON2_ITERATIONS";
+    pub const ARGON2_PARALLELISM_ENV_VAR: &str = "ARGON2_PARALLELISM";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_AUTH_SERVICE_URL: &str = "http://localhost:3000";
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_ORIGIN: &str = "http://localhost:3000";
+pub const WEBAUTHN_RP_NAME: &str = "Auth Service";
+pub const DEFAULT_JWT_ALGORITHM: &str = "HS256";
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+pub const TWO_FA_CODE_TTL_SECONDS: u64 = 10 * 60;
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+pub const MAX_PENDING_TWO_FA_CODES: usize = 5;
+pub const TWO_FA_RESEND_INTERVAL_SECONDS: u64 = 30;
+pub const MAX_TWO_FA_RESENDS: u32 = 3;
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 15 * 60;
+pub const PASSWORD_RESET_RESEND_INTERVAL_SECONDS: u64 = 60;
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: u64 = 24 * 60 * 60;
+pub const EMAIL_VERIFICATION_RESEND_INTERVAL_SECONDS: u64 = 60;
+pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS: u64 = 60 * 60;
+pub const MAGIC_LINK_TOKEN_TTL_SECONDS: u64 = 15 * 60;
+pub const MAGIC_LINK_RESEND_INTERVAL_SECONDS: u64 = 60;
+pub const PASSKEY_CHALLENGE_TTL_SECONDS: u64 = 5 * 60;
+pub const DEFAULT_ACCOUNT_DELETION_GRACE_PERIOD_DAYS: u64 = 0;
+pub const ACCOUNT_PURGE_INTERVAL_SECONDS: u64 = 60 * 60;
+pub const DEFAULT_LOGIN_LOCKOUT_THRESHOLD: u32 = 5;
+pub const DEFAULT_LOGIN_IP_LOCKOUT_THRESHOLD: u32 = 20;
+pub const FAILED_LOGIN_WINDOW_SECONDS: u64 = 60 * 60;
+pub const LOGIN_LOCKOUT_BASE_SECONDS: u64 = 60;
+pub const LOGIN_LOCKOUT_MAX_SECONDS: u64 = 60 * 60;
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = 1500;
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+pub const DEFAULT_PASSWORD_PEPPER_VERSION: i32 = 1;
+pub const DEFAULT_PASSWORD_MIN_LENGTH: usize = 8;
+pub const DEFAULT_PASSWORD_MAX_LENGTH: usize = 128;
+pub const DEFAULT_PASSWORD_MIN_STRENGTH: u8 = 0;
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
@@ -66,10 +374,17 @@ pub mod prod {
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub const TWO_FA_RESEND_INTERVAL_SECONDS: u64 = 1;
     pub mod email_client {
         use std::time::Duration;
 
         pub const SENDER: &str = "test@email.com";
         pub const TIMEOUT: Duration = Duration::from_millis(200);
     }
+    pub mod password_pepper {
+        pub const VERSION: i32 = 2;
+        pub const PEPPER: &str = "test-pepper";
+        pub const PREVIOUS_VERSION: i32 = 1;
+        pub const PREVIOUS_PEPPER: &str = "previous-test-pepper";
+    }
 }
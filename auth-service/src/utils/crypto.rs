@@ -0,0 +1,89 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+use super::constants::TOTP_ENCRYPTION_KEY;
+
+const NONCE_LENGTH: usize = 12;
+
+/// Encrypts a secret for storage at rest. The output is the random nonce
+/// followed by the AES-256-GCM ciphertext.
+#[tracing::instrument(name = "Encrypt Secret", skip_all)]
+pub fn encrypt_secret(secret: &Secret<String>) -> Result<Vec<u8>> {
+    let cipher = cipher();
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, secret.expose_secret().as_bytes())
+        .map_err(|_| eyre!("failed to encrypt secret"))?;
+
+    let mut encrypted = nonce.to_vec();
+    encrypted.extend_from_slice(&ciphertext);
+    Ok(encrypted)
+}
+
+#[tracing::instrument(name = "Decrypt Secret", skip_all)]
+pub fn decrypt_secret(encrypted: &[u8]) -> Result<Secret<String>> {
+    if encrypted.len() < NONCE_LENGTH {
+        return Err(eyre!("encrypted secret is too short"));
+    }
+
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+
+    let plaintext = cipher()
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| eyre!("failed to decrypt secret"))?;
+
+    String::from_utf8(plaintext)
+        .map(Secret::new)
+        .wrap_err("decrypted secret is not valid UTF-8")
+}
+
+// The configured key can be any string, so it is stretched to the 256 bits
+// AES needs.
+fn cipher() -> Aes256Gcm {
+    let key = Sha256::digest(TOTP_ENCRYPTION_KEY.expose_secret().as_bytes());
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let secret = Secret::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_owned());
+        let encrypted = encrypt_secret(&secret).unwrap();
+        assert_ne!(encrypted, secret.expose_secret().as_bytes());
+
+        let decrypted = decrypt_secret(&encrypted).unwrap();
+        assert_eq!(decrypted.expose_secret(), secret.expose_secret());
+    }
+
+    #[test]
+    fn test_nonce_is_random() {
+        let secret = Secret::new("secret".to_owned());
+        assert_ne!(
+            encrypt_secret(&secret).unwrap(),
+            encrypt_secret(&secret).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tampered_ciphertext() {
+        let secret = Secret::new("secret".to_owned());
+        let mut encrypted = encrypt_secret(&secret).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0x01;
+        assert!(decrypt_secret(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_too_short() {
+        assert!(decrypt_secret(&[0u8; 4]).is_err());
+    }
+}
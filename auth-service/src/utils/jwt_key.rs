@@ -0,0 +1,268 @@
+use std::{fs, str::FromStr};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use data_encoding::BASE64URL_NOPAD;
+use ed25519_dalek::SigningKey;
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts, RsaPrivateKey,
+};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+use super::constants::{JWT_ALGORITHM, JWT_KEY_ID, JWT_PRIVATE_KEY_PATH, JWT_SECRET};
+
+/// Key used to sign and verify auth tokens. Asymmetric keys also carry the
+/// public JWK that is published for other services to verify tokens with.
+pub struct JwtKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+impl JwtKey {
+    /// Builds the key described by the `JWT_*` environment variables.
+    pub fn from_config() -> Result<Self> {
+        let algorithm = Algorithm::from_str(&JWT_ALGORITHM)
+            .map_err(|_| eyre!("Unsupported JWT algorithm {}", JWT_ALGORITHM.as_str()))?;
+
+        if algorithm == Algorithm::HS256 {
+            return Ok(Self::hs256(&JWT_SECRET, JWT_KEY_ID.clone()));
+        }
+
+        let path = JWT_PRIVATE_KEY_PATH.as_ref().ok_or(eyre!(
+            "JWT_PRIVATE_KEY_PATH must be set for {:?}",
+            algorithm
+        ))?;
+        let pem = fs::read_to_string(path)
+            .wrap_err(format!("failed to read JWT private key from {}", path))?;
+
+        Self::from_private_key_pem(algorithm, &Secret::new(pem), JWT_KEY_ID.clone())
+    }
+
+    /// Shared secret key. It has no public part, so it never appears in the
+    /// JWKS and only this service can verify the tokens it signs.
+    pub fn hs256(secret: &Secret<String>, kid: Option<String>) -> Self {
+        let secret = secret.expose_secret().as_bytes();
+
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    /// Loads an RS256 (PKCS#1 or PKCS#8) or EdDSA (PKCS#8, Ed25519) private
+    /// key. The public key is derived from it, and without an explicit `kid`
+    /// its RFC 7638 thumbprint is used.
+    pub fn from_private_key_pem(
+        algorithm: Algorithm,
+        pem: &Secret<String>,
+        kid: Option<String>,
+    ) -> Result<Self> {
+        let pem = pem.expose_secret();
+
+        let (encoding_key, parameters, thumbprint_input) = match algorithm {
+            Algorithm::RS256 => {
+                let private_key = RsaPrivateKey::from_pkcs8_pem(pem)
+                    .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+                    .wrap_err("failed to parse RSA private key")?;
+                let n = BASE64URL_NOPAD.encode(&private_key.n().to_bytes_be());
+                let e = BASE64URL_NOPAD.encode(&private_key.e().to_bytes_be());
+
+                (
+                    EncodingKey::from_rsa_pem(pem.as_bytes())
+                        .wrap_err("failed to create RSA encoding key")?,
+                    AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: n.clone(),
+                        e: e.clone(),
+                    }),
+                    format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n),
+                )
+            }
+            Algorithm::EdDSA => {
+                let private_key = SigningKey::from_pkcs8_pem(pem)
+                    .map_err(|e| eyre!("failed to parse Ed25519 private key: {}", e))?;
+                let x = BASE64URL_NOPAD.encode(private_key.verifying_key().as_bytes());
+
+                (
+                    EncodingKey::from_ed_pem(pem.as_bytes())
+                        .wrap_err("failed to create Ed25519 encoding key")?,
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x: x.clone(),
+                    }),
+                    format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x),
+                )
+            }
+            _ => return Err(eyre!("Unsupported JWT algorithm {:?}", algorithm)),
+        };
+
+        let kid = kid.unwrap_or_else(|| {
+            BASE64URL_NOPAD.encode(&Sha256::digest(thumbprint_input.as_bytes()))
+        });
+
+        let key_algorithm = match algorithm {
+            Algorithm::RS256 => KeyAlgorithm::RS256,
+            _ => KeyAlgorithm::EdDSA,
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+
+        let decoding_key = DecodingKey::from_jwk(&jwk).wrap_err("failed to create decoding key")?;
+
+        Ok(Self {
+            kid: Some(kid),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    /// Public key in JWK form, `None` for HS256.
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, encode, Header, Validation};
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    const RS256_PEM: &str = include_str!("../../tests/fixtures/jwt_rs256.pem");
+    const ED25519_PEM: &str = include_str!("../../tests/fixtures/jwt_ed25519.pem");
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn round_trip(key: &JwtKey) {
+        let claims = TestClaims {
+            sub: "test@example.com".to_owned(),
+            exp: 10_000_000_000,
+        };
+
+        let mut header = Header::new(key.algorithm());
+        header.kid = key.kid().map(str::to_owned);
+
+        let token = encode(&header, &claims, key.encoding_key()).unwrap();
+
+        // Verify with the published JWK, as a downstream service would ///////
+        let decoding_key = DecodingKey::from_jwk(key.jwk().unwrap()).unwrap();
+        let decoded =
+            decode::<TestClaims>(&token, &decoding_key, &Validation::new(key.algorithm()))
+                .unwrap()
+                .claims;
+
+        assert_eq!(decoded, claims);
+    }
+
+    #[test]
+    fn test_rs256_key() {
+        let key = JwtKey::from_private_key_pem(
+            Algorithm::RS256,
+            &Secret::new(RS256_PEM.to_owned()),
+            None,
+        )
+        .unwrap();
+
+        assert!(key.kid().is_some());
+        round_trip(&key);
+    }
+
+    #[test]
+    fn test_ed25519_key() {
+        let key = JwtKey::from_private_key_pem(
+            Algorithm::EdDSA,
+            &Secret::new(ED25519_PEM.to_owned()),
+            None,
+        )
+        .unwrap();
+
+        assert!(key.kid().is_some());
+        round_trip(&key);
+    }
+
+    #[test]
+    fn test_explicit_kid() {
+        let key = JwtKey::from_private_key_pem(
+            Algorithm::EdDSA,
+            &Secret::new(ED25519_PEM.to_owned()),
+            Some("key-1".to_owned()),
+        )
+        .unwrap();
+
+        assert_eq!(key.kid(), Some("key-1"));
+        assert_eq!(key.jwk().unwrap().common.key_id.as_deref(), Some("key-1"));
+    }
+
+    #[test]
+    fn test_kid_is_stable() {
+        let pem = Secret::new(RS256_PEM.to_owned());
+        let first = JwtKey::from_private_key_pem(Algorithm::RS256, &pem, None).unwrap();
+        let second = JwtKey::from_private_key_pem(Algorithm::RS256, &pem, None).unwrap();
+
+        assert_eq!(first.kid(), second.kid());
+    }
+
+    #[test]
+    fn test_mismatched_algorithm() {
+        assert!(JwtKey::from_private_key_pem(
+            Algorithm::RS256,
+            &Secret::new(ED25519_PEM.to_owned()),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_hs256_key_has_no_jwk() {
+        let key = JwtKey::hs256(&Secret::new("secret".to_owned()), None);
+
+        assert_eq!(key.algorithm(), Algorithm::HS256);
+        assert!(key.kid().is_none());
+        assert!(key.jwk().is_none());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::{jwk::JwkSet, Algorithm};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use super::{constants::JWT_KEYRING_PATH, jwt_key::JwtKey};
+
+/// The key tokens are signed with, plus the keys of earlier rotations that
+/// are still accepted for verification. Keys are told apart by `kid`; a key
+/// is retired by dropping it from the keyring once its tokens have expired.
+pub struct JwtKeyring {
+    current: JwtKey,
+    previous: Vec<JwtKey>,
+}
+
+impl JwtKeyring {
+    pub fn new(current: JwtKey, previous: Vec<JwtKey>) -> Result<Self> {
+        let mut kids = HashSet::new();
+
+        for key in std::iter::once(&current).chain(previous.iter()) {
+            match key.kid() {
+                Some(kid) if !kids.insert(kid) => {
+                    return Err(eyre!("duplicate JWT key id {}", kid))
+                }
+                Some(_) => (),
+                None if !previous.is_empty() => {
+                    return Err(eyre!("every JWT key needs a key id when rotating keys"))
+                }
+                None => (),
+            }
+        }
+
+        Ok(Self { current, previous })
+    }
+
+    /// Loads the keyring file at `JWT_KEYRING_PATH`, or falls back to the
+    /// single key described by the other `JWT_*` environment variables.
+    pub fn from_config() -> Result<Self> {
+        match JWT_KEYRING_PATH.as_ref() {
+            Some(path) => Self::from_file(path),
+            None => Self::new(JwtKey::from_config()?, Vec::new()),
+        }
+    }
+
+    /// Reads a keyring file such as
+    ///
+    /// ```json
+    /// {
+    ///   "current": "2024-11",
+    ///   "keys": [
+    ///     { "kid": "2024-11", "algorithm": "EdDSA", "path": "2024-11.pem" },
+    ///     { "kid": "2024-10", "algorithm": "HS256", "path": "2024-10.secret" }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// `path` points at a PEM private key, or at the shared secret for HS256,
+    /// and is resolved relative to the keyring file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).wrap_err(format!(
+            "failed to read JWT keyring from {}",
+            path.display()
+        ))?;
+        let file: KeyringFile =
+            serde_json::from_str(&contents).wrap_err("failed to parse JWT keyring")?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut current = None;
+        let mut previous = Vec::new();
+
+        for entry in file.keys {
+            let key = entry.load(base_dir)?;
+            if entry.kid == file.current {
+                current = Some(key);
+            } else {
+                previous.push(key);
+            }
+        }
+
+        let current = current.ok_or(eyre!(
+            "current JWT key {} is not in the keyring",
+            file.current
+        ))?;
+
+        Self::new(current, previous)
+    }
+
+    /// Key new tokens are signed with.
+    pub fn signing_key(&self) -> &JwtKey {
+        &self.current
+    }
+
+    /// Key to verify a token with, picked by the `kid` in its header. Tokens
+    /// without a `kid` predate key ids and can only match the current key.
+    pub fn verification_key(&self, kid: Option<&str>) -> Option<&JwtKey> {
+        match kid {
+            Some(kid) => std::iter::once(&self.current)
+                .chain(self.previous.iter())
+                .find(|key| key.kid() == Some(kid)),
+            None => Some(&self.current),
+        }
+    }
+
+    /// Public keys of every key still accepted, so tokens signed before a
+    /// rotation keep verifying downstream.
+    pub fn jwks(&self) -> JwkSet {
+        let keys = std::iter::once(&self.current)
+            .chain(self.previous.iter())
+            .filter_map(|key| key.jwk().cloned())
+            .collect();
+
+        JwkSet { keys }
+    }
+}
+
+#[derive(Deserialize)]
+struct KeyringFile {
+    current: String,
+    keys: Vec<KeyringEntry>,
+}
+
+#[derive(Deserialize)]
+struct KeyringEntry {
+    kid: String,
+    algorithm: String,
+    path: PathBuf,
+}
+
+impl KeyringEntry {
+    fn load(&self, base_dir: &Path) -> Result<JwtKey> {
+        let algorithm = Algorithm::from_str(&self.algorithm)
+            .map_err(|_| eyre!("Unsupported JWT algorithm {}", self.algorithm))?;
+
+        let path = base_dir.join(&self.path);
+        let contents = fs::read_to_string(&path)
+            .wrap_err(format!("failed to read JWT key from {}", path.display()))?;
+
+        if algorithm == Algorithm::HS256 {
+            let secret = contents.trim().to_owned();
+            if secret.is_empty() {
+                return Err(eyre!("JWT secret {} must not be empty", self.kid));
+            }
+            return Ok(JwtKey::hs256(&Secret::new(secret), Some(self.kid.clone())));
+        }
+
+        JwtKey::from_private_key_pem(algorithm, &Secret::new(contents), Some(self.kid.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RS256_PEM: &str = include_str!("../../tests/fixtures/jwt_rs256.pem");
+    const ED25519_PEM: &str = include_str!("../../tests/fixtures/jwt_ed25519.pem");
+
+    fn hs256(secret: &str, kid: &str) -> JwtKey {
+        JwtKey::hs256(&Secret::new(secret.to_owned()), Some(kid.to_owned()))
+    }
+
+    #[test]
+    fn test_verification_key_by_kid() {
+        let keyring = JwtKeyring::new(hs256("new", "new"), vec![hs256("old", "old")]).unwrap();
+
+        assert_eq!(keyring.signing_key().kid(), Some("new"));
+        assert_eq!(
+            keyring.verification_key(Some("old")).and_then(JwtKey::kid),
+            Some("old")
+        );
+        assert_eq!(
+            keyring.verification_key(None).and_then(JwtKey::kid),
+            Some("new")
+        );
+        assert!(keyring.verification_key(Some("retired")).is_none());
+    }
+
+    #[test]
+    fn test_duplicate_kid() {
+        assert!(JwtKeyring::new(hs256("new", "key"), vec![hs256("old", "key")]).is_err());
+    }
+
+    #[test]
+    fn test_previous_keys_need_kid() {
+        let current = JwtKey::hs256(&Secret::new("new".to_owned()), None);
+
+        assert!(JwtKeyring::new(current, vec![hs256("old", "old")]).is_err());
+    }
+
+    #[test]
+    fn test_jwks_lists_every_public_key() {
+        let current = JwtKey::from_private_key_pem(
+            Algorithm::EdDSA,
+            &Secret::new(ED25519_PEM.to_owned()),
+            Some("ed".to_owned()),
+        )
+        .unwrap();
+        let previous = vec![
+            JwtKey::from_private_key_pem(
+                Algorithm::RS256,
+                &Secret::new(RS256_PEM.to_owned()),
+                Some("rsa".to_owned()),
+            )
+            .unwrap(),
+            hs256("old", "hs"),
+        ];
+
+        let jwks = JwtKeyring::new(current, previous).unwrap().jwks();
+
+        let kids: Vec<_> = jwks
+            .keys
+            .iter()
+            .map(|jwk| jwk.common.key_id.clone().unwrap())
+            .collect();
+        assert_eq!(kids, vec!["ed", "rsa"]);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = std::env::temp_dir().join(format!("jwt-keyring-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ed.pem"), ED25519_PEM).unwrap();
+        fs::write(dir.join("old.secret"), "old-secret\n").unwrap();
+        fs::write(
+            dir.join("keyring.json"),
+            r#"{
+                "current": "ed",
+                "keys": [
+                    { "kid": "ed", "algorithm": "EdDSA", "path": "ed.pem" },
+                    { "kid": "old", "algorithm": "HS256", "path": "old.secret" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let keyring = JwtKeyring::from_file(dir.join("keyring.json")).unwrap();
+
+        assert_eq!(keyring.signing_key().kid(), Some("ed"));
+        assert_eq!(keyring.signing_key().algorithm(), Algorithm::EdDSA);
+        assert_eq!(
+            keyring.verification_key(Some("old")).map(JwtKey::algorithm),
+            Some(Algorithm::HS256)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_from_file_missing_current_key() {
+        let dir = std::env::temp_dir().join(format!("jwt-keyring-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old.secret"), "old-secret").unwrap();
+        fs::write(
+            dir.join("keyring.json"),
+            r#"{
+                "current": "new",
+                "keys": [{ "kid": "old", "algorithm": "HS256", "path": "old.secret" }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(JwtKeyring::from_file(dir.join("keyring.json")).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,10 @@
+pub mod account_purge;
 pub mod auth;
 pub mod constants;
+pub mod crypto;
+pub mod jwt_key;
+pub mod jwt_keyring;
+pub mod password_hashing;
+pub mod password_policy;
 pub mod tracing;
+pub mod webauthn;
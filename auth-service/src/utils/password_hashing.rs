@@ -0,0 +1,311 @@
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher, Version,
+};
+use color_eyre::eyre::{eyre, Context, Result};
+use data_encoding::BASE64;
+use hmac::{Hmac, Mac};
+use pbkdf2::Pbkdf2;
+use scrypt::Scrypt;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use tokio::task;
+
+use super::constants::{
+    ARGON2_ITERATIONS, ARGON2_MEMORY_KIB, ARGON2_PARALLELISM, PASSWORD_PEPPER,
+    PASSWORD_PEPPER_VERSION, PASSWORD_PREVIOUS_PEPPERS,
+};
+
+const BCRYPT_PREFIXES: [&str; 3] = ["$2a$", "$2b$", "$2y$"];
+
+/// Checks a candidate against a stored hash. Besides our own Argon2 hashes
+/// this accepts bcrypt, scrypt and PBKDF2 hashes imported from older systems,
+/// which get replaced through `needs_rehash` on the next successful login.
+#[tracing::instrument(name = "Verify password hash", skip_all)]
+pub async fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<()> {
+    let current_span: tracing::Span = tracing::Span::current();
+    let hash_result = task::spawn_blocking(move || {
+        current_span.in_scope(|| {
+            let expected_password_hash = expected_password_hash.expose_secret();
+            let password_candidate = password_candidate.expose_secret().as_bytes();
+
+            if is_bcrypt(expected_password_hash) {
+                return match bcrypt::verify(password_candidate, expected_password_hash) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(eyre!("failed to verify password hash")),
+                    Err(e) => Err(e).wrap_err("failed to verify bcrypt password hash"),
+                };
+            }
+
+            // Argon2 reads the parameters from the hash, so older ones still verify
+            PasswordHash::new(expected_password_hash)?
+                .verify_password(&[&Argon2::default(), &Scrypt, &Pbkdf2], password_candidate)
+                .wrap_err("failed to verify password hash")
+        })
+    })
+    .await;
+
+    hash_result?
+}
+
+#[tracing::instrument(name = "Computing password hash", skip_all)]
+pub async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+    let current_span: tracing::Span = tracing::Span::current();
+    let compute_result = task::spawn_blocking(move || {
+        current_span.in_scope(|| Ok(Secret::new(hash_password(&password)?)))
+    })
+    .await;
+
+    compute_result?
+}
+
+/// Hashes with Argon2id and the configured parameters. Blocks for as long as
+/// the parameters make it take, so async callers go through
+/// `compute_password_hash`.
+pub fn hash_password(password: &Secret<String>) -> Result<String> {
+    let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = argon2()?
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
+
+    Ok(password_hash)
+}
+
+/// Tells whether a stored hash was made with anything other than Argon2id and
+/// the configured parameters, and should be replaced once the password is
+/// known.
+pub fn needs_rehash(password_hash: &Secret<String>) -> bool {
+    let password_hash = password_hash.expose_secret();
+
+    if is_bcrypt(password_hash) {
+        return true;
+    }
+
+    let Ok(password_hash) = PasswordHash::new(password_hash) else {
+        return true;
+    };
+
+    if password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    match Params::try_from(&password_hash) {
+        Ok(params) => {
+            params.m_cost() != *ARGON2_MEMORY_KIB
+                || params.t_cost() != *ARGON2_ITERATIONS
+                || params.p_cost() != *ARGON2_PARALLELISM
+        }
+        Err(_) => true,
+    }
+}
+
+/// The pepper new hashes are made with, if any, and the previous ones older
+/// hashes may still use, each with its version.
+#[derive(Clone, Default)]
+pub struct Peppers {
+    pub current: Option<(i32, Secret<String>)>,
+    pub previous: Vec<(i32, Secret<String>)>,
+}
+
+impl Peppers {
+    pub fn configured() -> Self {
+        Self {
+            current: PASSWORD_PEPPER
+                .clone()
+                .map(|pepper| (*PASSWORD_PEPPER_VERSION, pepper)),
+            previous: PASSWORD_PREVIOUS_PEPPERS.clone(),
+        }
+    }
+
+    pub fn current_version(&self) -> Option<i32> {
+        self.current.as_ref().map(|(version, _)| *version)
+    }
+
+    /// Looks up the pepper a stored hash was made with, current or previous.
+    pub fn get(&self, version: i32) -> Result<&Secret<String>> {
+        self.current
+            .iter()
+            .chain(self.previous.iter())
+            .find(|(pepper_version, _)| *pepper_version == version)
+            .map(|(_, pepper)| pepper)
+            .ok_or_else(|| eyre!("password pepper version {} is not configured", version))
+    }
+}
+
+/// Keys the password with the pepper before it is hashed, so a stolen hash
+/// can't be cracked without the pepper as well.
+pub fn pepper_password(
+    password: &Secret<String>,
+    pepper: &Secret<String>,
+) -> Result<Secret<String>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(pepper.expose_secret().as_bytes())
+        .wrap_err("failed to initialise pepper HMAC")?;
+    mac.update(password.expose_secret().as_bytes());
+
+    Ok(Secret::new(BASE64.encode(&mac.finalize().into_bytes())))
+}
+
+/// Checks that the configured Argon2 parameters are accepted, so a bad
+/// setting stops the service at startup instead of failing every signup.
+pub fn validate_argon2_params() -> Result<()> {
+    argon2().map(|_| ())
+}
+
+fn argon2() -> Result<Argon2<'static>> {
+    let params = Params::new(
+        *ARGON2_MEMORY_KIB,
+        *ARGON2_ITERATIONS,
+        *ARGON2_PARALLELISM,
+        None,
+    )
+    .map_err(|e| eyre!("invalid Argon2 parameters: {}", e))?;
+
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+fn is_bcrypt(password_hash: &str) -> bool {
+    BCRYPT_PREFIXES
+        .iter()
+        .any(|prefix| password_hash.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use pbkdf2::password_hash::PasswordHasher as _;
+
+    use super::*;
+
+    fn password() -> Secret<String> {
+        Secret::new("password123".to_owned())
+    }
+
+    fn argon2_hash(algorithm: Algorithm, params: Params) -> Secret<String> {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let password_hash = Argon2::new(algorithm, Version::V0x13, params)
+            .hash_password(password().expose_secret().as_bytes(), &salt)
+            .unwrap()
+            .to_string();
+
+        Secret::new(password_hash)
+    }
+
+    fn legacy_hashes() -> Vec<Secret<String>> {
+        let password = password();
+        let password = password.expose_secret().as_bytes();
+        let salt = SaltString::generate(&mut rand::thread_rng());
+
+        let bcrypt_hash = bcrypt::hash(password, 4).unwrap();
+        let scrypt_hash = Scrypt
+            .hash_password_customized(
+                password,
+                None,
+                None,
+                scrypt::Params::new(4, 8, 1, 32).unwrap(),
+                &salt,
+            )
+            .unwrap()
+            .to_string();
+        let pbkdf2_hash = Pbkdf2
+            .hash_password_customized(
+                password,
+                None,
+                None,
+                pbkdf2::Params {
+                    rounds: 1000,
+                    output_length: 32,
+                },
+                &salt,
+            )
+            .unwrap()
+            .to_string();
+
+        [bcrypt_hash, scrypt_hash, pbkdf2_hash]
+            .into_iter()
+            .map(Secret::new)
+            .collect()
+    }
+
+    #[test]
+    fn pepper_changes_password() {
+        let pepper = Secret::new("pepper".to_owned());
+        let peppered = pepper_password(&password(), &pepper).unwrap();
+
+        assert_ne!(peppered.expose_secret(), password().expose_secret());
+        assert_eq!(
+            peppered.expose_secret(),
+            pepper_password(&password(), &pepper)
+                .unwrap()
+                .expose_secret()
+        );
+        assert_ne!(
+            peppered.expose_secret(),
+            pepper_password(&password(), &Secret::new("other-pepper".to_owned()))
+                .unwrap()
+                .expose_secret()
+        );
+    }
+
+    #[tokio::test]
+    async fn verifies_current_hash() {
+        let password_hash = compute_password_hash(password()).await.unwrap();
+
+        assert!(verify_password_hash(password_hash.clone(), password())
+            .await
+            .is_ok());
+        assert!(
+            verify_password_hash(password_hash, Secret::new("password124".to_owned()))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn verifies_legacy_hashes() {
+        for password_hash in legacy_hashes() {
+            assert!(verify_password_hash(password_hash.clone(), password())
+                .await
+                .is_ok());
+            assert!(
+                verify_password_hash(password_hash, Secret::new("password124".to_owned()))
+                    .await
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn current_hash_is_up_to_date() {
+        assert!(!needs_rehash(&Secret::new(
+            hash_password(&password()).unwrap()
+        )));
+    }
+
+    #[test]
+    fn outdated_hashes_need_rehash() {
+        let weaker_params = Params::new(*ARGON2_MEMORY_KIB / 2, 1, 1, None).unwrap();
+        let current_params = Params::new(
+            *ARGON2_MEMORY_KIB,
+            *ARGON2_ITERATIONS,
+            *ARGON2_PARALLELISM,
+            None,
+        )
+        .unwrap();
+
+        assert!(needs_rehash(&argon2_hash(
+            Algorithm::Argon2id,
+            weaker_params
+        )));
+        assert!(needs_rehash(&argon2_hash(
+            Algorithm::Argon2i,
+            current_params
+        )));
+
+        for password_hash in legacy_hashes() {
+            assert!(needs_rehash(&password_hash));
+        }
+    }
+}
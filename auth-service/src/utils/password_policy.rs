@@ -0,0 +1,56 @@
+use color_eyre::eyre::Result;
+use lazy_static::lazy_static;
+use secrecy::Secret;
+
+use crate::domain::{AuthAPIError, BreachedPasswords, Email, PasswordPolicy};
+
+use super::constants::{
+    BREACHED_PASSWORDS_PATH, DEFAULT_PASSWORD_MIN_LENGTH, PASSWORD_MAX_LENGTH, PASSWORD_MIN_LENGTH,
+    PASSWORD_MIN_STRENGTH, PASSWORD_REJECT_EMAIL, PASSWORD_REQUIRE_DIGIT,
+    PASSWORD_REQUIRE_LOWERCASE, PASSWORD_REQUIRE_SYMBOL, PASSWORD_REQUIRE_UPPERCASE,
+};
+
+lazy_static! {
+    static ref PASSWORD_POLICY: PasswordPolicy =
+        load_password_policy().expect("invalid password policy configuration");
+}
+
+/// Policy new passwords are checked against, built from the configuration
+/// the first time it's needed.
+pub fn password_policy() -> &'static PasswordPolicy {
+    &PASSWORD_POLICY
+}
+
+/// Checks a password that is about to be set for the account with `email`.
+pub fn check_password_policy(password: &Secret<String>, email: &Email) -> Result<(), AuthAPIError> {
+    password_policy()
+        .check(password, email)
+        .map_err(AuthAPIError::PasswordPolicyViolation)
+}
+
+fn load_password_policy() -> Result<PasswordPolicy> {
+    let breached_passwords = match BREACHED_PASSWORDS_PATH.as_ref() {
+        Some(path) => {
+            let breached_passwords = BreachedPasswords::load(path)?;
+            tracing::info!(
+                "Loaded {} breached password digests",
+                breached_passwords.len()
+            );
+            Some(breached_passwords)
+        }
+        None => None,
+    };
+
+    Ok(PasswordPolicy {
+        // `Password::parse` never accepts anything shorter
+        min_length: (*PASSWORD_MIN_LENGTH).max(DEFAULT_PASSWORD_MIN_LENGTH),
+        max_length: *PASSWORD_MAX_LENGTH,
+        require_lowercase: *PASSWORD_REQUIRE_LOWERCASE,
+        require_uppercase: *PASSWORD_REQUIRE_UPPERCASE,
+        require_digit: *PASSWORD_REQUIRE_DIGIT,
+        require_symbol: *PASSWORD_REQUIRE_SYMBOL,
+        min_strength: *PASSWORD_MIN_STRENGTH,
+        reject_email: *PASSWORD_REJECT_EMAIL,
+        breached_passwords,
+    })
+}
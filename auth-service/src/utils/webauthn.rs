@@ -0,0 +1,480 @@
+use chrono::Utc;
+use ciborium::Value;
+use color_eyre::eyre::{eyre, Context, Result};
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::domain::{CredentialId, Passkey, PasskeyChallenge, UserId};
+
+use super::constants::{WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID};
+
+pub const CREATE_CEREMONY_TYPE: &str = "webauthn.create";
+pub const GET_CEREMONY_TYPE: &str = "webauthn.get";
+/// COSE identifier of ECDSA with P-256 and SHA-256.
+pub const ES256_ALGORITHM: i64 = -7;
+
+const USER_PRESENT: u8 = 0x01;
+const USER_VERIFIED: u8 = 0x04;
+const ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// rpIdHash, flags and signCount
+const AUTHENTICATOR_DATA_MIN_LENGTH: usize = 32 + 1 + 4;
+const AAGUID_LENGTH: usize = 16;
+
+const COSE_KEY_TYPE: i128 = 1;
+const COSE_ALGORITHM: i128 = 3;
+const COSE_EC2_CURVE: i128 = -1;
+const COSE_EC2_X: i128 = -2;
+const COSE_EC2_Y: i128 = -3;
+const COSE_KEY_TYPE_EC2: i128 = 2;
+const COSE_CURVE_P256: i128 = 1;
+
+/// The client data an authenticator response was made for. Parsing checks the
+/// ceremony type and the origin; the challenge is left for the caller to
+/// look up.
+pub struct ClientData {
+    pub challenge: PasskeyChallenge,
+    hash: [u8; 32],
+}
+
+#[derive(Deserialize)]
+struct CollectedClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+}
+
+impl ClientData {
+    pub fn parse(client_data_json: &[u8], ceremony_type: &str) -> Result<Self> {
+        let collected: CollectedClientData =
+            serde_json::from_slice(client_data_json).wrap_err("Invalid client data")?;
+
+        if collected.ceremony_type != ceremony_type {
+            return Err(eyre!("Unexpected ceremony type in client data"));
+        }
+
+        if collected.origin != WEBAUTHN_ORIGIN.as_str() {
+            return Err(eyre!("Unexpected origin in client data"));
+        }
+
+        Ok(Self {
+            challenge: PasskeyChallenge::parse(&collected.challenge)?,
+            hash: Sha256::digest(client_data_json).into(),
+        })
+    }
+}
+
+/// Checks the response to a registration challenge and returns the new
+/// passkey of `user_id`. Only ES256 keys are accepted, and only when the
+/// authenticator verified the user, since a passkey can log in on its own.
+/// The attestation statement isn't verified, since passkeys are requested
+/// without one and any authenticator is trusted.
+pub fn verify_registration(user_id: UserId, attestation_object: &[u8]) -> Result<Passkey> {
+    let attestation: Value =
+        ciborium::de::from_reader(attestation_object).wrap_err("Invalid attestation object")?;
+
+    let auth_data = attestation
+        .into_map()
+        .map_err(|_| eyre!("Invalid attestation object"))?
+        .into_iter()
+        .find(|(key, _)| key.as_text() == Some("authData"))
+        .and_then(|(_, value)| value.into_bytes().ok())
+        .ok_or(eyre!("Attestation object has no authenticator data"))?;
+
+    let auth_data = AuthenticatorData::parse(&auth_data)?;
+
+    if auth_data.flags & (USER_PRESENT | USER_VERIFIED) != USER_PRESENT | USER_VERIFIED {
+        return Err(eyre!("User was not verified by the authenticator"));
+    }
+
+    let (credential_id, public_key) = auth_data
+        .attested_credential
+        .ok_or(eyre!("Authenticator data has no attested credential"))?;
+
+    Ok(Passkey {
+        credential_id,
+        user_id,
+        public_key,
+        sign_count: auth_data.sign_count,
+        created_at: Utc::now(),
+        last_used_at: None,
+    })
+}
+
+/// Checks the response to an authentication challenge against a stored
+/// passkey and returns the sign count to store for it.
+pub fn verify_assertion(
+    passkey: &Passkey,
+    client_data: &ClientData,
+    authenticator_data: &[u8],
+    signature: &[u8],
+    require_user_verification: bool,
+) -> Result<u32> {
+    let auth_data = AuthenticatorData::parse(authenticator_data)?;
+
+    if require_user_verification && auth_data.flags & USER_VERIFIED == 0 {
+        return Err(eyre!("User was not verified by the authenticator"));
+    }
+
+    let key = VerifyingKey::from_sec1_bytes(&passkey.public_key)
+        .wrap_err("Invalid passkey public key")?;
+    let signature = Signature::from_der(signature).wrap_err("Invalid signature")?;
+
+    let mut signed_data = authenticator_data.to_vec();
+    signed_data.extend_from_slice(&client_data.hash);
+
+    key.verify(&signed_data, &signature)
+        .wrap_err("Signature does not match the passkey")?;
+
+    // A counter that doesn't move forward means the passkey may have been
+    // cloned. Authenticators without a counter always report 0.
+    if (auth_data.sign_count != 0 || passkey.sign_count != 0)
+        && auth_data.sign_count <= passkey.sign_count
+    {
+        return Err(eyre!("Sign count did not increase"));
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+struct AuthenticatorData {
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<(CredentialId, Vec<u8>)>,
+}
+
+impl AuthenticatorData {
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < AUTHENTICATOR_DATA_MIN_LENGTH {
+            return Err(eyre!("Authenticator data is too short"));
+        }
+
+        let (rp_id_hash, rest) = data.split_at(32);
+
+        if rp_id_hash != Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).as_slice() {
+            return Err(eyre!("Authenticator data is for another relying party"));
+        }
+
+        let flags = rest[0];
+        let sign_count = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]);
+
+        if flags & USER_PRESENT == 0 {
+            return Err(eyre!("User was not present"));
+        }
+
+        let attested_credential = if flags & ATTESTED_CREDENTIAL_DATA != 0 {
+            Some(parse_attested_credential(&rest[5..])?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+}
+
+fn parse_attested_credential(data: &[u8]) -> Result<(CredentialId, Vec<u8>)> {
+    let invalid = || eyre!("Invalid attested credential data");
+
+    let data = data.get(AAGUID_LENGTH..).ok_or_else(invalid)?;
+    let (length, data) = data.split_at_checked(2).ok_or_else(invalid)?;
+    let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+    let (credential_id, mut public_key) = data.split_at_checked(length).ok_or_else(invalid)?;
+
+    let credential_id = CredentialId::from_bytes(credential_id.to_vec())?;
+    // Extensions may follow the key, so only one CBOR item is read
+    let public_key: Value =
+        ciborium::de::from_reader(&mut public_key).wrap_err("Invalid credential public key")?;
+
+    Ok((credential_id, parse_cose_key(public_key)?))
+}
+
+/// Reads an ES256 COSE key into an uncompressed SEC1 point.
+fn parse_cose_key(key: Value) -> Result<Vec<u8>> {
+    let entries = key
+        .into_map()
+        .map_err(|_| eyre!("Credential public key is not a COSE key"))?;
+
+    let mut key_type = None;
+    let mut algorithm = None;
+    let mut curve = None;
+    let mut x = None;
+    let mut y = None;
+
+    for (label, value) in entries {
+        let Some(label) = label.as_integer().map(i128::from) else {
+            continue;
+        };
+
+        match label {
+            COSE_KEY_TYPE => key_type = value.as_integer().map(i128::from),
+            COSE_ALGORITHM => algorithm = value.as_integer().map(i128::from),
+            COSE_EC2_CURVE => curve = value.as_integer().map(i128::from),
+            COSE_EC2_X => x = value.into_bytes().ok(),
+            COSE_EC2_Y => y = value.into_bytes().ok(),
+            _ => (),
+        }
+    }
+
+    if key_type != Some(COSE_KEY_TYPE_EC2)
+        || algorithm != Some(ES256_ALGORITHM.into())
+        || curve != Some(COSE_CURVE_P256)
+    {
+        return Err(eyre!("Only ES256 passkeys are supported"));
+    }
+
+    let (Some(x), Some(y)) = (x, y) else {
+        return Err(eyre!("Credential public key is missing coordinates"));
+    };
+
+    let mut point = vec![0x04];
+    point.extend_from_slice(&x);
+    point.extend_from_slice(&y);
+
+    VerifyingKey::from_sec1_bytes(&point).wrap_err("Invalid credential public key")?;
+
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::{signature::Signer, SigningKey};
+
+    use super::*;
+
+    struct TestAuthenticator {
+        rp_id: String,
+        credential_id: Vec<u8>,
+        signing_key: SigningKey,
+    }
+
+    impl TestAuthenticator {
+        fn new() -> Self {
+            Self {
+                rp_id: WEBAUTHN_RP_ID.to_owned(),
+                credential_id: vec![7; 16],
+                signing_key: SigningKey::random(&mut rand::rngs::OsRng),
+            }
+        }
+
+        fn authenticator_data(&self, flags: u8, sign_count: u32) -> Vec<u8> {
+            let mut data = Sha256::digest(self.rp_id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend_from_slice(&sign_count.to_be_bytes());
+            data
+        }
+
+        fn attestation_object(&self, flags: u8) -> Vec<u8> {
+            let point = self.signing_key.verifying_key().to_encoded_point(false);
+            let cose_key = Value::Map(vec![
+                (1.into(), 2.into()),
+                (3.into(), ES256_ALGORITHM.into()),
+                ((-1).into(), 1.into()),
+                ((-2).into(), Value::Bytes(point.x().unwrap().to_vec())),
+                ((-3).into(), Value::Bytes(point.y().unwrap().to_vec())),
+            ]);
+
+            let mut auth_data = self.authenticator_data(flags | ATTESTED_CREDENTIAL_DATA, 0);
+            auth_data.extend_from_slice(&[0; AAGUID_LENGTH]);
+            auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            auth_data.extend_from_slice(&self.credential_id);
+            ciborium::ser::into_writer(&cose_key, &mut auth_data).unwrap();
+
+            let attestation = Value::Map(vec![
+                ("fmt".into(), "none".into()),
+                ("attStmt".into(), Value::Map(Vec::new())),
+                ("authData".into(), Value::Bytes(auth_data)),
+            ]);
+
+            let mut attestation_object = Vec::new();
+            ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+            attestation_object
+        }
+
+        fn sign(&self, authenticator_data: &[u8], client_data_json: &[u8]) -> Vec<u8> {
+            let mut signed_data = authenticator_data.to_vec();
+            signed_data.extend_from_slice(&Sha256::digest(client_data_json));
+            let signature: Signature = self.signing_key.sign(&signed_data);
+            signature.to_der().as_bytes().to_vec()
+        }
+    }
+
+    fn client_data_json(ceremony_type: &str, origin: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony_type,
+            "challenge": PasskeyChallenge::default().encoded(),
+            "origin": origin,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn register(authenticator: &TestAuthenticator) -> Passkey {
+        verify_registration(
+            UserId::default(),
+            &authenticator.attestation_object(USER_PRESENT | USER_VERIFIED),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_client_data() {
+        let json = client_data_json(GET_CEREMONY_TYPE, &WEBAUTHN_ORIGIN);
+        assert!(ClientData::parse(&json, GET_CEREMONY_TYPE).is_ok());
+    }
+
+    #[test]
+    fn rejects_client_data_of_other_ceremony_or_origin() {
+        let json = client_data_json(CREATE_CEREMONY_TYPE, &WEBAUTHN_ORIGIN);
+        assert!(ClientData::parse(&json, GET_CEREMONY_TYPE).is_err());
+
+        let json = client_data_json(GET_CEREMONY_TYPE, "https://evil.example.com");
+        assert!(ClientData::parse(&json, GET_CEREMONY_TYPE).is_err());
+    }
+
+    #[test]
+    fn registers_passkey() {
+        let authenticator = TestAuthenticator::new();
+        let passkey = register(&authenticator);
+
+        assert_eq!(passkey.credential_id.as_ref(), authenticator.credential_id);
+        assert_eq!(
+            passkey.public_key,
+            authenticator
+                .signing_key
+                .verifying_key()
+                .to_encoded_point(false)
+                .as_bytes()
+        );
+    }
+
+    #[test]
+    fn rejects_registration_without_user_presence() {
+        let authenticator = TestAuthenticator::new();
+        let attestation_object = authenticator.attestation_object(0);
+
+        assert!(verify_registration(UserId::default(), &attestation_object).is_err());
+    }
+
+    #[test]
+    fn rejects_registration_without_user_verification() {
+        let authenticator = TestAuthenticator::new();
+        let attestation_object = authenticator.attestation_object(USER_PRESENT);
+
+        assert!(verify_registration(UserId::default(), &attestation_object).is_err());
+    }
+
+    #[test]
+    fn rejects_registration_for_other_relying_party() {
+        let authenticator = TestAuthenticator {
+            rp_id: "example.com".to_owned(),
+            ..TestAuthenticator::new()
+        };
+        let attestation_object = authenticator.attestation_object(USER_PRESENT | USER_VERIFIED);
+
+        assert!(verify_registration(UserId::default(), &attestation_object).is_err());
+    }
+
+    #[test]
+    fn verifies_assertion() {
+        let authenticator = TestAuthenticator::new();
+        let passkey = register(&authenticator);
+
+        let json = client_data_json(GET_CEREMONY_TYPE, &WEBAUTHN_ORIGIN);
+        let client_data = ClientData::parse(&json, GET_CEREMONY_TYPE).unwrap();
+        let authenticator_data = authenticator.authenticator_data(USER_PRESENT | USER_VERIFIED, 1);
+        let signature = authenticator.sign(&authenticator_data, &json);
+
+        assert_eq!(
+            verify_assertion(
+                &passkey,
+                &client_data,
+                &authenticator_data,
+                &signature,
+                true
+            )
+            .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_assertion_with_wrong_signature() {
+        let authenticator = TestAuthenticator::new();
+        let passkey = register(&authenticator);
+
+        let json = client_data_json(GET_CEREMONY_TYPE, &WEBAUTHN_ORIGIN);
+        let client_data = ClientData::parse(&json, GET_CEREMONY_TYPE).unwrap();
+        let authenticator_data = authenticator.authenticator_data(USER_PRESENT, 0);
+        // Signed by another authenticator
+        let signature = TestAuthenticator::new().sign(&authenticator_data, &json);
+
+        assert!(verify_assertion(
+            &passkey,
+            &client_data,
+            &authenticator_data,
+            &signature,
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_assertion_without_required_user_verification() {
+        let authenticator = TestAuthenticator::new();
+        let passkey = register(&authenticator);
+
+        let json = client_data_json(GET_CEREMONY_TYPE, &WEBAUTHN_ORIGIN);
+        let client_data = ClientData::parse(&json, GET_CEREMONY_TYPE).unwrap();
+        let authenticator_data = authenticator.authenticator_data(USER_PRESENT, 0);
+        let signature = authenticator.sign(&authenticator_data, &json);
+
+        assert!(verify_assertion(
+            &passkey,
+            &client_data,
+            &authenticator_data,
+            &signature,
+            true
+        )
+        .is_err());
+        assert!(verify_assertion(
+            &passkey,
+            &client_data,
+            &authenticator_data,
+            &signature,
+            false
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_assertion_with_stale_sign_count() {
+        let authenticator = TestAuthenticator::new();
+        let passkey = Passkey {
+            sign_count: 5,
+            ..register(&authenticator)
+        };
+
+        let json = client_data_json(GET_CEREMONY_TYPE, &WEBAUTHN_ORIGIN);
+        let client_data = ClientData::parse(&json, GET_CEREMONY_TYPE).unwrap();
+
+        for sign_count in [0, 5] {
+            let authenticator_data = authenticator.authenticator_data(USER_PRESENT, sign_count);
+            let signature = authenticator.sign(&authenticator_data, &json);
+
+            assert!(verify_assertion(
+                &passkey,
+                &client_data,
+                &authenticator_data,
+                &signature,
+                false
+            )
+            .is_err());
+        }
+    }
+}
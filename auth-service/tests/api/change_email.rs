@@ -0,0 +1,272 @@
+use auth_service::{routes::UserInfoResponse, ErrorResponse};
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_switch_email_once_confirmed() {
+    let old_email = get_random_email();
+
+    app.signup_and_login(&old_email, false).await;
+    let new_email = get_random_email();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Nothing changes before the new address confirms
+    let response = app
+        .post_login(&serde_json::json!({"email": old_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .get_token_from_last_email()
+        .await
+        .expect("No token found in email");
+
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": old_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": new_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_email_both_addresses() {
+    app.signup_and_login(&get_random_email(), false).await;
+    let new_email = get_random_email();
+
+    let sent_before = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests")
+        .len();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests");
+
+    assert_eq!(requests.len(), sent_before + 2);
+
+    let bodies: Vec<String> = requests[sent_before..]
+        .iter()
+        .map(|request| String::from_utf8_lossy(&request.body).to_string())
+        .collect();
+
+    assert!(bodies[0].contains("Email change requested"));
+    assert!(bodies[1].contains(&new_email));
+    assert!(bodies[1].contains("token="));
+}
+
+#[test_and_cleanup]
+async fn should_keep_sessions_on_confirm() {
+    app.signup_and_login(&get_random_email(), false).await;
+    let new_email = get_random_email();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .get_token_from_last_email()
+        .await
+        .expect("No token found in email");
+
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Sessions refer to the user by id, so the address change doesn't end them
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<UserInfoResponse>()
+            .await
+            .expect("Could not deserialize response body to UserInfoResponse")
+            .email,
+        new_email
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_token_reused() {
+    app.signup_and_login(&get_random_email(), false).await;
+    let new_email = get_random_email();
+
+    app.post_change_email(&serde_json::json!({
+        "newEmail": new_email,
+        "password": "password123"
+    }))
+    .await;
+
+    let token = app
+        .get_token_from_last_email()
+        .await
+        .expect("No token found in email");
+
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email change token is invalid or has expired".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_password_incorrect() {
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": get_random_email(),
+            "password": "wrong-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_notify_owner_if_new_email_taken() {
+    let taken_email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": taken_email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let old_email = get_random_email();
+
+    app.signup_and_login(&old_email, false).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": taken_email,
+            "password": "password123"
+        }))
+        .await;
+
+    // Same as for an address without an account
+    assert_eq!(response.status().as_u16(), 200);
+
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests");
+    let body = String::from_utf8_lossy(&requests.last().expect("No email sent").body).to_string();
+
+    assert!(body.contains(&taken_email));
+    assert!(body.contains("Email change attempt"));
+    assert!(!body.contains("token="));
+
+    let response = app
+        .post_login(&serde_json::json!({"email": old_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_switch_email_by_following_link() {
+    app.signup_and_login(&get_random_email(), false).await;
+    let new_email = get_random_email();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let link = app
+        .get_link_from_last_email()
+        .await
+        .expect("No link found in email");
+
+    assert!(link.contains("/change-email/confirm?token="));
+
+    let response = app.follow_link(&link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": new_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_invalid_input() {
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": "not-an-email",
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,242 @@
+use auth_service::{
+    utils::constants::{JWT_COOKIE_NAME, LOGIN_LOCKOUT_THRESHOLD},
+    ErrorResponse,
+};
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_return_200_and_replace_password() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "new-password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_notify_user_by_email() {
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests");
+    let body = String::from_utf8_lossy(&requests.last().expect("No email sent").body).to_string();
+
+    assert!(body.contains("Password changed"));
+}
+
+#[test_and_cleanup]
+async fn should_end_other_sessions_and_keep_current() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let other_token = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No JWT cookie found")
+        .value()
+        .to_owned();
+
+    // Log in again so the request below comes from a different session
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_invalidate_outstanding_password_reset_links() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let token = app.request_password_reset(&random_email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "other-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_new_password_invalid() {
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "short"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_with_violations_if_new_password_breaks_policy() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let local_part = random_email.split('@').next().unwrap();
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": format!("{}-password", local_part)
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+
+    assert_eq!(body.error, "Password does not meet the requirements");
+    assert_eq!(
+        body.violations
+            .iter()
+            .map(|violation| violation.code.as_str())
+            .collect::<Vec<_>>(),
+        ["contains_email"]
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_current_password_incorrect() {
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "wrong-password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect Credentials".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_429_after_repeated_wrong_current_passwords() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    for _ in 0..*LOGIN_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "currentPassword": "wrong-password123",
+                "newPassword": "new-password123"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The session alone isn't enough to keep guessing
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    // The lockout applies to logins as well
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
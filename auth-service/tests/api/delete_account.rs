@@ -0,0 +1,231 @@
+use auth_service::{
+    routes::{DeleteAccountResponse, TwoFactorAuthResponse},
+    utils::constants::LOGIN_LOCKOUT_THRESHOLD,
+};
+use macros::test_and_cleanup;
+use secrecy::ExposeSecret;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_delete_account_and_end_sessions() {
+    let random_email = get_random_email();
+
+    let auth_token = app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({"password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<DeleteAccountResponse>()
+            .await
+            .expect("Could not deserialize response body to DeleteAccountResponse")
+            .message,
+        "Account deleted".to_owned()
+    );
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The address can be used for a new account right away
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_password_incorrect() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({"password": "wrong-password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app
+        .delete_account(&serde_json::json!({"password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_422_if_malformed_input() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({"2FACode": "123456"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[test_and_cleanup]
+async fn should_require_2fa_code_if_enabled() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, true).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({"password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    // The code goes out by email, like on login
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    let response = app
+        .delete_account(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "000000"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .delete_account(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code.as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_restore_account_deleted_within_grace_period() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    // Tests run without a grace period, so hide the account the way a
+    // deletion within one would instead of purging it
+    sqlx::query("UPDATE users SET deleted_at = NOW() WHERE email = $1")
+        .bind(&random_email)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to mark user deleted");
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_account_restore(&serde_json::json!({
+            "email": random_email,
+            "password": "wrong-password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_account_restore(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<DeleteAccountResponse>()
+            .await
+            .expect("Could not deserialize response body to DeleteAccountResponse")
+            .message,
+        "Account restored".to_owned()
+    );
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_when_restoring_unknown_account() {
+    let response = app
+        .post_account_restore(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_429_after_repeated_failed_restores() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    sqlx::query("UPDATE users SET deleted_at = NOW() WHERE email = $1")
+        .bind(&random_email)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to mark user deleted");
+
+    for _ in 0..*LOGIN_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_account_restore(&serde_json::json!({
+                "email": random_email,
+                "password": "wrong-password123"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Locked out even with the right password
+    let response = app
+        .post_account_restore(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
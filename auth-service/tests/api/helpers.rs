@@ -1,24 +1,38 @@
-use std::{str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use auth_service::{
     app_state::AppState,
-    domain::Email,
+    domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore},
     get_postgres_pool, get_redis_client,
+    routes::{PasskeyCreationOptions, PasskeyRequestOptions, TwoFactorAuthResponse},
     services::{
-	PostgresUserStore, PostmarkEmailClient, RedisBannedTokenStore, RedisTwoFACodeStore,
+	HashmapFailedLoginStore, HashmapMagicLinkTokenStore, HashmapPasswordResetTokenStore,
+	PostgresPasskeyStore, PostgresRecoveryCodeStore, PostgresSessionStore, PostgresUserStore,
+	PostmarkEmailClient, RedisBannedTokenStore, RedisEmailChangeTokenStore,
+	RedisEmailVerificationTokenStore, RedisPasskeyChallengeStore, RedisRefreshTokenStore,
+	RedisTwoFACodeStore,
+    },
+    utils::{
+	constants::{
+	    test, AUTH_SERVICE_URL, DATABASE_URL, JWT_COOKIE_NAME, REDIS_HOST_NAME, WEBAUTHN_ORIGIN,
+	},
+	password_hashing::Peppers,
     },
-    utils::constants::{test, DATABASE_URL, REDIS_HOST_NAME},
     Application,
 };
+use ciborium::Value;
+use data_encoding::BASE64URL_NOPAD;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
 use reqwest::{cookie::Jar, Client};
 use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
-    Connection, Executor, PgConnection,
+    Connection, Executor, PgConnection, PgPool,
 };
 use tokio::sync::RwLock;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
 
 pub struct TestApp {
     pub address: String,
@@ -27,6 +41,7 @@ pub struct TestApp {
     pub two_fa_code_store: Arc<RwLock<RedisTwoFACodeStore>>,
     pub http_client: reqwest::Client,
     pub email_server: MockServer,
+    pub pg_pool: PgPool,
     pub db_name: String,
     pub cleaned_up: bool,
 }
@@ -37,17 +52,59 @@ impl TestApp {
 	let pg_pool = configure_postgresql(&db_name).await;
 	let redis_conn = Arc::new(RwLock::new(configure_redis()));
 
-	let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+	let user_store = Arc::new(RwLock::new(
+	    PostgresUserStore::new(pg_pool.clone()).with_peppers(configure_peppers()),
+	));
 	let banned_token_store =
 	    Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
-	let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
+	let refresh_token_store =
+	    Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+	let session_store = Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone())));
+	let two_fa_code_store = Arc::new(RwLock::new(
+	    RedisTwoFACodeStore::new(redis_conn.clone())
+		.with_resend_interval(test::TWO_FA_RESEND_INTERVAL_SECONDS),
+	));
+	let recovery_code_store =
+	    Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+	// Reset requests are throttled per IP address, so like failed logins
+	// they are kept per app instead of in the shared Redis
+	let password_reset_token_store =
+	    Arc::new(RwLock::new(HashmapPasswordResetTokenStore::default()));
+	let email_verification_token_store =
+	    Arc::new(RwLock::new(RedisEmailVerificationTokenStore::new(redis_conn.clone())));
+	let email_change_token_store =
+	    Arc::new(RwLock::new(RedisEmailChangeTokenStore::new(redis_conn.clone())));
+	// Magic links are throttled per IP address as well, so like failed
+	// logins they are kept per app instead of in the shared Redis
+	let magic_link_token_store = Arc::new(RwLock::new(HashmapMagicLinkTokenStore::default()));
+	let passkey_store = Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone())));
+	let passkey_challenge_store =
+	    Arc::new(RwLock::new(RedisPasskeyChallengeStore::new(redis_conn)));
+	// Every test logs in from 127.0.0.1, so failures are counted per app
+	// instead of in the shared Redis
+	let failed_login_store = Arc::new(RwLock::new(HashmapFailedLoginStore::default()));
 	let email_server = MockServer::start().await;
+	// Accept every outgoing email so flows that notify users don't fail //
+	Mock::given(method("POST"))
+	    .respond_with(ResponseTemplate::new(200))
+	    .mount(&email_server)
+	    .await;
 	let base_url = email_server.uri();
 	let email_client = Arc::new(configure_postmark_email_client(base_url));
 	let app_state = AppState::new(
 	    user_store,
 	    banned_token_store.clone(),
+	    refresh_token_store,
+	    session_store,
 	    two_fa_code_store.clone(),
+	    recovery_code_store,
+	    password_reset_token_store,
+	    email_verification_token_store,
+	    email_change_token_store,
+	    magic_link_token_store,
+	    passkey_store,
+	    passkey_challenge_store,
+	    failed_login_store,
 	    email_client,
 	);
 
@@ -75,6 +132,7 @@ impl TestApp {
 	    two_fa_code_store,
 	    http_client,
 	    email_server,
+	    pg_pool,
 	    db_name,
 	    cleaned_up: false,
 	}
@@ -82,7 +140,7 @@ impl TestApp {
 
     pub async fn get_root(&self) -> reqwest::Response {
 	self.http_client
-	    .get(&format!("{}/", self.address))
+	    .get(format!("{}/", self.address))
 	    .send()
 	    .await
 	    .expect("Failed to send request.")
@@ -93,7 +151,7 @@ impl TestApp {
 	Body: serde::Serialize,
     {
 	self.http_client
-	    .post(&format!("{}/signup", &self.address))
+	    .post(format!("{}/signup", &self.address))
 	    .json(body)
 	    .send()
 	    .await
@@ -105,7 +163,7 @@ impl TestApp {
 	Body: serde::Serialize,
     {
 	self.http_client
-	    .post(&format!("{}/login", self.address))
+	    .post(format!("{}/login", self.address))
 	    .json(body)
 	    .send()
 	    .await
@@ -117,33 +175,505 @@ impl TestApp {
 	Body: serde::Serialize,
     {
 	self.http_client
-	    .post(&format!("{}/verify-2fa", self.address))
+	    .post(format!("{}/verify-2fa", self.address))
 	    .json(&body)
 	    .send()
 	    .await
 	    .expect("Failed to send request.")
     }
 
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/resend-2fa", self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/magic-link", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn get_magic_link_callback(&self, token: &str) -> reqwest::Response {
+	self.http_client
+	    .get(format!("{}/magic-link/callback", &self.address))
+	    .query(&[("token", token)])
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/passkeys/register/start", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/passkeys/register/finish", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_start(&self) -> reqwest::Response {
+	self.http_client
+	    .post(format!("{}/passkeys/login/start", &self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/passkeys/login/finish", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn get_passkeys(&self) -> reqwest::Response {
+	self.http_client
+	    .get(format!("{}/passkeys", &self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_passkey(&self, id: &str) -> reqwest::Response {
+	self.http_client
+	    .delete(format!("{}/passkeys/{}", &self.address, id))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_2fa_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/passkeys/2fa/start", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_recovery_code<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/verify-2fa/recovery", self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_regenerate_recovery_codes(&self) -> reqwest::Response {
+	self.http_client
+	    .post(format!("{}/recovery-codes/regenerate", self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn get_user_info(&self) -> reqwest::Response {
+	self.http_client
+	    .get(format!("{}/user", self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
 	self.http_client
-	    .post(&format!("{}/logout", self.address))
+	    .post(format!("{}/logout", self.address))
 	    .send()
 	    .await
 	    .expect("Failed to send request.")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+	self.http_client
+	    .post(format!("{}/logout-all", self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+	self.http_client
+	    .get(format!("{}/sessions", self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+	self.http_client
+	    .delete(format!("{}/sessions/{}", self.address, id))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+	self.http_client
+	    .post(format!("{}/refresh", self.address))
+	    .send()
+	    .await
+	    .expect("Failed to send request.")
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+	self.http_client
+	    .post(format!("{}/totp/enroll", &self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/totp/confirm", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_disable<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/totp/disable", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+	self.http_client
+	    .get(format!("{}/.well-known/jwks.json", &self.address))
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
 	Body: serde::Serialize,
     {
 	self.http_client
-	    .post(&format!("{}/verify-token", &self.address))
+	    .post(format!("{}/verify-token", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/verify-email", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/verify-email/resend", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    /// Confirms the address of the most recently signed up user with the
+    /// token from their verification email.
+    pub async fn verify_last_signup(&self) {
+	let token = self
+	    .get_token_from_last_email()
+	    .await
+	    .expect("No verification token found in email");
+
+	let response = self
+	    .post_verify_email(&serde_json::json!({ "token": token }))
+	    .await;
+
+	assert_eq!(response.status().as_u16(), 200);
+    }
+
+    /// Signs up with `password123` and verifies the address.
+    pub async fn signup(&self, email: &str, requires_2fa: bool) {
+	let signup_body = serde_json::json!({
+	    "email": email,
+	    "password": "password123",
+	    "requires2FA": requires_2fa
+	});
+
+	let response = self.post_signup(&signup_body).await;
+
+	assert_eq!(response.status().as_u16(), 201);
+
+	self.verify_last_signup().await;
+    }
+
+    /// Signs up, logs in (through email 2FA if `requires_2fa`) and returns the
+    /// auth token of the new session.
+    pub async fn signup_and_login(&self, email: &str, requires_2fa: bool) -> String {
+	self.signup(email, requires_2fa).await;
+
+	let response = if requires_2fa {
+	    let login_attempt_id = self.start_login(email).await;
+	    let two_fa_code = self.get_two_fa_code(&login_attempt_id).await;
+
+	    self.post_verify_2fa(&serde_json::json!({
+		"email": email,
+		"loginAttemptId": login_attempt_id,
+		"2FACode": two_fa_code.as_ref().expose_secret()
+	    }))
+	    .await
+	} else {
+	    self.post_login(&serde_json::json!({"email": email, "password": "password123"}))
+		.await
+	};
+
+	assert_eq!(response.status().as_u16(), 200);
+
+	let auth_token = response
+	    .cookies()
+	    .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+	    .map(|cookie| cookie.value().to_owned())
+	    .expect("No auth cookie found");
+
+	auth_token
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/password-reset/request", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/password-reset/confirm", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/change-password", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/change-email", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/change-email/confirm", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    /// Returns the value of the `token` query parameter in the most recent
+    /// email sent through the mock Postmark server.
+    pub async fn delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .delete(format!("{}/account", &self.address))
+	    .json(body)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account_restore<Body>(&self, body: &Body) -> reqwest::Response
+    where
+	Body: serde::Serialize,
+    {
+	self.http_client
+	    .post(format!("{}/account/restore", &self.address))
 	    .json(body)
 	    .send()
 	    .await
 	    .expect("Failed to execute request.")
     }
 
+    pub async fn get_token_from_last_email(&self) -> Option<String> {
+	let requests = self.email_server.received_requests().await?;
+	let body = String::from_utf8_lossy(&requests.last()?.body).to_string();
+
+	let start = body.find("token=")? + "token=".len();
+	let token = body[start..]
+	    .chars()
+	    .take_while(|c| c.is_ascii_alphanumeric())
+	    .collect();
+
+	Some(token)
+    }
+
+    /// Finds the link to this service in the last email sent, pointed at the
+    /// test app instead of `AUTH_SERVICE_URL`.
+    pub async fn get_link_from_last_email(&self) -> Option<String> {
+	let requests = self.email_server.received_requests().await?;
+	let body = String::from_utf8_lossy(&requests.last()?.body).to_string();
+
+	let start = body.find(AUTH_SERVICE_URL.as_str())? + AUTH_SERVICE_URL.len();
+	let path: String = body[start..]
+	    .chars()
+	    .take_while(|c| !c.is_whitespace() && *c != '"' && *c != '\\')
+	    .collect();
+
+	Some(format!("{}{}", self.address, path))
+    }
+
+    /// Waits for emails sent after the response, failing after two seconds.
+    pub async fn wait_for_emails(&self, count: usize) {
+	for _ in 0..40 {
+	    if self.email_server.received_requests().await.unwrap().len() >= count {
+		return;
+	    }
+
+	    tokio::time::sleep(Duration::from_millis(50)).await;
+	}
+
+	panic!("Expected {} emails to have been sent", count);
+    }
+
+    pub async fn follow_link(&self, link: &str) -> reqwest::Response {
+	self.http_client
+	    .get(link)
+	    .send()
+	    .await
+	    .expect("Failed to execute request.")
+    }
+
+    pub async fn get_two_fa_code(&self, login_attempt_id: &str) -> TwoFACode {
+	let login_attempt_id = LoginAttemptId::parse(Secret::new(login_attempt_id.to_owned()))
+	    .expect("Could not parse login attempt id");
+
+	self.two_fa_code_store
+	    .read()
+	    .await
+	    .get_code(&login_attempt_id)
+	    .await
+	    .expect("No 2FA code stored")
+	    .1
+    }
+
+    /// Logs a 2FA user in with their password and returns the login attempt
+    /// ID.
+    pub async fn start_login(&self, email: &str) -> String {
+	let response = self
+	    .post_login(&serde_json::json!({"email": email, "password": "password123"}))
+	    .await;
+
+	assert_eq!(response.status().as_u16(), 206);
+
+	response
+	    .json::<TwoFactorAuthResponse>()
+	    .await
+	    .expect("Could not deserialize response body to TwoFactorAuthResponse")
+	    .login_attempt_id
+    }
+
+    /// Requests a password reset link and returns the token from the email,
+    /// which is sent after the response.
+    pub async fn request_password_reset(&self, email: &str) -> String {
+	let emails_sent = self.email_server.received_requests().await.unwrap().len();
+
+	let response = self
+	    .post_password_reset_request(&serde_json::json!({"email": email}))
+	    .await;
+
+	assert_eq!(response.status().as_u16(), 200);
+
+	self.wait_for_emails(emails_sent + 1).await;
+
+	self.get_token_from_last_email()
+	    .await
+	    .expect("No password reset token found in email")
+    }
+
     pub async fn clean_up(&mut self) {
 	if self.cleaned_up {
 	    return;
@@ -242,6 +772,129 @@ pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
+/// Reads the `jti` claim from a JWT without verifying it.
+pub fn get_jti(token: &str) -> String {
+    let payload = token.split('.').nth(1).expect("JWT has no payload");
+    let payload = data_encoding::BASE64URL_NOPAD
+	.decode(payload.as_bytes())
+	.expect("JWT payload is not base64url");
+    let claims: serde_json::Value =
+	serde_json::from_slice(&payload).expect("JWT payload is not JSON");
+
+    claims["jti"].as_str().expect("JWT has no jti").to_owned()
+}
+
+const USER_PRESENT: u8 = 0x01;
+const USER_VERIFIED: u8 = 0x04;
+const ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+/// Stands in for the authenticator of a browser in passkey tests. It holds a
+/// single ES256 passkey and answers the options of the passkey routes with
+/// the credentials `navigator.credentials` would return.
+pub struct SoftwareAuthenticator {
+    pub credential_id: Vec<u8>,
+    signing_key: SigningKey,
+    sign_count: u32,
+    /// Whether the user counts as verified with a PIN or biometrics.
+    pub user_verified: bool,
+}
+
+impl Default for SoftwareAuthenticator {
+    fn default() -> Self {
+	Self {
+	    credential_id: Uuid::new_v4().as_bytes().to_vec(),
+	    signing_key: SigningKey::random(&mut rand::rngs::OsRng),
+	    sign_count: 0,
+	    user_verified: true,
+	}
+    }
+}
+
+impl SoftwareAuthenticator {
+    pub fn create(&self, options: &PasskeyCreationOptions) -> serde_json::Value {
+	let client_data_json = client_data_json("webauthn.create", &options.challenge);
+
+	let point = self.signing_key.verifying_key().to_encoded_point(false);
+	let public_key = Value::Map(vec![
+	    (1.into(), 2.into()),
+	    (3.into(), (-7).into()),
+	    ((-1).into(), 1.into()),
+	    ((-2).into(), Value::Bytes(point.x().unwrap().to_vec())),
+	    ((-3).into(), Value::Bytes(point.y().unwrap().to_vec())),
+	]);
+
+	let mut auth_data =
+	    self.authenticator_data(&options.rp.id, ATTESTED_CREDENTIAL_DATA, self.sign_count);
+	auth_data.extend_from_slice(&[0; 16]);
+	auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+	auth_data.extend_from_slice(&self.credential_id);
+	ciborium::ser::into_writer(&public_key, &mut auth_data).unwrap();
+
+	let attestation = Value::Map(vec![
+	    ("fmt".into(), "none".into()),
+	    ("attStmt".into(), Value::Map(Vec::new())),
+	    ("authData".into(), Value::Bytes(auth_data)),
+	]);
+
+	let mut attestation_object = Vec::new();
+	ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+
+	serde_json::json!({
+	    "id": BASE64URL_NOPAD.encode(&self.credential_id),
+	    "type": "public-key",
+	    "response": {
+		"clientDataJSON": BASE64URL_NOPAD.encode(&client_data_json),
+		"attestationObject": BASE64URL_NOPAD.encode(&attestation_object),
+	    },
+	})
+    }
+
+    pub fn get(&mut self, options: &PasskeyRequestOptions) -> serde_json::Value {
+	self.sign_count += 1;
+
+	let client_data_json = client_data_json("webauthn.get", &options.challenge);
+	let auth_data = self.authenticator_data(&options.rp_id, 0, self.sign_count);
+
+	let mut signed_data = auth_data.clone();
+	signed_data.extend_from_slice(&Sha256::digest(&client_data_json));
+	let signature: Signature = self.signing_key.sign(&signed_data);
+
+	serde_json::json!({
+	    "id": BASE64URL_NOPAD.encode(&self.credential_id),
+	    "type": "public-key",
+	    "response": {
+		"clientDataJSON": BASE64URL_NOPAD.encode(&client_data_json),
+		"authenticatorData": BASE64URL_NOPAD.encode(&auth_data),
+		"signature": BASE64URL_NOPAD.encode(signature.to_der().as_bytes()),
+	    },
+	})
+    }
+
+    fn authenticator_data(&self, rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+	let mut flags = flags | USER_PRESENT;
+
+	if self.user_verified {
+	    flags |= USER_VERIFIED;
+	}
+
+	let mut auth_data = Sha256::digest(rp_id.as_bytes()).to_vec();
+	auth_data.push(flags);
+	auth_data.extend_from_slice(&sign_count.to_be_bytes());
+	auth_data
+    }
+}
+
+fn client_data_json(ceremony_type: &str, challenge: &str) -> Vec<u8> {
+    serde_json::json!({
+	"type": ceremony_type,
+	"challenge": challenge,
+	"origin": WEBAUTHN_ORIGIN.as_str(),
+	"crossOrigin": false,
+    })
+    .to_string()
+    .into_bytes()
+}
+
 pub fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
     let postmark_auth_token = Secret::new("auth_token".to_owned());
 
@@ -254,3 +907,16 @@ pub fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient
 
     PostmarkEmailClient::new(http_client, base_url, sender, postmark_auth_token)
 }
+
+fn configure_peppers() -> Peppers {
+    Peppers {
+	current: Some((
+	    test::password_pepper::VERSION,
+	    Secret::new(test::password_pepper::PEPPER.to_owned()),
+	)),
+	previous: vec![(
+	    test::password_pepper::PREVIOUS_VERSION,
+	    Secret::new(test::password_pepper::PREVIOUS_PEPPER.to_owned()),
+	)],
+    }
+}
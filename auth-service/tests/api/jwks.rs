@@ -0,0 +1,27 @@
+use auth_service::utils::auth::jwt_keyring;
+use jsonwebtoken::jwk::JwkSet;
+use macros::test_and_cleanup;
+
+use crate::helpers::TestApp;
+
+#[test_and_cleanup]
+async fn should_return_200_with_signing_keys() {
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    let expected: Vec<_> = jwt_keyring()
+        .jwks()
+        .keys
+        .into_iter()
+        .map(|jwk| jwk.common.key_id)
+        .collect();
+    let kids: Vec<_> = jwks.keys.into_iter().map(|jwk| jwk.common.key_id).collect();
+
+    assert_eq!(kids, expected);
+}
@@ -1,7 +1,13 @@
-use auth_service::domain::{Email, TwoFACodeStore};
+use auth_service::domain::{LoginAttemptId, TwoFACodeStore};
 use auth_service::{
-    routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME, ErrorResponse,
+    routes::TwoFactorAuthResponse,
+    utils::{
+        constants::{test, JWT_COOKIE_NAME, LOGIN_IP_LOCKOUT_THRESHOLD, LOGIN_LOCKOUT_THRESHOLD},
+        password_hashing::{hash_password, pepper_password},
+    },
+    ErrorResponse,
 };
+use reqwest::header::RETRY_AFTER;
 use secrecy::Secret;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -17,6 +23,8 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({"email": random_email, "password": "password123"});
 
     let response = app.post_login(&login_body).await;
@@ -41,6 +49,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({"email": random_email, "password": "password123"});
 
     let response = app.post_login(&login_body).await;
@@ -54,11 +64,11 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let store = app.two_fa_code_store.read().await;
+    let login_attempt_id = LoginAttemptId::parse(Secret::new(json_body.login_attempt_id)).unwrap();
 
-    let email = Email::parse(Secret::new(random_email)).expect("Could not parse email");
+    let store = app.two_fa_code_store.read().await;
 
-    assert!(store.get_code(&email).await.is_ok());
+    assert!(store.get_code(&login_attempt_id).await.is_ok());
 }
 
 #[test_and_cleanup]
@@ -118,6 +128,253 @@ async fn should_return_401_if_incorrect_credentials() {
     }
 }
 
+#[test_and_cleanup]
+async fn should_return_same_response_for_unknown_email_and_wrong_password() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let wrong_password = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "wrong-password"}))
+        .await;
+    let unknown_email = app
+        .post_login(&serde_json::json!({"email": get_random_email(), "password": "password123"}))
+        .await;
+
+    assert_eq!(wrong_password.status(), unknown_email.status());
+    assert_eq!(
+        wrong_password
+            .text()
+            .await
+            .expect("Could not read response body"),
+        unknown_email
+            .text()
+            .await
+            .expect("Could not read response body")
+    );
+}
+
+#[test_and_cleanup]
+async fn should_upgrade_legacy_password_hash_on_login() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    // As if the account was imported from a system that used bcrypt
+    let legacy_hash = bcrypt::hash("password123", 4).expect("Failed to hash password");
+
+    sqlx::query("UPDATE users SET password_hash = $1, pepper_version = NULL WHERE email = $2")
+        .bind(&legacy_hash)
+        .bind(&random_email)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to set legacy password hash");
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let password_hash: String =
+        sqlx::query_scalar("SELECT password_hash FROM users WHERE email = $1")
+            .bind(&random_email)
+            .fetch_one(&app.pg_pool)
+            .await
+            .expect("Failed to get password hash");
+
+    assert!(password_hash.starts_with("$argon2id$"));
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_upgrade_previous_pepper_hash_on_login() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    // As if the password was set before the pepper was rotated
+    let peppered = pepper_password(
+        &Secret::new("password123".to_owned()),
+        &Secret::new(test::password_pepper::PREVIOUS_PEPPER.to_owned()),
+    )
+    .expect("Failed to pepper password");
+    let previous_hash = hash_password(&peppered).expect("Failed to hash password");
+
+    set_password_hash(
+        &app,
+        &random_email,
+        &previous_hash,
+        Some(test::password_pepper::PREVIOUS_VERSION),
+    )
+    .await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (password_hash, pepper_version) = get_password_hash(&app, &random_email).await;
+
+    assert_ne!(password_hash, previous_hash);
+    assert_eq!(pepper_version, Some(test::password_pepper::VERSION));
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_upgrade_unpeppered_hash_on_login() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    // As if the password was set before a pepper was configured
+    let unpeppered_hash =
+        hash_password(&Secret::new("password123".to_owned())).expect("Failed to hash password");
+
+    set_password_hash(&app, &random_email, &unpeppered_hash, None).await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (password_hash, pepper_version) = get_password_hash(&app, &random_email).await;
+
+    assert_ne!(password_hash, unpeppered_hash);
+    assert_eq!(pepper_version, Some(test::password_pepper::VERSION));
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_429_after_repeated_failures() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let sent_before = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests")
+        .len();
+
+    for _ in 0..*LOGIN_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_login(&serde_json::json!({"email": random_email, "password": "wrong-password"}))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Locked out even with the right password
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .expect("Retry-After is not a number of seconds");
+
+    assert!(retry_after > 0);
+
+    app.wait_for_emails(sent_before + 1).await;
+
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests");
+
+    assert_eq!(requests.len(), sent_before + 1);
+    assert!(
+        String::from_utf8_lossy(&requests[sent_before].body).contains("Account temporarily locked")
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_429_after_failures_across_accounts() {
+    for _ in 0..*LOGIN_IP_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_login(
+                &serde_json::json!({"email": get_random_email(), "password": "password123"}),
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_login(&serde_json::json!({"email": get_random_email(), "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key(RETRY_AFTER));
+}
+
+#[test_and_cleanup]
+async fn should_ignore_forwarded_for_from_untrusted_peer() {
+    for _ in 0..*LOGIN_IP_LOCKOUT_THRESHOLD {
+        let response = app
+            .post_login(
+                &serde_json::json!({"email": get_random_email(), "password": "password123"}),
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // A client can't claim another address to get out of the lockout
+    let response = app
+        .http_client
+        .post(format!("{}/login", app.address))
+        .header("X-Forwarded-For", "203.0.113.7")
+        .json(&serde_json::json!({"email": get_random_email(), "password": "password123"}))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 429);
+}
+
 #[test_and_cleanup]
 async fn should_return_422_if_malformed_credentials() {
     let random_email = get_random_email();
@@ -133,3 +390,26 @@ async fn should_return_422_if_malformed_credentials() {
         assert_eq!(response.status().as_u16(), 422);
     }
 }
+
+async fn set_password_hash(
+    app: &TestApp,
+    email: &str,
+    password_hash: &str,
+    pepper_version: Option<i32>,
+) {
+    sqlx::query("UPDATE users SET password_hash = $1, pepper_version = $2 WHERE email = $3")
+        .bind(password_hash)
+        .bind(pepper_version)
+        .bind(email)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to set password hash");
+}
+
+async fn get_password_hash(app: &TestApp, email: &str) -> (String, Option<i32>) {
+    sqlx::query_as("SELECT password_hash, pepper_version FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to get password hash")
+}
@@ -2,9 +2,8 @@ use auth_service::domain::BannedTokenStore;
 use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
 use macros::test_and_cleanup;
 use reqwest::Url;
-use secrecy::Secret;
 
-use crate::helpers::{get_random_email, TestApp};
+use crate::helpers::{get_jti, get_random_email, TestApp};
 
 #[test_and_cleanup]
 async fn should_return_200_if_valid_jwt_cookie() {
@@ -20,6 +19,8 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({
     "email": random_email,
     "password": "password123"
@@ -36,8 +37,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert!(!auth_cookie.value().is_empty());
 
-    let token = auth_cookie.value();
-    let token = Secret::new(token.to_owned());
+    let jti = get_jti(auth_cookie.value());
 
     let response = app.post_logout().await;
 
@@ -46,7 +46,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
     let banned_token_store = app.banned_token_store.read().await;
 
     let contains_token = banned_token_store
-	.is_banned_token(&token)
+	.is_banned_token(&jti)
 	.await
 	.expect("Failed to check if token is banned");
 
@@ -67,6 +67,8 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({
     "email": random_email,
     "password": "password123"
@@ -0,0 +1,141 @@
+use auth_service::{routes::MagicLinkResponse, utils::constants::JWT_COOKIE_NAME};
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_log_in_with_magic_link_once() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.wait_for_emails(emails_sent + 1).await;
+
+    let token = app
+        .get_token_from_last_email()
+        .await
+        .expect("No magic link token found in email");
+
+    let response = app.get_magic_link_callback(&token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Links are single-use ///////////////////////////////////////////////////
+    let response = app.get_magic_link_callback(&token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_200_without_email_for_unknown_user() {
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": get_random_email()}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<MagicLinkResponse>()
+            .await
+            .expect("Could not deserialize response body to MagicLinkResponse")
+            .message,
+        "If the account exists, a login link has been sent".to_owned()
+    );
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[test_and_cleanup]
+async fn should_not_send_link_if_2fa_enabled() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, true).await;
+
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": random_email}))
+        .await;
+
+    // Same response as for any other account
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        emails_sent
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_429_if_link_requested_too_soon() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    // The same client asking for another address is throttled too, whether
+    // or not the address is registered
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": get_random_email()}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_invalid_token() {
+    let test_cases = ["invalid-token".to_owned(), "a".repeat(64)];
+
+    for token in test_cases.iter() {
+        let response = app.get_magic_link_callback(token).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Test case failed: {:?}",
+            token
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_invalid_email() {
+    let response = app
+        .post_magic_link(&serde_json::json!({"email": "invalid-email"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,7 +1,21 @@
+mod change_email;
+mod change_password;
+mod delete_account;
 mod helpers;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod passkeys;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod resend_2fa;
 mod root;
+mod sessions;
 mod signup;
+mod totp;
+mod user_info;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
@@ -0,0 +1,453 @@
+use auth_service::{
+    routes::{
+        PasskeyCreationOptions, PasskeyRequestOptions, PasskeysResponse, TwoFactorAuthResponse,
+    },
+    utils::constants::JWT_COOKIE_NAME,
+};
+use macros::test_and_cleanup;
+use secrecy::ExposeSecret;
+
+use crate::helpers::{get_random_email, SoftwareAuthenticator, TestApp};
+
+#[test_and_cleanup]
+async fn should_register_passkey_and_log_in_without_password() {
+    let random_email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = start_passkey_registration(&app).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<PasskeyCreationOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyCreationOptions");
+
+    assert_eq!(options.user.name, random_email);
+    assert!(options.exclude_credentials.is_empty());
+
+    let response = app
+        .post_passkey_register_finish(&authenticator.create(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_skip_2fa_when_logging_in_with_passkey() {
+    let random_email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&random_email, true).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_challenge_reused() {
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_passkey_login(&app).await;
+    let assertion = authenticator.get(&options);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_passkey_login_finish(&assertion).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_user_not_verified() {
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    authenticator.user_verified = false;
+
+    let response = login_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_passkey_not_registered() {
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    let response = login_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_409_if_passkey_already_registered() {
+    let authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let response = start_passkey_registration(&app).await;
+    let options = response
+        .json::<PasskeyCreationOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyCreationOptions");
+
+    assert_eq!(options.exclude_credentials.len(), 1);
+
+    // A browser wouldn't let the excluded authenticator answer
+    let response = app
+        .post_passkey_register_finish(&authenticator.create(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_registering_without_login() {
+    let response = app
+        .post_passkey_register_start(&serde_json::json!({"password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_registering_with_wrong_password() {
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_passkey_register_start(&serde_json::json!({"password": "wrong-password"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_require_2fa_to_register_passkey() {
+    app.signup_and_login(&get_random_email(), true).await;
+
+    let response = app
+        .post_passkey_register_start(&serde_json::json!({"password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_passkey_register_start(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "000000",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    let response = app
+        .post_passkey_register_start(&serde_json::json!({
+            "password": "password123",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": two_fa_code.as_ref().expose_secret(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_list_and_delete_passkeys() {
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let passkeys = get_passkeys(&app).await;
+
+    assert_eq!(passkeys.passkeys.len(), 1);
+    assert!(passkeys.passkeys[0].last_used_at.is_none());
+
+    let id = passkeys.passkeys[0].id.clone();
+
+    let response = app.delete_passkey(&id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(get_passkeys(&app).await.passkeys.is_empty());
+
+    let response = app.delete_passkey(&id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = login_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_404_if_deleting_passkey_of_other_user() {
+    let authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&get_random_email(), false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let id = get_passkeys(&app).await.passkeys[0].id.clone();
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app.delete_passkey(&id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[test_and_cleanup]
+async fn should_remove_passkeys_on_password_reset() {
+    let random_email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&random_email, false).await;
+    register_passkey(&app, &authenticator).await;
+
+    let token = app.request_password_reset(&random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(
+            &serde_json::json!({"token": token, "newPassword": "newpassword123"}),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login_with_passkey(&app, &mut authenticator).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_verify_2fa_with_passkey() {
+    let random_email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&random_email, true).await;
+    register_passkey(&app, &authenticator).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let response = app
+        .post_passkey_2fa_start(
+            &serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id}),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<PasskeyRequestOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyRequestOptions");
+
+    assert_eq!(options.allow_credentials.len(), 1);
+
+    let verify_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "passkey": authenticator.get(&options),
+    });
+
+    let response = app.post_verify_2fa(&verify_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    // The login attempt is used up ///////////////////////////////////////////
+    let response = app.post_verify_2fa(&verify_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_passkey_answers_other_login_attempt() {
+    let random_email = get_random_email();
+    let mut authenticator = SoftwareAuthenticator::default();
+
+    app.signup_and_login(&random_email, true).await;
+    register_passkey(&app, &authenticator).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+    let other_login_attempt_id = app.start_login(&random_email).await;
+
+    let response = app
+        .post_passkey_2fa_start(
+            &serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id}),
+        )
+        .await;
+
+    let options = response
+        .json::<PasskeyRequestOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyRequestOptions");
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": other_login_attempt_id,
+            "passkey": authenticator.get(&options),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_no_passkey_for_2fa() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, true).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let response = app
+        .post_passkey_2fa_start(
+            &serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id}),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_422_if_malformed_input() {
+    let test_cases = [
+        serde_json::json!({"id": "AAAA"}),
+        serde_json::json!({"response": {"clientDataJSON": "", "authenticatorData": "", "signature": ""}}),
+        serde_json::json!({"id": "AAAA", "response": {"clientDataJSON": ""}}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_passkey_login_finish(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+/// Starts registering a passkey for the logged in user, completing 2FA if
+/// they have it.
+async fn start_passkey_registration(app: &TestApp) -> reqwest::Response {
+    let response = app
+        .post_passkey_register_start(&serde_json::json!({"password": "password123"}))
+        .await;
+
+    if response.status().as_u16() != 206 {
+        return response;
+    }
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    app.post_passkey_register_start(&serde_json::json!({
+        "password": "password123",
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code.as_ref().expose_secret(),
+    }))
+    .await
+}
+
+async fn register_passkey(app: &TestApp, authenticator: &SoftwareAuthenticator) {
+    let options = start_passkey_registration(app)
+        .await
+        .json::<PasskeyCreationOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyCreationOptions");
+
+    let response = app
+        .post_passkey_register_finish(&authenticator.create(&options))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn get_passkeys(app: &TestApp) -> PasskeysResponse {
+    let response = app.get_passkeys().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<PasskeysResponse>()
+        .await
+        .expect("Could not deserialize response body to PasskeysResponse")
+}
+
+async fn start_passkey_login(app: &TestApp) -> PasskeyRequestOptions {
+    let response = app.post_passkey_login_start().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<PasskeyRequestOptions>()
+        .await
+        .expect("Could not deserialize response body to PasskeyRequestOptions")
+}
+
+async fn login_with_passkey(
+    app: &TestApp,
+    authenticator: &mut SoftwareAuthenticator,
+) -> reqwest::Response {
+    let options = start_passkey_login(app).await;
+
+    app.post_passkey_login_finish(&authenticator.get(&options))
+        .await
+}
@@ -0,0 +1,268 @@
+use auth_service::ErrorResponse;
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_return_200_and_send_email_if_user_exists() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The email goes out after the response
+    app.wait_for_emails(emails_sent + 1).await;
+
+    assert!(app.get_token_from_last_email().await.is_some());
+}
+
+#[test_and_cleanup]
+async fn emailed_link_serves_password_reset_page() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.request_password_reset(&random_email).await;
+
+    let link = app.get_link_from_last_email().await.unwrap();
+    let response = app.follow_link(&link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("password-reset-form"));
+}
+
+#[test_and_cleanup]
+async fn should_return_200_without_email_if_user_does_not_exist() {
+    let response = app
+        .post_password_reset_request(&serde_json::json!({"email": get_random_email()}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[test_and_cleanup]
+async fn should_return_429_if_reset_requested_too_soon() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    // The same client asking for another address is throttled too, whether
+    // or not the address is registered
+    let response = app
+        .post_password_reset_request(&serde_json::json!({"email": get_random_email()}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[test_and_cleanup]
+async fn should_reset_password_with_valid_token() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let token = app.request_password_reset(&random_email).await;
+
+    let confirm_body = serde_json::json!({"token": token, "newPassword": "newpassword123"});
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "newpassword123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_end_sessions_and_notify_user() {
+    let random_email = get_random_email();
+
+    let auth_token = app.signup_and_login(&random_email, false).await;
+
+    let token = app.request_password_reset(&random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(
+            &serde_json::json!({"token": token, "newPassword": "newpassword123"}),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The client still holds the refresh cookie from before the reset
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests");
+    let body = String::from_utf8_lossy(&requests.last().expect("No email sent").body).to_string();
+
+    assert!(body.contains("Password reset"));
+    assert!(body.contains("signed out"));
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_token_used_twice() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app.request_password_reset(&random_email).await;
+
+    let confirm_body = serde_json::json!({"token": token, "newPassword": "newpassword123"});
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Password reset token is invalid or has expired".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_invalid_new_password() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app.request_password_reset(&random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({"token": token, "newPassword": "short"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    // The rejected attempt must not have consumed the token //////////////////
+    let response = app
+        .post_password_reset_confirm(
+            &serde_json::json!({"token": token, "newPassword": "newpassword123"}),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_unknown_token() {
+    let test_cases = [
+        serde_json::json!({"token": "a".repeat(64), "newPassword": "newpassword123"}),
+        serde_json::json!({"token": "not-a-token", "newPassword": "newpassword123"}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_return_422_if_malformed_input() {
+    let test_cases = [
+        serde_json::json!({"token": "a".repeat(64)}),
+        serde_json::json!({"newPassword": "newpassword123"}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
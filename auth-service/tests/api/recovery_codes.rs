@@ -0,0 +1,317 @@
+use auth_service::{
+    routes::{RecoveryCodesResponse, UserInfoResponse},
+    utils::constants::{JWT_COOKIE_NAME, MAX_TWO_FA_ATTEMPTS},
+    ErrorResponse,
+};
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body =
+        serde_json::json!({"email": email, "password": "password123", "requires2FA": true});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    app.verify_last_signup().await;
+
+    recovery_codes
+}
+
+#[test_and_cleanup]
+async fn should_return_recovery_codes_on_signup_with_2fa() {
+    let recovery_codes = signup_with_2fa(&app, &get_random_email()).await;
+
+    assert_eq!(recovery_codes.len(), 10);
+}
+
+#[test_and_cleanup]
+async fn should_not_return_recovery_codes_on_signup_without_2fa() {
+    let signup_body = serde_json::json!({"email": get_random_email(), "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let json_body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    assert!(json_body.get("recoveryCodes").is_none());
+}
+
+#[test_and_cleanup]
+async fn should_login_with_recovery_code_once() {
+    let random_email = get_random_email();
+
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let user_info = app
+        .get_user_info()
+        .await
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+
+    assert_eq!(user_info.recovery_codes_remaining, 9);
+
+    // The code is burnt after use ////////////////////////////////////////////
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_accept_code_without_hyphen_in_upper_case() {
+    let random_email = get_random_email();
+
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0].replace('-', "").to_uppercase(),
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_invalidate_login_attempt_after_too_many_wrong_codes() {
+    let random_email = get_random_email();
+
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+        let response = app
+            .post_verify_recovery_code(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id,
+                "recoveryCode": "aaaaa-aaaaa",
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The login attempt was thrown away, so even a valid code no longer works
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The code itself wasn't burnt //////////////////////////////////////////
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_incorrect_login_attempt_id() {
+    let random_email = get_random_email();
+
+    let recovery_codes = signup_with_2fa(&app, &random_email).await;
+
+    app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+        "recoveryCode": recovery_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // A rejected attempt must not burn the code //////////////////////////////
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_invalid_input() {
+    let random_email = get_random_email();
+    let random_uuid = uuid::Uuid::new_v4().to_string();
+
+    let test_cases = [
+        serde_json::json!({"email": "invalid-email", "loginAttemptId": random_uuid, "recoveryCode": "abcde-fghjk"}),
+        serde_json::json!({"email": random_email, "loginAttemptId": "not-a-uuid", "recoveryCode": "abcde-fghjk"}),
+        serde_json::json!({"email": random_email, "loginAttemptId": random_uuid, "recoveryCode": "123456"}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_recovery_code(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_return_422_if_malformed_input() {
+    let random_email = get_random_email();
+    let random_uuid = uuid::Uuid::new_v4().to_string();
+
+    let test_cases = [
+        serde_json::json!({"loginAttemptId": random_uuid, "recoveryCode": "abcde-fghjk"}),
+        serde_json::json!({"email": random_email, "recoveryCode": "abcde-fghjk"}),
+        serde_json::json!({"email": random_email, "loginAttemptId": random_uuid}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_recovery_code(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_regenerate_recovery_codes() {
+    let random_email = get_random_email();
+
+    let old_codes = signup_with_2fa(&app, &random_email).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": old_codes[0],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_codes.len(), 10);
+
+    // Codes from the previous set no longer work /////////////////////////////
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": old_codes[1],
+    });
+
+    let response = app.post_verify_recovery_code(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_regenerating_without_2fa() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_regenerate_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "2FA is not enabled".to_owned()
+    );
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use auth_service::utils::constants::test;
+use macros::test_and_cleanup;
+use secrecy::ExposeSecret;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_send_new_code_that_replaces_old_one() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, true).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+    let old_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    tokio::time::sleep(Duration::from_secs(test::TWO_FA_RESEND_INTERVAL_SECONDS)).await;
+
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        emails_sent + 1
+    );
+
+    let new_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    assert_ne!(new_code, old_code);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": old_code.as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": new_code.as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_429_if_resend_requested_too_soon() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, true).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = app
+        .post_resend_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        emails_sent
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_incorrect_credentials() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, true).await;
+
+    let login_attempt_id = app.start_login(&random_email).await;
+
+    let other_email = get_random_email();
+
+    app.signup(&other_email, true).await;
+
+    let other_login_attempt_id = app.start_login(&other_email).await;
+
+    let test_cases = [
+        // Unknown user
+        serde_json::json!({"email": get_random_email(), "loginAttemptId": login_attempt_id}),
+        // No such login attempt
+        serde_json::json!({"email": random_email, "loginAttemptId": uuid::Uuid::new_v4().to_string()}),
+        // Another user's login attempt
+        serde_json::json!({"email": random_email, "loginAttemptId": other_login_attempt_id}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_resend_2fa(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_invalid_input() {
+    let random_uuid = uuid::Uuid::new_v4().to_string();
+
+    let test_cases = [
+        serde_json::json!({"email": "invalid-email", "loginAttemptId": random_uuid}),
+        serde_json::json!({"email": get_random_email(), "loginAttemptId": "454325433455432"}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_resend_2fa(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_return_422_if_malformed_input() {
+    let test_cases = [
+        serde_json::json!({"email": get_random_email()}),
+        serde_json::json!({"loginAttemptId": uuid::Uuid::new_v4().to_string()}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_resend_2fa(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
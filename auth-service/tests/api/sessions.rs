@@ -0,0 +1,186 @@
+use auth_service::{
+    routes::SessionsResponse,
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use macros::test_and_cleanup;
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_list_current_session_after_login() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    login(&app, &random_email).await;
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse");
+
+    assert_eq!(body.sessions.len(), 1);
+    assert!(body.sessions[0].current);
+    assert_eq!(body.sessions[0].ip_address.as_deref(), Some("127.0.0.1"));
+}
+
+#[test_and_cleanup]
+async fn should_return_200_and_revoke_other_session() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    // Each login starts its own session; the cookies keep the latest one
+    login(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    let body = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse");
+
+    assert_eq!(body.sessions.len(), 2);
+
+    let other = body
+        .sessions
+        .iter()
+        .find(|session| !session.current)
+        .expect("No other session found");
+
+    let response = app.delete_session(&other.id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse");
+
+    assert_eq!(body.sessions.len(), 1);
+    assert!(body.sessions[0].current);
+}
+
+#[test_and_cleanup]
+async fn should_invalidate_token_when_current_session_revoked() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    let (auth_token, _) = login(&app, &random_email).await;
+
+    let body = app
+        .get_sessions()
+        .await
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse");
+
+    let response = app.delete_session(&body.sessions[0].id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_404_if_session_not_found() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    login(&app, &random_email).await;
+
+    let response = app.delete_session(&Uuid::new_v4().to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Session not found".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_revoke_every_session_on_logout_all() {
+    let random_email = get_random_email();
+
+    app.signup(&random_email, false).await;
+
+    let (first_auth_token, first_refresh_token) = login(&app, &random_email).await;
+    let (second_auth_token, _) = login(&app, &random_email).await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in [first_auth_token, second_auth_token] {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_COOKIE_NAME, first_refresh_token
+        ),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+/// Logs in and returns the auth and refresh tokens of the new session.
+async fn login(app: &TestApp, email: &str) -> (String, String) {
+    let login_body = serde_json::json!({"email": email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let find_cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .map(|cookie| cookie.value().to_owned())
+            .expect("No cookie found")
+    };
+
+    (
+        find_cookie(JWT_COOKIE_NAME),
+        find_cookie(REFRESH_COOKIE_NAME),
+    )
+}
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use auth_service::ErrorResponse;
 use macros::test_and_cleanup;
 
@@ -23,66 +25,277 @@ async fn should_return_201_if_valid_input() {
     }
 }
 
+#[test_and_cleanup]
+async fn should_treat_email_addresses_case_insensitively() {
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email.to_uppercase(),
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Only the domain is normalized, mail still goes to the local part as given
+    let (stored_email,): (String,) =
+        sqlx::query_as("SELECT email FROM users WHERE lower(email) = lower($1)")
+            .bind(&random_email)
+            .fetch_one(&app.pg_pool)
+            .await
+            .expect("Failed to get stored email");
+
+    let uppercase_email = random_email.to_uppercase();
+    let (local_part, _) = uppercase_email.split_once('@').unwrap();
+
+    assert_eq!(stored_email, format!("{}@example.com", local_part));
+
+    app.verify_last_signup().await;
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Signing up again with another spelling doesn't create a second account
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "other-password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "other-password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
 #[test_and_cleanup]
 async fn should_return_400_if_invalid_input() {
+    let test_case = serde_json::json!({"email": "invalid-email", "password": "password123", "requires2FA": true});
+
+    let response = app.post_signup(&test_case).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid credentials".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_400_with_violations_if_password_breaks_policy() {
     let random_email = get_random_email();
+    let local_part = random_email.split('@').next().unwrap().to_owned();
 
     let test_cases = [
-        serde_json::json!({"email": "invalid-email", "password": "password123", "requires2FA": true}),
-        serde_json::json!({"email": random_email, "password": "pass", "requires2FA": true}),
+        ("pass".to_owned(), "too_short"),
+        (format!("{}-password", local_part), "contains_email"),
     ];
 
-    for test_case in test_cases.iter() {
-        let response = app.post_signup(test_case).await;
+    for (password, code) in test_cases.iter() {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": random_email,
+                "password": password,
+                "requires2FA": true
+            }))
+            .await;
 
         assert_eq!(
             response.status().as_u16(),
             400,
             "Test case failed: {:?}",
-            test_case
+            password
         );
 
-        assert_eq!(
-            response
-                .json::<ErrorResponse>()
-                .await
-                .expect("Could not deserialize response body to ErrorResponse")
-                .error,
-            "Invalid credentials".to_owned()
-        );
+        let body = response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse");
+
+        assert_eq!(body.error, "Password does not meet the requirements");
+        assert_eq!(body.violations.len(), 1, "Test case failed: {:?}", password);
+        assert_eq!(body.violations[0].code, *code);
+        assert!(!body.violations[0].message.is_empty());
     }
 }
 
 #[test_and_cleanup]
-async fn should_return_409_if_email_already_exists() {
-    let email1 = get_random_email();
+async fn should_return_same_response_if_email_already_exists() {
+    let random_email = get_random_email();
 
     let test_body =
-        serde_json::json!({"email": email1, "password": "password123", "requires2FA": true});
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": true});
 
-    app.post_signup(&test_body).await;
+    let response = app.post_signup(&test_body).await;
 
-    app.post_signup(&test_body).await;
+    assert_eq!(response.status().as_u16(), 201);
 
-    let response = app.post_signup(&test_body).await;
+    let first_body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body");
 
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "another-password",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let second_body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    assert_eq!(first_body["message"], second_body["message"]);
     assert_eq!(
-        response.status().as_u16(),
-        409,
-        "Test case failed: {:?}",
-        test_body
+        first_body["recoveryCodes"].as_array().map(Vec::len),
+        second_body["recoveryCodes"].as_array().map(Vec::len)
     );
+    assert_ne!(first_body["recoveryCodes"], second_body["recoveryCodes"]);
 
-    assert_eq!(
-        response
-            .json::<ErrorResponse>()
-            .await
-            .expect("Could not deserialize response body to ErrorResponse")
-            .error,
-        "User already exists".to_owned()
+    // The account still belongs to the first signup
+    app.verify_last_signup().await;
+
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "another-password"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_take_as_long_if_email_already_exists_with_2fa() {
+    let existing_email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": existing_email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let mut new_durations = Vec::new();
+    let mut existing_durations = Vec::new();
+
+    for _ in 0..3 {
+        for (email, durations) in [
+            (get_random_email(), &mut new_durations),
+            (existing_email.clone(), &mut existing_durations),
+        ] {
+            let started = Instant::now();
+            let response = app
+                .post_signup(&serde_json::json!({
+                    "email": email,
+                    "password": "password123",
+                    "requires2FA": true
+                }))
+                .await;
+
+            durations.push(started.elapsed());
+            assert_eq!(response.status().as_u16(), 201);
+        }
+    }
+
+    // A new account hashes its password and every recovery code; skipping
+    // the codes for an existing one would make it several times faster
+    let new = new_durations.into_iter().min().unwrap();
+    let existing = existing_durations.into_iter().min().unwrap();
+
+    assert!(
+        existing * 2 > new,
+        "existing address took {:?}, new one {:?}",
+        existing,
+        new
     );
 }
 
+#[test_and_cleanup]
+async fn should_email_owner_if_email_already_exists() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    // Move the account to an address that hasn't been emailed yet, so the next
+    // signup isn't throttled
+    let response = app
+        .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let owner_email = get_random_email();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": owner_email,
+            "password": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .get_token_from_last_email()
+        .await
+        .expect("No token found in email");
+
+    let response = app
+        .post_change_email_confirm(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": owner_email,
+            "password": "another-password",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let requests = app
+        .email_server
+        .received_requests()
+        .await
+        .expect("Failed to get received requests");
+    let body = String::from_utf8_lossy(&requests.last().expect("No email sent").body).to_string();
+
+    assert!(body.contains(&owner_email));
+    assert!(body.contains("Signup attempt"));
+    assert!(!body.contains("token="));
+}
+
 #[test_and_cleanup]
 async fn should_return_422_if_malformed_input() {
     let random_email = get_random_email();
@@ -0,0 +1,345 @@
+use auth_service::{
+    domain::{TotpSecret, TOTP_STEP_SECONDS},
+    routes::{ConfirmTotpResponse, EnrollTotpResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use chrono::Utc;
+use macros::test_and_cleanup;
+use reqwest::{cookie::CookieStore, header::COOKIE, Url};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn enroll(app: &TestApp) -> TotpSecret {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(json_body.otpauth_uri.starts_with("otpauth://totp/"));
+
+    TotpSecret::parse(Secret::new(json_body.secret)).expect("Invalid TOTP secret")
+}
+
+fn current_code(secret: &TotpSecret) -> String {
+    secret
+        .generate_code(Utc::now().timestamp() as u64)
+        .unwrap()
+        .as_ref()
+        .expose_secret()
+        .to_owned()
+}
+
+/// Code of the time step before the current one, which is still accepted.
+/// Each step can only be used once, so confirming the enrollment with it
+/// leaves the current code for the next request.
+fn previous_code(secret: &TotpSecret) -> String {
+    secret
+        .generate_code(Utc::now().timestamp() as u64 - TOTP_STEP_SECONDS)
+        .unwrap()
+        .as_ref()
+        .expose_secret()
+        .to_owned()
+}
+
+#[test_and_cleanup]
+async fn should_enable_totp_with_valid_code() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": previous_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let json_body = response
+        .json::<ConfirmTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to ConfirmTotpResponse");
+
+    assert_eq!(json_body.recovery_codes.len(), 10);
+
+    // Enrolling again would replace the active secret ///////////////////////
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[test_and_cleanup]
+async fn should_login_with_totp_code() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": previous_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    // No code is emailed to TOTP users ///////////////////////////////////////
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        emails_sent
+    );
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": current_code(&secret),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[test_and_cleanup]
+async fn should_not_accept_totp_code_twice() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": previous_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let code = current_code(&secret);
+
+    for expected_status in [200, 401] {
+        let response = app
+            .post_login(&serde_json::json!({"email": random_email, "password": "password123"}))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": code,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), expected_status);
+    }
+
+    // The code used to confirm the enrollment is spent as well
+    let response = app
+        .post_totp_disable(&serde_json::json!({"2FACode": previous_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_not_accept_stored_code_for_totp_user() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": previous_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let stored_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    // Skip the unlikely case of the stored code colliding with the TOTP one //
+    if stored_code.as_ref().expose_secret() == &current_code(&secret) {
+        return;
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": stored_code.as_ref().expose_secret(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_incorrect_confirmation_code() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let secret = enroll(&app).await;
+
+    let code = current_code(&secret);
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": wrong_code}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // TOTP is still off, so login doesn't ask for a second factor ////////////
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_confirming_without_enrollment() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": "123456"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "TOTP enrollment has not been started".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_disable_totp_with_valid_code() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": previous_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_totp_disable(&serde_json::json!({"2FACode": current_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_totp_disable(&serde_json::json!({"2FACode": current_code(&secret)}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({"2FACode": "123456"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_totp_disable(&serde_json::json!({"2FACode": "123456"}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_after_logout() {
+    let random_email = get_random_email();
+
+    app.signup_and_login(&random_email, false).await;
+
+    let auth_cookie = app
+        .cookie_jar
+        .cookies(&Url::parse(&app.address).unwrap())
+        .expect("No cookies found");
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Replay the banned JWT //////////////////////////////////////////////////
+    let response = app
+        .http_client
+        .post(format!("{}/totp/enroll", &app.address))
+        .header(COOKIE, auth_cookie)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,73 @@
+use auth_service::routes::UserInfoResponse;
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_return_200_with_user_info() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user_info = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+
+    assert_eq!(user_info.email, random_email);
+    assert!(user_info.email_verified);
+    assert_eq!(user_info.two_fa_method, "none");
+    assert_eq!(user_info.recovery_codes_remaining, 0);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test_and_cleanup]
+async fn should_return_400_after_logout() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_user_info().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
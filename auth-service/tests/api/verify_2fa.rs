@@ -1,10 +1,10 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACodeStore},
+    domain::LoginAttemptId,
     routes::TwoFactorAuthResponse,
-    utils::constants::JWT_COOKIE_NAME,
+    utils::constants::{JWT_COOKIE_NAME, MAX_TWO_FA_ATTEMPTS},
 };
 use macros::test_and_cleanup;
-use secrecy::{ExposeSecret, Secret};
+use secrecy::ExposeSecret;
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -18,6 +18,8 @@ async fn should_return_200_if_correct_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({"email": random_email, "password": "password123"});
 
     let response = app.post_login(&login_body).await;
@@ -33,15 +35,9 @@ async fn should_return_200_if_correct_code() {
 
     let login_attempt_id = json_body.login_attempt_id;
 
-    let two_fa_code = app
-	.two_fa_code_store
-	.read()
-	.await
-	.get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-	.await
-	.unwrap();
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
 
-    let two_fa_code = two_fa_code.1.as_ref().expose_secret();
+    let two_fa_code = two_fa_code.as_ref().expose_secret();
 
     let test_cases = [
 	serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id, "2FACode": two_fa_code}),
@@ -89,6 +85,8 @@ async fn should_return_401_if_incorrect_credentials() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({"email": random_email, "password": "password123"});
 
     let response = app.post_login(&login_body).await;
@@ -104,15 +102,9 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let login_attempt_id = json_body.login_attempt_id;
 
-    let two_fa_code = app
-	.two_fa_code_store
-	.read()
-	.await
-	.get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-	.await
-	.unwrap();
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
 
-    let two_fa_code = two_fa_code.1.as_ref().expose_secret();
+    let two_fa_code = two_fa_code.as_ref().expose_secret();
 
     let incorrect_login_attempt_id = uuid::Uuid::new_v4().to_string();
 
@@ -139,6 +131,8 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({"email": random_email, "password": "password123"});
 
     let response = app.post_login(&login_body).await;
@@ -154,15 +148,9 @@ async fn should_return_401_if_old_code() {
 
     let login_attempt_id = json_body.login_attempt_id;
 
-    let two_fa_code = app
-	.two_fa_code_store
-	.read()
-	.await
-	.get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-	.await
-	.unwrap();
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
 
-    let two_fa_code = two_fa_code.1.as_ref().expose_secret();
+    let two_fa_code = two_fa_code.as_ref().expose_secret();
 
     let response = app.post_login(&login_body).await;
 
@@ -175,8 +163,9 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
+    // The code of the first attempt doesn't answer the new one
     let test_cases = [
-	serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id, "2FACode": two_fa_code}),
+	serde_json::json!({"email": random_email, "loginAttemptId": json_body.login_attempt_id, "2FACode": two_fa_code}),
     ];
 
     for test_case in test_cases.iter() {
@@ -196,6 +185,8 @@ async fn should_return_401_if_same_code_twice() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({"email": random_email, "password": "password123"});
 
     let response = app.post_login(&login_body).await;
@@ -211,15 +202,9 @@ async fn should_return_401_if_same_code_twice() {
 
     let login_attempt_id = json_body.login_attempt_id;
 
-    let two_fa_code = app
-	.two_fa_code_store
-	.read()
-	.await
-	.get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-	.await
-	.unwrap();
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
 
-    let two_fa_code = two_fa_code.1.as_ref().expose_secret();
+    let two_fa_code = two_fa_code.as_ref().expose_secret();
 
     let test_case = serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id, "2FACode": two_fa_code});
 
@@ -239,6 +224,106 @@ async fn should_return_401_if_same_code_twice() {
     assert_eq!(response.status().as_u16(), 401);
 }
 
+#[test_and_cleanup]
+async fn should_return_401_after_too_many_wrong_codes() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({"email": random_email.clone(), "password": "password123", "requires2FA": true});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+	.json::<TwoFactorAuthResponse>()
+	.await
+	.expect("Could not deserialize response body to TwoFactorAuthResponse")
+	.login_attempt_id;
+
+    let two_fa_code = app.get_two_fa_code(&login_attempt_id).await;
+
+    let two_fa_code = two_fa_code.as_ref().expose_secret();
+    let wrong_code = if two_fa_code == "123456" { "654321" } else { "123456" };
+
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+	let response = app
+	    .post_verify_2fa(&serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id, "2FACode": wrong_code}))
+	    .await;
+
+	assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The code was thrown away, so even the right one no longer works
+    let response = app
+	.post_verify_2fa(&serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id, "2FACode": two_fa_code}))
+	.await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[test_and_cleanup]
+async fn should_complete_concurrent_logins_independently() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({"email": random_email.clone(), "password": "password123", "requires2FA": true});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    // Two devices start logging in before either finishes
+    let mut login_attempt_ids = Vec::new();
+
+    for _ in 0..2 {
+	let response = app.post_login(&login_body).await;
+
+	assert_eq!(response.status().as_u16(), 206);
+
+	let login_attempt_id = response
+	    .json::<TwoFactorAuthResponse>()
+	    .await
+	    .expect("Could not deserialize response body to TwoFactorAuthResponse")
+	    .login_attempt_id;
+
+	login_attempt_ids.push(login_attempt_id);
+    }
+
+    let mut two_fa_codes = Vec::new();
+
+    for login_attempt_id in &login_attempt_ids {
+	two_fa_codes.push(app.get_two_fa_code(login_attempt_id).await);
+    }
+
+    // A code only works for the attempt it was sent for
+    if two_fa_codes[0] != two_fa_codes[1] {
+	let response = app
+	    .post_verify_2fa(&serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_ids[0], "2FACode": two_fa_codes[1].as_ref().expose_secret()}))
+	    .await;
+
+	assert_eq!(response.status().as_u16(), 401);
+    }
+
+    for (login_attempt_id, two_fa_code) in login_attempt_ids.iter().zip(&two_fa_codes).rev() {
+	let response = app
+	    .post_verify_2fa(&serde_json::json!({"email": random_email, "loginAttemptId": login_attempt_id, "2FACode": two_fa_code.as_ref().expose_secret()}))
+	    .await;
+
+	assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
 #[test_and_cleanup]
 async fn should_return_422_if_malformed_input() {
     let random_email = get_random_email();
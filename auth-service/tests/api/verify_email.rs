@@ -0,0 +1,189 @@
+use auth_service::ErrorResponse;
+use macros::test_and_cleanup;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[test_and_cleanup]
+async fn should_send_verification_email_on_signup() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+    assert!(app.get_token_from_last_email().await.is_some());
+}
+
+#[test_and_cleanup]
+async fn should_return_403_on_login_if_email_not_verified() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email address not verified".to_owned()
+    );
+
+    app.verify_last_signup().await;
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_verify_email_by_following_link() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let link = app
+        .get_link_from_last_email()
+        .await
+        .expect("No link found in email");
+
+    assert!(link.contains("/verify-email?token="));
+
+    let response = app.follow_link(&link).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({"email": random_email, "password": "password123"});
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_token_used_twice() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app
+        .get_token_from_last_email()
+        .await
+        .expect("No verification token found in email");
+
+    let verify_body = serde_json::json!({ "token": token });
+
+    let response = app.post_verify_email(&verify_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_email(&verify_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email verification token is invalid or has expired".to_owned()
+    );
+}
+
+#[test_and_cleanup]
+async fn should_return_401_if_invalid_token() {
+    let test_cases = [
+        serde_json::json!({"token": "a".repeat(64)}),
+        serde_json::json!({"token": "not-a-token"}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_email(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
+
+#[test_and_cleanup]
+async fn should_return_429_if_resend_requested_too_soon() {
+    let random_email = get_random_email();
+
+    let signup_body =
+        serde_json::json!({"email": random_email, "password": "password123", "requires2FA": false});
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({"email": random_email}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(app.email_server.received_requests().await.unwrap().len(), 1);
+}
+
+#[test_and_cleanup]
+async fn should_return_200_without_email_if_resend_for_unknown_user() {
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({"email": get_random_email()}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[test_and_cleanup]
+async fn should_return_422_if_malformed_input() {
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({"email": get_random_email()}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_email(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Test case failed: {:?}",
+            test_case
+        );
+    }
+}
@@ -1,7 +1,6 @@
 use auth_service::domain::BannedTokenStore;
 use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
 use macros::test_and_cleanup;
-use secrecy::Secret;
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -19,6 +18,8 @@ async fn should_return_200_valid_token() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_last_signup().await;
+
     let login_body = serde_json::json!({
     "email": random_email,
     "password": "password123"
@@ -73,14 +74,11 @@ async fn should_return_401_if_valid_token() {
 
 #[test_and_cleanup]
 async fn should_return_401_if_banned_token() {
-    let token = "banned_token".to_owned();
-    let token = Secret::new(token);
-
     if app
 	.banned_token_store
 	.write()
 	.await
-	.add_banned_token(token.clone())
+	.add_banned_token("banned_token")
 	.await
 	.is_err()
     {
@@ -108,6 +106,58 @@ async fn should_return_401_if_banned_token() {
     );
 }
 
+#[test_and_cleanup]
+async fn should_return_401_if_token_revoked_by_logout() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+    "email": random_email,
+    "password": "password123",
+    "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_last_signup().await;
+
+    let login_body = serde_json::json!({
+    "email": random_email,
+    "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+	.cookies()
+	.find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+	.expect("No JWT cookie found")
+	.value()
+	.to_owned();
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+	.post_verify_token(&serde_json::json!({"token": token}))
+	.await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+	response
+	    .json::<ErrorResponse>()
+	    .await
+	    .expect("Could not deserialize response body to ErrorResponse")
+	    .error,
+	"JWT has been revoked".to_owned()
+    );
+}
+
 #[test_and_cleanup]
 async fn should_return_422_if_malformed_input() {
     let test_cases = [